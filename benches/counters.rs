@@ -15,3 +15,16 @@ fn test_count_c(b: &mut Bencher) {
 fn test_count_lua(b: &mut Bencher) {
     b.iter(|| count("tests/data/lua-big.lua"))
 }
+
+#[bench]
+fn test_count_corpus(b: &mut Bencher) {
+    let dir = std::env::temp_dir().join("loc-bench-corpus");
+    if !dir.exists() {
+        corpus::generate(&dir, Lang::Rust, 200, 0).unwrap();
+    }
+    let paths: Vec<String> = std::fs::read_dir(&dir).unwrap()
+        .flat_map(|sub| std::fs::read_dir(sub.unwrap().path()).unwrap())
+        .map(|f| f.unwrap().path().to_str().unwrap().to_string())
+        .collect();
+    b.iter(|| paths.iter().map(|p| count(p).lines).sum::<u32>())
+}
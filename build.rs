@@ -0,0 +1,38 @@
+// Builds the snippets in tests/conformance into loc, so --conformance works wherever loc
+// is installed and not just from a checkout.  Writes them out as a list of (language
+// directory, file name, contents) for conformance.rs to include.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=tests/conformance");
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("tests").join("conformance");
+    // A package without its tests just has none built in
+    let snippets = snippets(&dir).unwrap_or_default();
+    let mut out = String::from("&[\n");
+    for (lang_dir, name, path) in snippets {
+        out.push_str(&format!("    ({:?}, {:?}, include_bytes!({:?})),\n", lang_dir, name, path));
+    }
+    out.push_str("]\n");
+    fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("conformance.rs"), out).unwrap();
+}
+
+fn snippets(dir: &Path) -> io::Result<Vec<(String, String, String)>> {
+    let mut snippets = vec![];
+    for lang_dir in fs::read_dir(dir)? {
+        let lang_dir = lang_dir?;
+        if !lang_dir.path().is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(lang_dir.path())? {
+            let file = file?;
+            snippets.push((lang_dir.file_name().to_string_lossy().into_owned(),
+                           file.file_name().to_string_lossy().into_owned(),
+                           file.path().to_string_lossy().into_owned()));
+        }
+    }
+    snippets.sort();
+    Ok(snippets)
+}
@@ -0,0 +1,21 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use loc::*;
+
+// The first byte picks the language, the rest is the file.
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let lang = LANGS[data[0] as usize % LANGS.len()];
+    let bytes = &data[1..];
+
+    let count = count_bytes(lang, bytes);
+    assert_eq!(count.lines, count.code + count.comment + count.blank);
+    if let Some(lines) = line_spans(lang, bytes) {
+        assert_eq!(lines.len() as u32, count.lines);
+    }
+    let _ = file_meta(bytes);
+    let _ = conflict_markers(bytes);
+});
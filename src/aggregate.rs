@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::slice;
+
+use loc::*;
+
+use memory::{self, Overflow, Runs, Section};
+use output::lang_name;
+use sort::Sort;
+use worker::FileCount;
+
+type Order = dyn Fn(&FileCount, &FileCount) -> Ordering;
+
+// Every language's total and its files, in the order they're reported in, which is what
+// all the reports are drawn from.  Files that went past --max-memory stay on disk, sorted
+// into runs, and are merged back a language at a time as a report gets to them.
+pub struct ByLang {
+    langs: Vec<(Lang, LangTotal)>,
+    kept: HashMap<Lang, Vec<FileCount>>,
+    spilled: Option<Runs>,
+    order: Box<Order>,
+}
+
+// Languages and the files in each in the order sort puts them in
+pub fn by_lang(filecounts: Vec<FileCount>, overflow: &Overflow, sort: &Sort) -> ByLang {
+    let files = sort.clone();
+    let mut by_lang = ByLang::new(filecounts, overflow, Box::new(move |a, b| files.file_order(a, b)));
+    sort.rows(&mut by_lang.langs, |l| lang_name(&l.0), |l| &l.1);
+    by_lang
+}
+
+// For --ordered: languages in the order the walk first came across them, and their files
+// in the order it found them.  order is each path's place in the walk.
+pub fn by_lang_in_order(filecounts: Vec<FileCount>, overflow: &Overflow, order: HashMap<String, usize>) -> ByLang {
+    let place = move |fc: &FileCount| order.get(&fc.path).cloned().unwrap_or(usize::MAX);
+    let mut by_lang = ByLang::new(filecounts, overflow, Box::new(move |a, b| place(a).cmp(&place(b))));
+    let mut firsts: Vec<FileCount> = by_lang.langs.iter().filter_map(|&(lang, _)| by_lang.files(lang).next()).collect();
+    firsts.sort_by(|a, b| (by_lang.order)(a, b));
+    by_lang.langs.sort_by_key(|&(lang, _)| firsts.iter().position(|fc| fc.lang == lang));
+    by_lang
+}
+
+impl ByLang {
+    fn new(filecounts: Vec<FileCount>, overflow: &Overflow, order: Box<Order>) -> ByLang {
+        let mut totals: HashMap<Lang, LangTotal> = HashMap::new();
+        let mut kept: HashMap<Lang, Vec<FileCount>> = HashMap::new();
+        for fc in filecounts {
+            add(totals.entry(fc.lang).or_default(), &fc);
+            kept.entry(fc.lang).or_default().push(fc);
+        }
+        for files in kept.values_mut() {
+            files.sort_by(|a, b| order(a, b));
+        }
+        let spilled = match *overflow {
+            Overflow::Spill(ref spill) => {
+                match spill.sort(&*order, &mut |fc| add(totals.entry(fc.lang).or_default(), fc)) {
+                    Ok(runs) => runs,
+                    Err(e) => {
+                        println!("Error: --max-memory: sorting the counts set aside on disk: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Overflow::RollUp => None,
+        };
+        ByLang { langs: totals.into_iter().collect(), kept, spilled, order }
+    }
+
+    pub fn langs(&self) -> &[(Lang, LangTotal)] {
+        &self.langs
+    }
+
+    pub fn files(&self, lang: Lang) -> Files<'_> {
+        let spilled = match self.spilled {
+            Some(ref runs) => match runs.sections(lang) {
+                Ok(sections) => sections,
+                Err(e) => {
+                    println!("Error: --max-memory: reading back the counts set aside on disk: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            None => vec![],
+        };
+        Files {
+            kept: self.kept.get(&lang).map_or(&[][..], |files| &files[..]).iter().peekable(),
+            spilled: spilled.into_iter().map(Iterator::peekable).collect(),
+            order: &*self.order,
+        }
+    }
+
+    // Each language with its total and files
+    pub fn iter(&self) -> impl Iterator<Item = (Lang, &LangTotal, Files<'_>)> {
+        self.langs.iter().map(move |&(lang, ref total)| (lang, total, self.files(lang)))
+    }
+
+    // Every file, language by language
+    pub fn all(&self) -> impl Iterator<Item = FileCount> + '_ {
+        self.langs.iter().flat_map(move |&(lang, _)| self.files(lang))
+    }
+
+    pub fn total(&self) -> LangTotal {
+        let mut total = LangTotal::default();
+        for (_, lang_total) in &self.langs {
+            total.files += lang_total.files;
+            total.bytes += lang_total.bytes;
+            total.max_line = total.max_line.max(lang_total.max_line);
+            total.count.merge(&lang_total.count);
+        }
+        total
+    }
+}
+
+// A language's files in order, the ones kept in memory merged with those from each run
+pub struct Files<'a> {
+    kept: Peekable<slice::Iter<'a, FileCount>>,
+    spilled: Vec<Peekable<Section>>,
+    order: &'a Order,
+}
+
+impl<'a> Iterator for Files<'a> {
+    type Item = FileCount;
+
+    fn next(&mut self) -> Option<FileCount> {
+        let order = self.order;
+        // 0 for kept, or 1 + which run
+        let mut next = None;
+        {
+            let mut first: Option<&FileCount> = self.kept.peek().cloned();
+            if first.is_some() {
+                next = Some(0);
+            }
+            for (i, run) in self.spilled.iter_mut().enumerate() {
+                if let Some(fc) = run.peek() {
+                    if first.is_none_or(|first| order(fc, first) == Ordering::Less) {
+                        first = Some(fc);
+                        next = Some(i + 1);
+                    }
+                }
+            }
+        }
+        match next? {
+            0 => self.kept.next().cloned(),
+            i => self.spilled[i - 1].next(),
+        }
+    }
+}
+
+fn add(total: &mut LangTotal, fc: &FileCount) {
+    total.files += fc.files;
+    total.bytes += fc.bytes;
+    total.max_line = total.max_line.max(fc.max_line);
+    total.count.merge(&fc.count);
+}
+
+// --by-dir and --group-by-path: rows keyed by something about the path instead, with a
+// total for each language in them
+pub fn by_key<I, F>(filecounts: I, key: F) -> HashMap<String, Vec<FileCount>>
+    where I: IntoIterator<Item = FileCount>,
+          F: Fn(&str) -> String
+{
+    let mut grouped: HashMap<String, Vec<FileCount>> = HashMap::new();
+    for fc in filecounts {
+        memory::roll_up(grouped.entry(key(&fc.path)).or_default(), fc);
+    }
+    grouped
+}
@@ -0,0 +1,130 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+use tar::{Archive, EntryType};
+
+// --archives: zip and tar files (gzipped or not) get their files counted, in memory, as if
+// they were a directory of them.  Archives inside archives aren't opened.
+
+// Far past any source file anybody wrote, and it keeps a zip bomb from unpacking into all
+// the memory there is.  Anything bigger is skipped, with a warning.
+const MAX_MEMBER: u64 = 16 << 20;
+
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn kind(path: &str) -> Option<Kind> {
+    let path = path.to_lowercase();
+    if path.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if path.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &str) -> bool {
+    kind(path).is_some()
+}
+
+// Calls found with the path and contents of each regular file in the archive at path
+pub fn members<F: FnMut(String, Vec<u8>)>(path: &str, found: F) -> io::Result<()> {
+    match kind(path) {
+        Some(Kind::Zip)   => zip_members(path, &fs::read(path)?, found),
+        Some(Kind::Tar)   => tar_members(path, File::open(path)?, found),
+        Some(Kind::TarGz) => tar_members(path, GzDecoder::new(File::open(path)?), found),
+        None              => Ok(()),
+    }
+}
+
+fn tar_members<R: Read, F: FnMut(String, Vec<u8>)>(path: &str, tarball: R, mut found: F) -> io::Result<()> {
+    for entry in Archive::new(tarball).entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if entry.size() > MAX_MEMBER {
+            too_big(&name, path);
+            continue;
+        }
+        let mut bytes = vec![];
+        entry.read_to_end(&mut bytes)?;
+        found(name, bytes);
+    }
+    Ok(())
+}
+
+// Just enough of the zip format to get the files out: the central directory at the end
+// says where each one is, and they're either stored as is or deflated.  Not zip64, which
+// only archives over 4G need.
+fn zip_members<F: FnMut(String, Vec<u8>)>(path: &str, zip: &[u8], mut found: F) -> io::Result<()> {
+    let bad = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not a zip file loc can read ({})", what));
+    // The end of central directory record is the last 22 bytes, give or take a comment
+    let end = (0..zip.len().saturating_sub(21)).rev()
+        .take(65536)
+        .find(|&i| zip[i..].starts_with(b"PK\x05\x06"))
+        .ok_or_else(|| bad("no end of central directory"))?;
+    let files = le(zip, end + 10, 2)?;
+    let mut at = le(zip, end + 16, 4)?;
+    for _ in 0..files {
+        if !zip.get(at..).is_some_and(|header| header.starts_with(b"PK\x01\x02")) {
+            return Err(bad("broken central directory"));
+        }
+        let method = le(zip, at + 10, 2)?;
+        let compressed = le(zip, at + 20, 4)?;
+        let name_len = le(zip, at + 28, 2)?;
+        let skip = le(zip, at + 30, 2)? + le(zip, at + 32, 2)?;
+        let local = le(zip, at + 42, 4)?;
+        if compressed == 0xFFFF_FFFF || local == 0xFFFF_FFFF {
+            return Err(bad("zip64"));
+        }
+        let name = zip.get(at + 46..at + 46 + name_len).ok_or_else(|| bad("truncated"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_len + skip;
+        if name.ends_with('/') {
+            continue;
+        }
+
+        // Each file's own header has its name again, and maybe different extra fields
+        let start = local + 30 + le(zip, local + 26, 2)? + le(zip, local + 28, 2)?;
+        let raw = zip.get(start..start + compressed).ok_or_else(|| bad("truncated"))?;
+        // The sizes in the directory could be lying, so it's the unpacking that stops
+        let bytes = match method {
+            0 => raw.to_vec(),
+            8 => {
+                let mut bytes = vec![];
+                DeflateDecoder::new(raw).take(MAX_MEMBER + 1).read_to_end(&mut bytes)?;
+                bytes
+            }
+            _ => {
+                eprintln!("Warning: skipping {} in {}: compressed in a way loc can't read", name, path);
+                continue;
+            }
+        };
+        if bytes.len() as u64 > MAX_MEMBER {
+            too_big(&name, path);
+            continue;
+        }
+        found(name, bytes);
+    }
+    Ok(())
+}
+
+fn too_big(name: &str, path: &str) {
+    eprintln!("Warning: skipping {} in {}: over {}M unpacked", name, path, MAX_MEMBER >> 20);
+}
+
+// The little-endian number len bytes long at at
+fn le(zip: &[u8], at: usize, len: usize) -> io::Result<usize> {
+    let bytes = zip.get(at..at + len)
+                   .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a zip file loc can read (truncated)"))?;
+    Ok(bytes.iter().rev().fold(0, |n, &b| (n << 8) | usize::from(b)))
+}
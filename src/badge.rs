@@ -0,0 +1,57 @@
+use std::fs;
+use std::io;
+
+// --badge: a flat shields.io-style badge, "lines of code | 12.3k", to commit next to the
+// README instead of pointing it at a third-party service.
+pub fn write(path: &str, label: &str, code: u32) -> io::Result<()> {
+    fs::write(path, svg(label, &human(code)))
+}
+
+// 950, 12.3k, 4.5M
+fn human(n: u32) -> String {
+    match n {
+        n if n >= 1_000_000 => trim_zero(format!("{:.1}", f64::from(n) / 1e6)) + "M",
+        n if n >= 1_000     => trim_zero(format!("{:.1}", f64::from(n) / 1e3)) + "k",
+        n                   => n.to_string(),
+    }
+}
+
+fn trim_zero(s: String) -> String {
+    s.strip_suffix(".0").map(String::from).unwrap_or(s)
+}
+
+fn svg(label: &str, value: &str) -> String {
+    let (label_w, value_w) = (text_width(label) + 10, text_width(value) + 10);
+    let width = label_w + value_w;
+    let (label, value) = (escape_xml(label), escape_xml(value));
+    format!(r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_w}" height="20" fill="#555"/><rect x="{label_w}" width="{value_w}" height="20" fill="#007ec6"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{value_x}" y="15" fill="#010101" fill-opacity=".3">{value}</text><text x="{value_x}" y="14">{value}</text>
+</g>
+</svg>
+"##,
+            width = width, label_w = label_w, value_w = value_w, label = label, value = value,
+            label_x = f64::from(label_w) / 2.0, value_x = f64::from(label_w) + f64::from(value_w) / 2.0)
+}
+
+// Roughly how wide text is in 11px Verdana, which is what the badge asks for.  Close
+// enough that the text doesn't spill out of its half.
+fn text_width(text: &str) -> u32 {
+    let width: f64 = text.chars().map(|c| match c {
+        'i' | 'l' | 'j' | '.' | ',' | ':' | ';' | '\'' | '|' | '!' => 3.5,
+        ' ' | 'f' | 'r' | 't' | '(' | ')' | '[' | ']' | '/'      => 4.5,
+        'm' | 'w' | 'M' | 'W'                                    => 10.0,
+        c if c.is_ascii_uppercase()                              => 7.5,
+        _                                                        => 7.0,
+    }).sum();
+    width.ceil() as u32
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
+use loc::*;
+use loc::charset::Charset;
+
+use worker::FileCount;
+
+// --cache: remembers each file's counts between runs, keyed by its size and mtime, and by
+// the language and encoding it was counted as, so a second run over a big tree only reads
+// what changed, .loc.toml files included.  There's one cache file per
+// working directory, under $LOC_CACHE_DIR or the usual per-user cache directory.
+//
+// If that can't be written (read-only home, CI containers...) we just keep the cache in
+// memory for this run instead, and say why under --verbose.  A cache should never be
+// the reason a count fails.
+pub struct Cache {
+    enabled: bool,
+    // None when we're only keeping it in memory
+    file: Option<PathBuf>,
+    cwd: PathBuf,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+    dirty: AtomicBool,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    notes: Mutex<Vec<String>>,
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    size: u64,
+    mtime: u128,
+    pub lang: Lang,
+    // From [encodings], None for plain bytes
+    encoding: Option<Charset>,
+    pub count: Count,
+    pub conflicts: usize,
+    pub max_line: u32,
+}
+
+// Bumped whenever the same file could count differently, so old counts are thrown out
+const HEADER: &str = "loc-cache 4";
+
+impl Cache {
+    pub fn disabled() -> Cache {
+        Cache {
+            enabled: false,
+            file: None,
+            cwd: PathBuf::new(),
+            entries: Mutex::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            notes: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn open() -> Cache {
+        let mut cache = Cache::disabled();
+        cache.enabled = true;
+        cache.cwd = env::current_dir().unwrap_or_default();
+        let dir = match cache_dir() {
+            Some(dir) => dir,
+            None => {
+                cache.note("no cache directory (set LOC_CACHE_DIR), keeping it in memory".to_string());
+                return cache;
+            }
+        };
+        let file = dir.join(format!("counts-{:016x}", fnv(cache.cwd.to_string_lossy().as_bytes())));
+        match load(&file) {
+            Ok(entries) => cache.entries = Mutex::new(entries),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => cache.note(format!("couldn't read {}: {}, starting afresh", file.display(), e)),
+        }
+        match writable(&dir) {
+            Ok(()) => cache.file = Some(file),
+            Err(e) => cache.note(format!("{} isn't writable ({}), keeping it in memory", dir.display(), e)),
+        }
+        cache
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // What we have for path, if it hasn't changed since.  counted_as is the language and
+    // [encodings] charset it's counted as, and a count under any other is no good.
+    pub fn get(&self, path: &str, md: &fs::Metadata, counted_as: (Lang, Option<Charset>)) -> Option<Entry> {
+        let (lang, encoding) = counted_as;
+        if !self.enabled {
+            return None;
+        }
+        let entries = self.entries.lock().expect("cache lock");
+        match entries.get(&self.key(path)) {
+            Some(e) if e.size == md.len() && Some(e.mtime) == mtime(md) && e.lang == lang && e.encoding == encoding => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(e.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, path: &str, md: &fs::Metadata, counted_as: (Lang, Option<Charset>), count: &Count, conflicts: usize,
+                  max_line: u32) {
+        let (lang, encoding) = counted_as;
+        let mtime = match mtime(md) {
+            Some(mtime) if self.enabled => mtime,
+            _ => return,
+        };
+        let entry = Entry { size: md.len(), mtime, lang, encoding, count: count.clone(), conflicts, max_line };
+        self.entries.lock().expect("cache lock").insert(self.key(path), entry);
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    // Writes the cache back out, if there's anywhere to write it.
+    pub fn save(&self) {
+        let file = match self.file {
+            Some(ref file) if self.dirty.load(Ordering::Relaxed) => file,
+            _ => return,
+        };
+        if let Err(e) = save(file, &self.entries.lock().expect("cache lock")) {
+            self.note(format!("couldn't save {}: {}", file.display(), e));
+        }
+    }
+
+    // For --verbose
+    pub fn report(&self) -> Vec<String> {
+        if !self.enabled {
+            return vec![];
+        }
+        let mut lines = vec![match self.file {
+            Some(ref file) => format!("cache: {}", file.display()),
+            None => "cache: in memory only".to_string(),
+        }];
+        lines.push(format!("cache: {} hits, {} misses",
+                           self.hits.load(Ordering::Relaxed),
+                           self.misses.load(Ordering::Relaxed)));
+        lines.extend(self.notes.lock().expect("notes lock").iter().map(|n| format!("cache: {}", n)));
+        lines
+    }
+
+    // --only: what the cache has for the files under targets but not under any of except,
+    // which aren't walked or even looked at, as they were when last counted.  Paths come
+    // back under the target they were found in, the way the walk would give them.
+    pub fn counted_under(&self, targets: &[&str], except: &[&str]) -> Vec<FileCount> {
+        let targets: Vec<(&str, PathBuf)> = targets.iter().map(|&t| (t, self.key(t))).collect();
+        let except: Vec<PathBuf> = except.iter().map(|e| self.key(e)).collect();
+        let entries = self.entries.lock().expect("cache lock");
+        let mut counted = vec![];
+        for (key, e) in entries.iter().filter(|&(key, _)| !except.iter().any(|e| key.starts_with(e))) {
+            let under = targets.iter().find_map(|(target, root)| key.strip_prefix(root).ok().map(|rest| (target, rest)));
+            if let Some((target, rest)) = under {
+                counted.push(FileCount {
+                    path: Path::new(target).join(rest).to_string_lossy().into_owned(),
+                    lang: e.lang,
+                    count: e.count.clone(),
+                    meta: None,
+                    files: 1,
+                    bytes: e.size,
+                    max_line: e.max_line,
+                });
+            }
+        }
+        counted
+    }
+
+    // Whether path is somewhere under target
+    pub fn is_under(&self, path: &str, target: &str) -> bool {
+        self.key(path).starts_with(self.key(target))
+    }
+
+    fn note(&self, note: String) {
+        self.notes.lock().expect("notes lock").push(note);
+    }
+
+    fn key(&self, path: &str) -> PathBuf {
+        self.cwd.join(path).components().filter(|c| *c != Component::CurDir).collect()
+    }
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("LOC_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(dir).join("loc"));
+    }
+    if let Some(dir) = env::var_os("LOCALAPPDATA") {
+        return Some(PathBuf::from(dir).join("loc"));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("loc"))
+}
+
+// Creating the directory can work when writing into it doesn't (read-only bind mounts),
+// so actually try writing something.
+fn writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".probe-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+fn mtime(md: &fs::Metadata) -> Option<u128> {
+    md.modified().ok()?.duration_since(UNIX_EPOCH).ok().map(|d| d.as_nanos())
+}
+
+// One file per line: size, mtime, language, encoding (- for none), lines, code, comment,
+// blank, conflicts, the longest line and the path last, since it's the only thing that
+// could have a tab in it.
+fn load(file: &Path) -> io::Result<HashMap<PathBuf, Entry>> {
+    let contents = fs::read_to_string(file)?;
+    let mut lines = contents.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a loc cache, or an old one"));
+    }
+    let mut entries = HashMap::new();
+    for line in lines {
+        let fields: Vec<&str> = line.splitn(11, '\t').collect();
+        let parsed = match fields[..] {
+            [size, mtime, lang, encoding, lines, code, comment, blank, conflicts, max_line, path] => {
+                (|| Some((PathBuf::from(path), Entry {
+                    size: size.parse().ok()?,
+                    mtime: mtime.parse().ok()?,
+                    lang: Lang::from_name(lang)?,
+                    encoding: if encoding == "-" { None } else { Some(Charset::from_label(encoding)?) },
+                    count: Count {
+                        lines: lines.parse().ok()?,
+                        code: code.parse().ok()?,
+                        comment: comment.parse().ok()?,
+                        blank: blank.parse().ok()?,
+                    },
+                    conflicts: conflicts.parse().ok()?,
+                    max_line: max_line.parse().ok()?,
+                })))()
+            }
+            _ => None,
+        };
+        match parsed {
+            Some((path, entry)) => entries.insert(path, entry),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt line")),
+        };
+    }
+    Ok(entries)
+}
+
+fn save(file: &Path, entries: &HashMap<PathBuf, Entry>) -> io::Result<()> {
+    let mut out = String::from(HEADER);
+    out.push('\n');
+    for (path, e) in entries {
+        let path = match path.to_str() {
+            Some(path) if !path.contains('\n') => path,
+            _ => continue,
+        };
+        out.push_str(&format!("{}\t{}\t{:?}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                              e.size, e.mtime, e.lang, e.encoding.map_or("-", |c| c.name()),
+                              e.count.lines, e.count.code, e.count.comment, e.count.blank,
+                              e.conflicts, e.max_line, path));
+    }
+    // Write then rename, so a run that dies halfway doesn't leave half a cache
+    let tmp = file.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&tmp, out)?;
+    fs::rename(&tmp, file)
+}
+
+// Stable across builds, unlike DefaultHasher, which matters for a file name.
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| (h ^ u64::from(b)).wrapping_mul(0x100_0000_01b3))
+}
@@ -0,0 +1,77 @@
+use clap::{App, ArgSettings};
+use serde_json;
+
+use loc::*;
+
+use output::FORMAT_NAMES;
+
+// Bumped whenever a field changes meaning or goes away.  New fields don't bump it, so
+// readers should ignore ones they don't know.
+const SCHEMA: u32 = 1;
+
+// --capabilities: what this build of loc can do, as json, so wrappers and editor plugins
+// can check for a flag or a language instead of scraping --help.
+#[derive(Serialize)]
+struct Capabilities {
+    schema: u32,
+    version: &'static str,
+    languages: Vec<&'static str>,
+    formats: &'static [&'static str],
+    // The cargo features this build has, since they decide which flags exist
+    features: Vec<&'static str>,
+    flags: Vec<Flag>,
+}
+
+#[derive(Serialize)]
+struct Flag {
+    long: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short: Option<char>,
+    takes_value: bool,
+    multiple: bool,
+    // The values it's limited to, when it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    values: Option<Vec<String>>,
+    help: String,
+}
+
+const FEATURES: &[(&str, bool)] = &[
+    ("regex-filters", cfg!(feature = "regex-filters")),
+    ("git",           cfg!(feature = "git")),
+    ("image",         cfg!(feature = "image")),
+    ("archives",      cfg!(feature = "archives")),
+    ("sqlite",        cfg!(feature = "sqlite")),
+    ("tracing",       cfg!(feature = "tracing")),
+];
+
+pub fn print(app: &App) {
+    // clap 2 has no public way to list an App's args, but its builders are pub (if hidden
+    // from the docs), and this way the list can't drift from what's actually parsed.
+    let flags = app.p.flags.iter().map(|f| (&f.b, &f.s, None))
+        .chain(app.p.opts.iter().map(|o| (&o.b, &o.s, Some(&o.v))))
+        .filter(|(b, s, _)| s.long.is_some() && !b.is_set(ArgSettings::Hidden))
+        .map(|(b, s, v)| Flag {
+            long: s.long.unwrap_or_default().to_string(),
+            short: s.short,
+            takes_value: v.is_some(),
+            multiple: b.is_set(ArgSettings::Multiple),
+            values: v.and_then(|v| v.possible_vals.as_ref())
+                     .map(|vals| vals.iter().map(|v| v.to_string()).collect()),
+            help: b.help.unwrap_or_default().to_string(),
+        });
+    let mut flags: Vec<Flag> = flags.collect();
+    flags.sort_by(|a, b| a.long.cmp(&b.long));
+
+    let mut languages: Vec<&str> = LANGS.iter().map(Lang::to_s).collect();
+    languages.sort_by_key(|name| name.to_lowercase());
+
+    let capabilities = Capabilities {
+        schema: SCHEMA,
+        version: crate_version!(),
+        languages,
+        formats: FORMAT_NAMES,
+        features: FEATURES.iter().filter(|&&(_, on)| on).map(|&(name, _)| name).collect(),
+        flags,
+    };
+    println!("{}", serde_json::to_string_pretty(&capabilities).unwrap());
+}
@@ -0,0 +1,30 @@
+// --cocomo: what basic COCOMO reckons the code would take to write from scratch.  Organic
+// mode (a small team on familiar ground), with the same constants and defaults scc uses,
+// so the two agree.  Only as good as lines of code are as a measure of work, which is to
+// say it's for rough conversations, not budgets.
+
+// A year's salary, scc's default: the average US developer's, back when it picked it
+pub const DEFAULT_WAGE: f64 = 56286.0;
+// What a salary costs once everything else an employee needs is paid for
+const OVERHEAD: f64 = 2.4;
+
+#[derive(Debug, Serialize)]
+pub struct Estimate {
+    pub effort_months: f64,
+    // From start to finish
+    pub schedule_months: f64,
+    pub people: f64,
+    pub cost: f64,
+}
+
+pub fn estimate(code: u32, wage: f64) -> Estimate {
+    let kloc = f64::from(code) / 1000.0;
+    let effort = 2.4 * kloc.powf(1.05);
+    let schedule = 2.5 * effort.powf(0.38);
+    Estimate {
+        effort_months: effort,
+        schedule_months: schedule,
+        people: if schedule > 0.0 { effort / schedule } else { 0.0 },
+        cost: effort * wage / 12.0 * OVERHEAD,
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use toml;
+
+use loc::*;
+use loc::charset::Charset;
+
+pub const CONFIG_FILE: &str = ".loc.toml";
+
+// What a .loc.toml can say.  Patterns use .gitignore syntax, relative to the directory
+// the file is in:
+//
+//     exclude = ["generated/", "*.pb.go"]
+//
+//     [languages]
+//     "*.inc" = "PHP"
+//
+//     [encodings]
+//     C = "shift_jis"
+//
+//     [rename-languages]
+//     Cpp = "C++ (all dialects)"
+//
+//     [profiles.ci]
+//     min-comment-ratio = 0.05
+//     exclude-glob = ["vendor/"]
+//
+//     [targets]
+//     backend = ["services/", "libs/"]
+//     default = ["src/", "tests/"]
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct DirConfig {
+    #[serde(default)]
+    exclude: Vec<String>,
+    // pattern -> language name
+    #[serde(default)]
+    languages: HashMap<String, String>,
+    // language name -> encoding label
+    #[serde(default)]
+    encodings: HashMap<String, String>,
+    // name -> flag -> value
+    #[serde(default)]
+    profiles: HashMap<String, toml::value::Table>,
+    // language -> what to call it in reports
+    #[serde(default, rename = "rename-languages")]
+    rename_languages: HashMap<String, String>,
+    // name -> paths, relative to the file's directory
+    #[serde(default)]
+    targets: HashMap<String, Vec<String>>,
+}
+
+// A .loc.toml, compiled.
+struct Layer {
+    exclude: Gitignore,
+    // Longest (most specific) pattern first
+    languages: Vec<(Gitignore, Lang)>,
+    encodings: HashMap<Lang, Charset>,
+}
+
+// .loc.toml files apply to their directory and everything under it, including from
+// directories above the targets.  Like .gitignore, the closest one that says anything
+// about a path wins.  Each directory's file is read the first time we look in there.
+pub struct Configs {
+    cwd: Option<PathBuf>,
+    layers: Mutex<HashMap<PathBuf, Option<Arc<Layer>>>>,
+    found_any: AtomicBool,
+    // --force-ext, which beats any .loc.toml
+    forced_exts: HashMap<String, Lang>,
+    // --force-lang, which beats --force-ext.  Later ones win.
+    forced_paths: Vec<(Gitignore, Lang)>,
+}
+
+impl Configs {
+    pub fn new() -> Configs {
+        Configs {
+            cwd: env::current_dir().ok(),
+            layers: Mutex::new(HashMap::new()),
+            found_any: AtomicBool::new(false),
+            forced_exts: HashMap::new(),
+            forced_paths: vec![],
+        }
+    }
+
+    // pattern is a path prefix ("vendor/patched/") or a .gitignore style glob, relative to
+    // the current directory.
+    pub fn force_lang(&mut self, pattern: &str, lang: Lang) -> Result<(), ignore::Error> {
+        let root = self.cwd.clone().unwrap_or_else(|| PathBuf::from("."));
+        let mut builder = GitignoreBuilder::new(root);
+        builder.add_line(None, pattern.trim_start_matches("./"))?;
+        self.forced_paths.push((builder.build()?, lang));
+        Ok(())
+    }
+
+    pub fn force_ext(&mut self, ext: &str, lang: Lang) {
+        self.forced_exts.insert(ext.trim_start_matches('.').to_lowercase(), lang);
+    }
+
+    pub fn excluded(&self, path: &Path, is_dir: bool) -> bool {
+        for (layer, path) in self.layers_for(path) {
+            match layer.exclude.matched(&path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        false
+    }
+
+    // The language --force-lang, --force-ext or a .loc.toml assigns to path, if any.  Only meaningful
+    // once the walk has been through path's directory.
+    pub fn lang(&self, path: &str) -> Option<Lang> {
+        for &(ref pattern, lang) in self.forced_paths.iter().rev() {
+            // Would panic on absolute paths outside the current directory
+            let p = Path::new(path);
+            if p.is_absolute() && !p.starts_with(pattern.path()) {
+                continue;
+            }
+            if pattern.matched_path_or_any_parents(p, false).is_ignore() {
+                return Some(lang);
+            }
+        }
+        let ext = Path::new(path).extension().and_then(|e| e.to_str());
+        if let Some(&lang) = ext.and_then(|e| self.forced_exts.get(&e.to_lowercase())) {
+            return Some(lang);
+        }
+        if !self.found_any.load(Ordering::Relaxed) {
+            return None;
+        }
+        for (layer, path) in self.layers_for(Path::new(path)) {
+            for &(ref pattern, lang) in &layer.languages {
+                if pattern.matched(&path, false).is_ignore() {
+                    return Some(lang);
+                }
+            }
+        }
+        None
+    }
+
+    // What files of lang at path are encoded in, if a .loc.toml says.
+    pub fn encoding(&self, path: &str, lang: Lang) -> Option<Charset> {
+        if !self.found_any.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.layers_for(Path::new(path)).iter().find_map(|(layer, _)| layer.encodings.get(&lang).cloned())
+    }
+
+    // Every layer that applies to path, closest first, along with path in the form
+    // that layer's patterns match against.
+    fn layers_for(&self, path: &Path) -> Vec<(Arc<Layer>, PathBuf)> {
+        let path: PathBuf = path.components().filter(|c| *c != Component::CurDir).collect();
+        let mut layers = vec![];
+        for dir in path.ancestors().skip(1) {
+            if let Some(layer) = self.layer(dir) {
+                layers.push((layer, path.clone()));
+            }
+        }
+        if path.is_relative() {
+            if let Some(ref cwd) = self.cwd {
+                let abs = cwd.join(&path);
+                for dir in cwd.ancestors().skip(1) {
+                    if let Some(layer) = self.layer(dir) {
+                        layers.push((layer, abs.clone()));
+                    }
+                }
+            }
+        }
+        layers
+    }
+
+    fn layer(&self, dir: &Path) -> Option<Arc<Layer>> {
+        let mut layers = self.layers.lock().unwrap();
+        if let Some(layer) = layers.get(dir) {
+            return layer.clone();
+        }
+        let layer = load(dir).map(Arc::new);
+        if layer.is_some() {
+            self.found_any.store(true, Ordering::Relaxed);
+        }
+        layers.insert(dir.to_path_buf(), layer.clone());
+        layer
+    }
+}
+
+// Splices the flags from --profile NAME into args, ahead of the ones actually given, which
+// take precedence.  The profile comes from the closest .loc.toml at or above the current
+// directory that has one by that name.  Each setting in a profile is a long flag: true
+// for a switch, a value, or a list of values for a flag that can be repeated.
+pub fn with_profile(args: Vec<String>) -> Vec<String> {
+    let name = match profile_name(&args) {
+        Some(name) => name,
+        None => return args,
+    };
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let (path, profile) = match cwd.ancestors().find_map(|dir| find_profile(dir, &name)) {
+        Some(found) => found,
+        None => {
+            println!("Error: no profile named '{}' in any {} here or above", name, CONFIG_FILE);
+            std::process::exit(1);
+        }
+    };
+
+    let given = |flag: &str| args.iter().any(|a| a == flag || a.starts_with(&format!("{}=", flag)));
+    let mut spliced = vec![args[0].clone()];
+    for (key, value) in profile {
+        let flag = format!("--{}", key);
+        if given(&flag) {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Boolean(true) => vec![],
+            toml::Value::Boolean(false) => continue,
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        if values.is_empty() {
+            spliced.push(flag.clone());
+        }
+        for value in values {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(_) | toml::Value::Float(_) => value.to_string(),
+                _ => die(&path, &format!("profile '{}': bad value for {}", name, key)),
+            };
+            spliced.push(flag.clone());
+            spliced.push(value);
+        }
+    }
+    spliced.extend(args.into_iter().skip(1));
+    spliced
+}
+
+// [rename-languages] from the closest .loc.toml at or above the current directory that
+// has any.  Only that one file counts, these don't layer.
+pub fn renamed_langs() -> HashMap<String, String> {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    cwd.ancestors()
+       .filter_map(|dir| parse(&dir.join(CONFIG_FILE)))
+       .map(|config| config.rename_languages)
+       .find(|names| !names.is_empty())
+       .unwrap_or_default()
+}
+
+// A target as given: @name is the set of paths by that name from [targets], anything else
+// is just itself.
+pub fn expand_target(target: &str) -> Vec<String> {
+    match target.strip_prefix('@') {
+        Some(name) => match target_set(name) {
+            Some(targets) => targets,
+            None => {
+                println!("Error: no targets named '{}' in any {} here or above", name, CONFIG_FILE);
+                std::process::exit(1);
+            }
+        },
+        None => vec![target.to_string()],
+    }
+}
+
+// The paths in [targets] NAME from the closest .loc.toml at or above the current directory
+// that has a set by that name, as seen from the current directory.  "default" is what gets
+// counted when no target is given.
+pub fn target_set(name: &str) -> Option<Vec<String>> {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let (dir, targets) = cwd.ancestors().find_map(|dir| {
+        let mut config = parse(&dir.join(CONFIG_FILE))?;
+        config.targets.remove(name).map(|targets| (dir, targets))
+    })?;
+    // How far up the file is
+    let up = cwd.strip_prefix(dir).map(|rest| rest.components().count()).unwrap_or(0);
+    Some(targets.iter().map(|target| {
+        let mut path = PathBuf::new();
+        for _ in 0..up {
+            path.push("..");
+        }
+        path.push(target);
+        path.to_string_lossy().into_owned()
+    }).collect())
+}
+
+// We need the profile before clap gets to look at anything.
+fn profile_name(args: &[String]) -> Option<String> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--profile" {
+            return args.next().cloned();
+        } else if let Some(name) = arg.strip_prefix("--profile=") {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+fn find_profile(dir: &Path, name: &str) -> Option<(PathBuf, toml::value::Table)> {
+    let path = dir.join(CONFIG_FILE);
+    let mut config = parse(&path)?;
+    config.profiles.remove(name).map(|profile| (path, profile))
+}
+
+fn parse(path: &Path) -> Option<DirConfig> {
+    let contents = fs::read_to_string(path).ok()?;
+    match toml::from_str(&contents) {
+        Ok(config) => Some(config),
+        Err(e) => die(path, &e.to_string()),
+    }
+}
+
+fn load(dir: &Path) -> Option<Layer> {
+    let path = dir.join(CONFIG_FILE);
+    let config = parse(&path)?;
+
+    let exclude = match patterns(dir, config.exclude.iter()) {
+        Ok(gi) => gi,
+        Err(e) => die(&path, &e),
+    };
+    let mut languages = vec![];
+    for (pattern, name) in &config.languages {
+        let lang = match Lang::from_name(name) {
+            Some(lang) => lang,
+            None => die(&path, &format!("unknown language '{}'", name)),
+        };
+        match patterns(dir, Some(pattern)) {
+            Ok(gi) => languages.push((pattern.len(), gi, lang)),
+            Err(e) => die(&path, &e),
+        }
+    }
+    languages.sort_by_key(|&(len, _, _)| std::cmp::Reverse(len));
+
+    let mut encodings = HashMap::new();
+    for (name, label) in &config.encodings {
+        let lang = match Lang::from_name(name) {
+            Some(lang) => lang,
+            None => die(&path, &format!("unknown language '{}'", name)),
+        };
+        match Charset::from_label(label) {
+            Some(charset) => encodings.insert(lang, charset),
+            None => die(&path, &format!("unknown encoding '{}' for {}", label, name)),
+        };
+    }
+
+    Some(Layer {
+        exclude,
+        languages: languages.into_iter().map(|(_, gi, lang)| (gi, lang)).collect(),
+        encodings,
+    })
+}
+
+fn patterns<'a, I: IntoIterator<Item = &'a String>>(dir: &Path, patterns: I) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new(dir);
+    for pattern in patterns {
+        builder.add_line(None, pattern).map_err(|e| e.to_string())?;
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn die(path: &Path, e: &str) -> ! {
+    println!("Error: bad config {}: {}", path.display(), e);
+    std::process::exit(1);
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use toml;
+
+use loc::*;
+
+// loc --conformance: tricky snippets (trailing comments, comment markers in strings, nested
+// comments...) with the counts they should get, a directory of them per language.  Each
+// directory has an expected.toml keyed by file name:
+//
+//     ["trailing.rs"]
+//     code = 3
+//
+// known = true marks a snippet loc is known to get wrong, so the report says so instead
+// of failing, and says when one starts passing.
+#[derive(Deserialize)]
+struct Expected {
+    #[serde(default)]
+    code: u32,
+    #[serde(default)]
+    comment: u32,
+    #[serde(default)]
+    blank: u32,
+    #[serde(default)]
+    known: bool,
+}
+
+pub struct Case {
+    pub lang: Lang,
+    // The snippet's file name without its extension, which is what the report's columns
+    // are named after
+    pub name: String,
+    pub path: String,
+    pub expected: Count,
+    pub got: Count,
+    pub known: bool,
+}
+
+impl Case {
+    pub fn passed(&self) -> bool {
+        self.expected == self.got
+    }
+}
+
+// Where the snippets come from: a directory laid out like tests/conformance, or the copy
+// of it built into loc
+pub enum Snippets<'a> {
+    Dir(&'a Path),
+    BuiltIn,
+}
+
+// tests/conformance as it was when loc was built, from build.rs: (language directory, file
+// name, contents), in order
+static BUILT_IN: &[(&str, &str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/conformance.rs"));
+
+impl<'a> Snippets<'a> {
+    fn lang_dirs(&self) -> io::Result<Vec<String>> {
+        let mut lang_dirs = vec![];
+        match *self {
+            Snippets::Dir(dir) => {
+                let entries = fs::read_dir(dir).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dir.display(), e)))?;
+                for entry in entries {
+                    let entry = entry?;
+                    if entry.path().is_dir() {
+                        lang_dirs.push(entry.file_name().to_string_lossy().into_owned());
+                    }
+                }
+                lang_dirs.sort();
+            }
+            Snippets::BuiltIn => {
+                lang_dirs.extend(BUILT_IN.iter().map(|&(lang_dir, _, _)| lang_dir.to_string()));
+                lang_dirs.dedup();
+            }
+        }
+        Ok(lang_dirs)
+    }
+
+    // What the report calls a snippet
+    fn path(&self, lang_dir: &str, name: &str) -> String {
+        match *self {
+            Snippets::Dir(dir) => dir.join(lang_dir).join(name).to_string_lossy().into_owned(),
+            Snippets::BuiltIn => format!("{}/{}", lang_dir, name),
+        }
+    }
+
+    fn read(&self, lang_dir: &str, name: &str) -> io::Result<Vec<u8>> {
+        match *self {
+            Snippets::Dir(_) => fs::read(self.path(lang_dir, name)),
+            Snippets::BuiltIn => BUILT_IN.iter()
+                .find(|&&(d, n, _)| d == lang_dir && n == name)
+                .map(|&(_, _, bytes)| bytes.to_vec())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not built in")),
+        }
+    }
+}
+
+// Counts every snippet, each language's directory in turn
+pub fn run(snippets: &Snippets) -> io::Result<Vec<Case>> {
+    let mut cases = vec![];
+    for lang_dir in snippets.lang_dirs()? {
+        let expected_path = snippets.path(&lang_dir, "expected.toml");
+        let expected = snippets.read(&lang_dir, "expected.toml")
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", expected_path, e)))?;
+        let expected: HashMap<String, Expected> = toml::from_str(&String::from_utf8_lossy(&expected))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", expected_path, e)))?;
+        let mut names: Vec<&String> = expected.keys().collect();
+        names.sort();
+        for name in names {
+            let e = &expected[name];
+            let path = snippets.path(&lang_dir, name);
+            let bytes = snippets.read(&lang_dir, name)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+            let lang = lang_from_ext(&path);
+            cases.push(Case {
+                lang,
+                name: Path::new(name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+                path,
+                expected: Count { code: e.code, comment: e.comment, blank: e.blank, lines: e.code + e.comment + e.blank },
+                got: count_bytes(lang, &bytes),
+                known: e.known,
+            });
+        }
+    }
+    Ok(cases)
+}
@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::SyncSender;
+use std::thread;
+
+use deque;
+use num_cpus;
+
+use cache::Cache;
+use config::Configs;
+#[cfg(feature = "archives")]
+use filter::PathFilter;
+use memory::Budget;
+use sample::{Picker, Sample};
+use throttle::Throttle;
+use timings::Timings;
+use worker::{Counted, FileCount, Work, Worker};
+
+// How to count what the walk found.  Most of it goes straight to each Worker.
+pub struct CountOptions {
+    pub configs: Arc<Configs>,
+    pub meta: bool,
+    pub comment_lang: bool,
+    pub dead_code: bool,
+    pub cache: Arc<Cache>,
+    pub throttle: Arc<Throttle>,
+    pub strict: bool,
+    pub budget: Arc<Budget>,
+    // --format ndjson streaming: where the workers send each file
+    pub stream: Option<SyncSender<FileCount>>,
+    // With symlinks, overlapping targets or a --files-from list the same file can turn up
+    // more than once, so we go by its real path.  Not free, so only when that can happen.
+    pub dedupe: bool,
+    // Remember every path walked, for --skipped
+    pub census: bool,
+    // Remember the walk's order, for --ordered, since workers finish in any old order
+    pub ordered: bool,
+    pub sample: Option<Sample>,
+    #[cfg(feature = "archives")]
+    pub archives: Option<Arc<PathFilter>>,
+}
+
+// What the counting stage learned about the walk along the way
+#[derive(Default)]
+pub struct Walked {
+    pub paths: HashSet<String>,
+    // Each path's place in the walk
+    pub order: HashMap<String, usize>,
+    pub duplicates: usize,
+    // With --sample: how many files were counted, out of how many
+    pub sampled: Option<(usize, usize)>,
+}
+
+// Hands files out to a worker per cpu, and gathers up what they all found
+pub fn count<I: Iterator<Item = String>>(files: I, opts: CountOptions, timings: &mut Timings) -> (Counted, Walked) {
+    let mut workers = vec![];
+    let (workq, stealer) = deque::new();
+    for _ in 0..num_cpus::get() {
+        let worker = Worker {
+            chan: stealer.clone(),
+            configs: opts.configs.clone(),
+            meta: opts.meta,
+            comment_lang: opts.comment_lang,
+            dead_code: opts.dead_code,
+            cache: opts.cache.clone(),
+            throttle: opts.throttle.clone(),
+            strict: opts.strict,
+            stream: opts.stream.clone(),
+            budget: opts.budget.clone(),
+            #[cfg(feature = "archives")]
+            archives: opts.archives.clone(),
+        };
+        workers.push(thread::spawn(|| worker.run()));
+    }
+
+    let mut walked = Walked::default();
+    let mut seen = HashSet::new();
+    let mut picker = opts.sample.map(Picker::new);
+    for path in files {
+        if opts.census {
+            walked.paths.insert(path.clone());
+        }
+        if opts.ordered {
+            let n = walked.order.len();
+            walked.order.insert(path.clone(), n);
+        }
+        if opts.dedupe && !seen.insert(fs::canonicalize(&path).unwrap_or_else(|_| PathBuf::from(&path))) {
+            walked.duplicates += 1;
+            continue;
+        }
+        if let Some(ref mut picker) = picker {
+            picker.offer(path);
+        } else {
+            opts.budget.queue(&path);
+            workq.push(Work::File(path));
+        }
+    }
+    if let (Some(sample), Some(picker)) = (opts.sample, picker) {
+        let (counting, all_files) = picker.picked();
+        let sampled = counting.len();
+        for path in counting {
+            opts.budget.queue(&path);
+            workq.push(Work::File(path));
+        }
+        if sample.applies(all_files) {
+            walked.sampled = Some((sampled, all_files));
+        }
+    }
+    if walked.duplicates > 0 {
+        eprintln!("Warning: {} files were reachable more than once (through symlinks or overlapping \
+                   targets) and were only counted once", walked.duplicates);
+    }
+
+    timings.phase("count");
+    for _ in 0..workers.len() {
+        workq.push(Work::Quit);
+    }
+
+    let mut counted = Counted::default();
+    for worker in workers {
+        counted.merge(worker.join().unwrap());
+    }
+    (counted, walked)
+}
@@ -0,0 +1,154 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+use num_cpus;
+use serde_json::{self, Value};
+
+use loc::*;
+
+use detect;
+use walk::{self, WalkOptions};
+
+// One third-party package and everything counted in its source.
+pub struct Dep {
+    pub name: String,
+    pub version: String,
+    pub files: u32,
+    pub count: Count,
+    // The language most of its code is in
+    pub mostly: Option<Lang>,
+}
+
+// Every package outside the workspace that cargo resolves for the project in dir, counted
+// from where cargo keeps its source (~/.cargo/registry/src, git checkouts, ...).
+pub fn cargo_deps(dir: &str, opts: &WalkOptions) -> io::Result<Vec<Dep>> {
+    let out = Command::new("cargo").args(["metadata", "--format-version", "1"])
+                                   .current_dir(dir)
+                                   .output()?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let msg = stderr.lines().find(|l| l.starts_with("error")).unwrap_or("cargo metadata failed");
+        return Err(io::Error::other(msg.to_string()));
+    }
+    let metadata: Value = serde_json::from_slice(&out.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let members: HashSet<&str> = metadata["workspace_members"].as_array()
+        .map(|ids| ids.iter().filter_map(|id| id.as_str()).collect())
+        .unwrap_or_default();
+    let packages = metadata["packages"].as_array().map(|p| p.as_slice()).unwrap_or(&[]);
+    let found = packages.iter()
+        // path dependencies have no source, and are part of the project as far as we care
+        .filter(|p| !p["source"].is_null() && !members.contains(p["id"].as_str().unwrap_or("")))
+        .filter_map(|p| {
+            let manifest = Path::new(p["manifest_path"].as_str()?);
+            Some((p["name"].as_str()?.to_string(),
+                  p["version"].as_str()?.to_string(),
+                  manifest.parent()?.to_path_buf()))
+        })
+        .collect();
+    Ok(count_all(found, opts))
+}
+
+// Every package under dir/node_modules, nested ones and pnpm's .pnpm store included, each
+// counted without the node_modules inside it.  The same name and version installed twice
+// is counted twice, since it really is on disk twice.
+pub fn npm_deps(dir: &str, opts: &WalkOptions) -> io::Result<Vec<Dep>> {
+    let node_modules = Path::new(dir).join("node_modules");
+    if !node_modules.is_dir() {
+        return Err(io::Error::other(format!("no node_modules in {}, run npm install first", dir)));
+    }
+    let mut found = vec![];
+    let mut seen = HashSet::new();
+    find_npm_packages(&node_modules, &mut found, &mut seen);
+    Ok(count_all(found, opts))
+}
+
+fn find_npm_packages(node_modules: &Path, found: &mut Vec<(String, String, PathBuf)>, seen: &mut HashSet<PathBuf>) {
+    let entries = match fs::read_dir(node_modules) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ".pnpm" {
+            for store in fs::read_dir(&path).into_iter().flatten().filter_map(Result::ok) {
+                find_npm_packages(&store.path().join("node_modules"), found, seen);
+            }
+        } else if name.starts_with('@') {
+            // scoped packages, @scope/name
+            find_npm_packages(&path, found, seen);
+        } else if !name.starts_with('.') {
+            // pnpm (and npm link) leave symlinks to the real thing
+            let real = match fs::canonicalize(&path) {
+                Ok(real) if real.is_dir() => real,
+                _ => continue,
+            };
+            if !seen.insert(real.clone()) {
+                continue;
+            }
+            let manifest = fs::read(real.join("package.json")).ok()
+                .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok());
+            if let Some(manifest) = manifest {
+                let name = manifest["name"].as_str().unwrap_or(&name).to_string();
+                let version = manifest["version"].as_str().unwrap_or("").to_string();
+                found.push((name, version, real.clone()));
+            }
+            find_npm_packages(&real.join("node_modules"), found, seen);
+        }
+    }
+}
+
+// Counts each (name, version, dir) on its own, spread over a thread per cpu.
+fn count_all(packages: Vec<(String, String, PathBuf)>, opts: &WalkOptions) -> Vec<Dep> {
+    let threads = num_cpus::get().max(1);
+    let chunk = packages.len().div_ceil(threads).max(1);
+    thread::scope(|s| {
+        let handles: Vec<_> = packages.chunks(chunk).map(|chunk| s.spawn(move || {
+            chunk.iter().map(|(name, version, dir)| count_dep(name, version, dir, opts)).collect::<Vec<Dep>>()
+        })).collect();
+        handles.into_iter().flat_map(|h| h.join().expect("deps thread")).collect()
+    })
+}
+
+fn count_dep(name: &str, version: &str, dir: &Path, opts: &WalkOptions) -> Dep {
+    let mut dep = Dep {
+        name: name.to_string(),
+        version: version.to_string(),
+        files: 0,
+        count: Count::default(),
+        mostly: None,
+    };
+    let mut code_by_lang: HashMap<Lang, u32> = HashMap::new();
+    for path in walk::files(dir.to_str().expect("to_str"), opts) {
+        // Nested node_modules are packages of their own
+        let rel = Path::new(&path).strip_prefix(dir).unwrap_or(Path::new(&path));
+        if rel.components().any(|c| c.as_os_str() == "node_modules") {
+            continue;
+        }
+        let lang = detect::lang(&opts.configs, &path);
+        if lang == Lang::Unrecognized {
+            continue;
+        }
+        if let Ok(count) = try_count_as(lang, &path) {
+            dep.files += 1;
+            dep.count.merge(&count);
+            *code_by_lang.entry(lang).or_insert(0) += count.code;
+        }
+    }
+    dep.mostly = code_by_lang.into_iter()
+        .filter(|&(_, code)| code > 0)
+        .max_by_key(|&(lang, code)| (code, Reverse(lang.to_s().to_string())))
+        .map(|(lang, _)| lang);
+    dep
+}
+
+fn try_count_as(lang: Lang, path: &str) -> io::Result<Count> {
+    Ok(count_bytes(lang, &fs::read(path)?))
+}
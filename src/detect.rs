@@ -0,0 +1,21 @@
+use loc::*;
+
+use config::Configs;
+use worker::FileCount;
+
+// Which language a path is in: whatever .loc.toml, --force-ext or --force-lang say, then
+// the extension (or the file's name, for Makefile and the like).  Everything that sorts
+// files by language goes through here, so they can't disagree about a file.
+pub fn lang(configs: &Configs, path: &str) -> Lang {
+    configs.lang(path).unwrap_or_else(|| lang_from_ext(path))
+}
+
+// --ambiguous: the counted files whose language was a guess.  Anything a .loc.toml or
+// --force-ext settled isn't one.
+pub fn ambiguous<I: IntoIterator<Item = FileCount>>(configs: &Configs, filecounts: I) -> Vec<(String, Lang, Guess)> {
+    filecounts.into_iter()
+              .filter(|fc| configs.lang(&fc.path).is_none())
+              .map(|fc| { let guess = guess(&fc.path); (fc.path, fc.lang, guess) })
+              .filter(|&(_, _, g)| g != Guess::Certain)
+              .collect()
+}
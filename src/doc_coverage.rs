@@ -0,0 +1,180 @@
+// --doc-coverage: how many of a Rust crate's pub items have doc comments, module by module.
+// Goes line by line rather than parsing, like the rest of loc, so it can be fooled by items
+// in strings or macros, but it's right for ordinary code.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DocCoverage {
+    pub items: u32,
+    pub documented: u32,
+}
+
+impl DocCoverage {
+    pub fn merge(&mut self, o: &DocCoverage) {
+        self.items += o.items;
+        self.documented += o.documented;
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.items == 0 { 100.0 } else { 100.0 * f64::from(self.documented) / f64::from(self.items) }
+    }
+}
+
+pub struct CrateDocs {
+    pub name: String,
+    // By module path, crate, crate::walk, crate::git::revs...
+    pub modules: Vec<(String, DocCoverage)>,
+}
+
+impl CrateDocs {
+    pub fn total(&self) -> DocCoverage {
+        let mut total = DocCoverage::default();
+        for (_, cov) in &self.modules {
+            total.merge(cov);
+        }
+        total
+    }
+}
+
+// Items pub to the world, that is.  pub(crate) and the like aren't api, and a pub use
+// re-exports something documented (or not) where it's defined.
+const ITEMS: &[&str] = &["fn", "struct", "enum", "trait", "type", "const", "static", "mod", "union", "macro"];
+
+fn is_pub_item(line: &str) -> bool {
+    let rest = match line.strip_prefix("pub ") {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let kind = rest.split_whitespace()
+        .find(|w| !matches!(*w, "async" | "unsafe" | "extern" | "default") && !w.starts_with('"'));
+    matches!(kind, Some(kind) if ITEMS.contains(&kind))
+}
+
+fn brackets(line: &str) -> i32 {
+    line.chars().map(|c| match c { '[' => 1, ']' => -1, _ => 0 }).sum()
+}
+
+// The pub items in one file, and how many have an outer doc comment (///, /** */ or
+// #[doc = ...]) above them.  Attributes, blank lines and plain comments can sit between
+// the docs and the item, like rustdoc allows.
+pub fn scan(text: &str) -> DocCoverage {
+    let mut cov = DocCoverage::default();
+    let mut doc = false;
+    // Some(whether it's a doc comment) while inside a /* */ comment
+    let mut in_block: Option<bool> = None;
+    // Unclosed brackets of an attribute that runs over several lines
+    let mut in_attr = 0;
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(is_doc) = in_block {
+            if line.contains("*/") {
+                in_block = None;
+                doc |= is_doc;
+            }
+            continue;
+        }
+        if in_attr > 0 {
+            in_attr += brackets(line);
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("///") && !line.starts_with("////") {
+            doc = true;
+        } else if line.starts_with("//") {
+            // Plain comments, and //! which documents the module it's in instead
+        } else if let Some(rest) = line.strip_prefix("/*") {
+            let is_doc = rest.starts_with('*') && !rest.starts_with("**") && !rest.starts_with("*/");
+            if rest.contains("*/") {
+                doc |= is_doc;
+            } else {
+                in_block = Some(is_doc);
+            }
+        } else if line.starts_with("#[") {
+            doc |= line.starts_with("#[doc");
+            in_attr = brackets(line);
+        } else {
+            if is_pub_item(line) {
+                cov.items += 1;
+                if doc {
+                    cov.documented += 1;
+                }
+            }
+            doc = false;
+        }
+    }
+    cov
+}
+
+// The module a file under a crate's src/ is: lib.rs and main.rs are the crate itself,
+// foo/mod.rs and foo.rs are both crate::foo.
+pub fn module_path(in_src: &Path) -> String {
+    let mut parts: Vec<String> = in_src.with_extension("").iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    if parts.last().map(|p| p == "mod").unwrap_or(false) || parts == ["lib"] || parts == ["main"] {
+        parts.pop();
+    }
+    parts.insert(0, String::from("crate"));
+    parts.join("::")
+}
+
+// The name in a Cargo.toml's [package], without pulling in a toml parser.
+fn package_name(manifest: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some(value) = line.strip_prefix("name").map(str::trim_start).and_then(|l| l.strip_prefix('=')) {
+                return Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    None
+}
+
+// Every .rs file in paths that's in some crate's src/, grouped by crate and module.
+// Tests, examples and benches aren't anybody's api, so they're left out.
+pub fn by_crate<I: IntoIterator<Item = String>>(paths: I) -> Vec<CrateDocs> {
+    // dir -> the name of the crate there, None when there's no crate (or just a workspace)
+    let mut crate_dirs: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut crates: HashMap<String, HashMap<String, DocCoverage>> = HashMap::new();
+    for path in paths {
+        let path = Path::new(&path);
+        if path.extension().map(|e| e != "rs").unwrap_or(true) {
+            continue;
+        }
+        let (dir, name) = match path.ancestors().skip(1).find_map(|dir| {
+            let name = crate_dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+                fs::read_to_string(dir.join("Cargo.toml")).ok().and_then(|manifest| package_name(&manifest))
+            });
+            name.clone().map(|name| (dir.to_path_buf(), name))
+        }) {
+            Some(found) => found,
+            None => continue,
+        };
+        let in_src = match path.strip_prefix(dir.join("src")) {
+            Ok(in_src) => in_src,
+            Err(_) => continue,
+        };
+        let text = match fs::read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(_) => continue,
+        };
+        crates.entry(name).or_default()
+              .entry(module_path(in_src)).or_default()
+              .merge(&scan(&text));
+    }
+    let mut crates: Vec<CrateDocs> = crates.into_iter().map(|(name, modules)| {
+        let mut modules: Vec<(String, DocCoverage)> = modules.into_iter().filter(|(_, cov)| cov.items > 0).collect();
+        modules.sort_by(|a, b| a.0.cmp(&b.0));
+        CrateDocs { name, modules }
+    }).collect();
+    crates.sort_by(|a, b| a.name.cmp(&b.name));
+    crates
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use loc::*;
+
+use config::Configs;
+use detect;
+
+// --estimate: guess line counts from file sizes without reading anything.
+#[derive(Default)]
+pub struct SizeEstimate {
+    pub files: u32,
+    pub bytes: u64,
+    pub lines: u64,
+}
+
+pub fn estimate<I: Iterator<Item = String>>(paths: I, configs: &Configs) -> HashMap<Lang, SizeEstimate> {
+    let mut by_lang: HashMap<Lang, SizeEstimate> = HashMap::new();
+    for path in paths {
+        let lang = detect::lang(configs, &path);
+        if lang == Lang::Unrecognized {
+            continue;
+        }
+        let size = match fs::metadata(&path) {
+            Ok(md) => md.len(),
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {}", path, e);
+                continue;
+            }
+        };
+        let e = by_lang.entry(lang).or_default();
+        e.files += 1;
+        e.bytes += size;
+        e.lines += (size as f64 / bytes_per_line(lang)).round() as u64;
+    }
+    by_lang
+}
+
+// --count-files-only: files per language, and per extension within that, going by names
+// alone.  Files without an extension are keyed by their name (Makefile, Dockerfile).
+pub fn file_counts<I: Iterator<Item = String>>(paths: I, configs: &Configs) -> HashMap<Lang, HashMap<String, u32>> {
+    let mut by_lang: HashMap<Lang, HashMap<String, u32>> = HashMap::new();
+    for path in paths {
+        let lang = configs.lang(&path).unwrap_or_else(|| lang_from_path(&path));
+        if lang == Lang::Unrecognized {
+            continue;
+        }
+        let p = Path::new(&path);
+        let ext = match p.extension() {
+            Some(ext) => format!(".{}", ext.to_string_lossy().to_lowercase()),
+            None => p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        };
+        *by_lang.entry(lang).or_default().entry(ext).or_insert(0) += 1;
+    }
+    by_lang
+}
+
+// Average bytes per line, measured on about 15M lines of code: the crates in a cargo
+// registry, the Rust toolchain's sources, Python's standard library and /usr/include and
+// /usr/share of a Debian install.  Each is the language's total bytes over its total
+// lines, leaving out files averaging over 200 bytes a line (minified or generated).  A
+// language that didn't turn up 10,000 lines there gets the figure for all of them.
+fn bytes_per_line(lang: Lang) -> f64 {
+    match lang {
+        Lang::Rust                            => 39.6,
+        Lang::C                               => 34.9,
+        Lang::CCppHeader                      => 36.8,
+        Lang::Cpp                             => 34.8,
+        Lang::Python                          => 38.0,
+        Lang::Perl                            => 21.6,
+        Lang::JavaScript                      => 39.1,
+        Lang::Php                             => 32.0,
+        Lang::BourneShell                     => 28.0,
+        Lang::Tcl                             => 29.0,
+        Lang::Sql                             => 31.4,
+        Lang::Css                             => 23.3,
+        Lang::VimScript                       => 43.1,
+        Lang::Makefile                        => 42.5,
+        Lang::CMake                           => 40.7,
+        Lang::Markdown                        => 63.0,
+        Lang::XML                             => 40.7,
+        Lang::Json                            => 44.8,
+        Lang::Yaml                            => 34.4,
+        Lang::Toml                            => 28.6,
+        _                                     => 36.7,
+    }
+}
@@ -0,0 +1,338 @@
+use std::path::Path;
+
+use clap::{Arg, ArgMatches};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+#[cfg(feature = "archives")]
+use archive;
+
+#[cfg(feature = "regex-filters")]
+use regex::Regex;
+
+// Everything from --include / --exclude and --include-glob / --exclude-glob, compiled
+// once and shared (behind an Arc) with the walker, which checks it as it goes so that
+// excluded directories are never descended into.  Without the regex-filters feature
+// only the glob flags exist.
+pub struct PathFilter {
+    #[cfg(feature = "regex-filters")]
+    include: Option<Regex>,
+    #[cfg(feature = "regex-filters")]
+    exclude: Option<Regex>,
+    include_globs: Option<Gitignore>,
+    exclude_globs: Option<Gitignore>,
+    // --archives: an archive only has to get past the excludes, since the includes are
+    // for the files in it
+    #[cfg(feature = "archives")]
+    archives: bool,
+}
+
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = vec![
+        Arg::with_name("exclude-glob")
+            .required(false)
+            .multiple(true)
+            .number_of_values(1)
+            .long("exclude-glob")
+            .value_name("GLOB")
+            .takes_value(true)
+            .help("Exclude files and directories matching GLOB, using .gitignore syntax"),
+        Arg::with_name("include-glob")
+            .required(false)
+            .multiple(true)
+            .number_of_values(1)
+            .long("include-glob")
+            .value_name("GLOB")
+            .takes_value(true)
+            .help("Only count files matching GLOB, using .gitignore syntax"),
+        Arg::with_name("defaults")
+            .required(false)
+            .long("defaults")
+            .takes_value(false)
+            .help("Skip build output and minified files: target/, build/, dist/, .venv/, __pycache__/ \
+                   and *.min.*, even when they aren't gitignored"),
+        Arg::with_name("no-default-excludes")
+            .required(false)
+            .long("no-default-excludes")
+            .takes_value(false)
+            .help("Count what --defaults skips after all, e.g. when a --profile turns it on"),
+    ];
+    args.extend(regex_args());
+    args
+}
+
+#[cfg(feature = "regex-filters")]
+fn regex_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("exclude")
+            .required(false)
+            .multiple(true)
+            .long("exclude")
+            .value_name("REGEX")
+            .takes_value(true)
+            .help("Rust regex of files to exclude"),
+        Arg::with_name("include")
+            .required(false)
+            .multiple(true)
+            .long("include")
+            .value_name("REGEX")
+            .takes_value(true)
+            .help("Rust regex matching files to include. Anything not matched will be excluded"),
+        Arg::with_name("group-by")
+            .required(false)
+            .long("group-by")
+            .value_name("REGEX")
+            .takes_value(true)
+            .conflicts_with_all(&["files", "by-dir", "group-by-path", "format", "output", "template"])
+            .help("Show stats for each value REGEX's capture group takes in the paths, like \
+                   'services/([^/]+)/' for a row per service, and the language most of them are \
+                   written in"),
+    ]
+}
+
+#[cfg(not(feature = "regex-filters"))]
+fn regex_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![]
+}
+
+impl PathFilter {
+    pub fn from_matches(matches: &ArgMatches) -> PathFilter {
+        PathFilter {
+            #[cfg(feature = "regex-filters")]
+            include: combined_regex(matches, "include"),
+            #[cfg(feature = "regex-filters")]
+            exclude: combined_regex(matches, "exclude"),
+            include_globs: globs(matches, "include-glob"),
+            exclude_globs: exclude_globs(matches),
+            #[cfg(feature = "archives")]
+            archives: matches.is_present("archives"),
+        }
+    }
+
+    // For the walker.  Includes only ever apply to files: a directory that doesn't match
+    // an include can still have files in it that do.
+    pub fn keep_entry(&self, path: &Path, is_dir: bool) -> bool {
+        if self.excluded(path, is_dir) {
+            return false;
+        }
+        is_dir || self.included(path) || self.opened(path)
+    }
+
+    // For file paths that don't come from walking a directory tree (e.g. git ls-tree),
+    // so we also have to check whether any parent directory is excluded.
+    #[cfg_attr(not(any(feature = "git", feature = "archives")), allow(dead_code))]
+    pub fn is_match(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        if path.ancestors().skip(1).any(|dir| !dir.as_os_str().is_empty() && self.excluded(dir, true)) {
+            return false;
+        }
+        self.keep_entry(path, false)
+    }
+
+    fn excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if let Some(ref globs) = self.exclude_globs {
+            if globs.matched(path, is_dir).is_ignore() {
+                return true;
+            }
+        }
+        self.regex_excluded(path)
+    }
+
+    fn included(&self, path: &Path) -> bool {
+        if let Some(ref globs) = self.include_globs {
+            if !globs.matched(path, false).is_ignore() {
+                return false;
+            }
+        }
+        self.regex_included(path)
+    }
+
+    #[cfg(feature = "archives")]
+    fn opened(&self, path: &Path) -> bool {
+        self.archives && path.to_str().is_some_and(archive::is_archive)
+    }
+
+    #[cfg(not(feature = "archives"))]
+    fn opened(&self, _path: &Path) -> bool {
+        false
+    }
+
+    #[cfg(feature = "regex-filters")]
+    fn regex_excluded(&self, path: &Path) -> bool {
+        match (&self.exclude, path.to_str()) {
+            (Some(exclude), Some(path)) => exclude.is_match(path),
+            _ => false,
+        }
+    }
+
+    #[cfg(feature = "regex-filters")]
+    fn regex_included(&self, path: &Path) -> bool {
+        match (&self.include, path.to_str()) {
+            (Some(include), Some(path)) => include.is_match(path),
+            _ => true,
+        }
+    }
+
+    #[cfg(not(feature = "regex-filters"))]
+    fn regex_excluded(&self, _path: &Path) -> bool {
+        false
+    }
+
+    #[cfg(not(feature = "regex-filters"))]
+    fn regex_included(&self, _path: &Path) -> bool {
+        true
+    }
+}
+
+// --defaults: build output and the like, which is usually gitignored anyway but still
+// gets counted with -u, or in a tarball, or a repo that forgot.  Your --exclude-glob comes
+// after these, so "!build/" gets one back.
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    "target/", "build/", "dist/", ".venv/", "__pycache__/", "*.min.*",
+];
+
+fn exclude_globs(matches: &ArgMatches) -> Option<Gitignore> {
+    let defaults = if matches.is_present("defaults") && !matches.is_present("no-default-excludes") {
+        DEFAULT_EXCLUDES
+    } else {
+        &[][..]
+    };
+    let globs = matches.values_of("exclude-glob").into_iter().flatten();
+    build_globs("exclude-glob", defaults.iter().cloned().chain(globs))
+}
+
+fn globs(matches: &ArgMatches, name: &str) -> Option<Gitignore> {
+    build_globs(name, matches.values_of(name)?)
+}
+
+fn build_globs<'a, I: Iterator<Item = &'a str>>(name: &str, globs: I) -> Option<Gitignore> {
+    let mut globs = globs.peekable();
+    globs.peek()?;
+    let mut builder = GitignoreBuilder::new("");
+    for glob in globs {
+        if let Err(e) = builder.add_line(None, glob) {
+            println!("Error processing {} '{}': {}", name, glob, e);
+            std::process::exit(1);
+        }
+    }
+    match builder.build() {
+        Ok(gi) => Some(gi),
+        Err(e) => {
+            println!("Error processing {}: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(feature = "regex-filters")]
+fn combined_regex(matches: &ArgMatches, name: &str) -> Option<Regex> {
+    let regex_strs = matches.values_of(name)?;
+    let combined_regex = regex_strs.map(|r| format!("({})", r)).collect::<Vec<String>>().join("|");
+    match Regex::new(&combined_regex) {
+        Ok(r) => Some(r),
+        Err(e) => {
+            println!("Error processing {} regex: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Private keys, certificates, keystores and .env files.  Their line counts still go into
+// the totals, but we keep their names out of per-file output since that tends to get
+// pasted into issues and chat.
+pub fn is_sensitive(path: &str) -> bool {
+    let path = Path::new(path);
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.to_lowercase(),
+        None => return false,
+    };
+    match name.as_str() {
+        "id_rsa" | "id_dsa" | "id_ecdsa" | "id_ed25519" | ".netrc" | ".pgpass"
+      | "credentials.json" | "secrets.json" | "secrets.yml" | "secrets.yaml" => return true,
+        ".env.example" | ".env.sample" | ".env.template" => return false,
+        _ => {}
+    }
+    if name == ".env" || name.starts_with(".env.") {
+        return true;
+    }
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()) {
+        Some(ext) => matches!(ext.as_str(), "pem" | "key" | "p12" | "pfx" | "jks" | "keystore" | "gpg" | "asc"),
+        None => false,
+    }
+}
+
+// --group-by-path 'src/*' 'tests/*': totals for the files under each pattern (.gitignore
+// syntax, from the top of the target), a lighter way to split a repo up than labels.
+// A file goes under the first pattern it matches.
+pub struct PathGroups {
+    groups: Vec<(String, Gitignore)>,
+}
+
+// Where the files no pattern matched go
+pub const OTHER_GROUP: &str = "(other)";
+
+impl PathGroups {
+    pub fn from_matches(matches: &ArgMatches) -> Option<PathGroups> {
+        let groups = matches.values_of("group-by-path")?
+            .map(|pattern| (pattern.to_string(), build_globs("group-by-path", Some(pattern).into_iter()).expect("one glob")))
+            .collect();
+        Some(PathGroups { groups })
+    }
+
+    pub fn group(&self, path: &str, targets: &[&str]) -> &str {
+        let path = Path::new(path);
+        let rest = targets.iter().find_map(|target| path.strip_prefix(target).ok()).unwrap_or(path);
+        self.groups.iter()
+            .find(|(_, glob)| glob.matched_path_or_any_parents(rest, false).is_ignore())
+            .map(|(pattern, _)| pattern.as_str())
+            .unwrap_or(OTHER_GROUP)
+    }
+}
+
+// --group-by 'services/([^/]+)/': totals for each thing the regex's capture group matched
+// in the paths (from the top of the target), for splits that don't follow the directory
+// layout.  Files it doesn't match go under (other).
+pub struct RegexGroups {
+    #[cfg(feature = "regex-filters")]
+    regex: Regex,
+}
+
+impl RegexGroups {
+    #[cfg(feature = "regex-filters")]
+    pub fn from_matches(matches: &ArgMatches) -> Option<RegexGroups> {
+        let regex = matches.value_of("group-by")?;
+        let regex = match Regex::new(regex) {
+            Ok(regex) => regex,
+            Err(e) => {
+                println!("Error processing group-by regex: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if regex.captures_len() < 2 {
+            println!("Error: --group-by needs a capture group for what to group by, like 'services/([^/]+)/'");
+            std::process::exit(1);
+        }
+        Some(RegexGroups { regex })
+    }
+
+    #[cfg(not(feature = "regex-filters"))]
+    pub fn from_matches(_matches: &ArgMatches) -> Option<RegexGroups> {
+        None
+    }
+
+    // What the first capture group that took part in the match matched
+    #[cfg(feature = "regex-filters")]
+    pub fn group(&self, path: &str, targets: &[&str]) -> String {
+        let rest = targets.iter()
+            .find_map(|target| Path::new(path).strip_prefix(target).ok())
+            .and_then(Path::to_str)
+            .unwrap_or(path);
+        self.regex.captures(rest)
+            .and_then(|caps| caps.iter().skip(1).flatten().next().map(|m| m.as_str().to_string()))
+            .unwrap_or_else(|| OTHER_GROUP.to_string())
+    }
+
+    #[cfg(not(feature = "regex-filters"))]
+    pub fn group(&self, _path: &str, _targets: &[&str]) -> String {
+        OTHER_GROUP.to_string()
+    }
+}
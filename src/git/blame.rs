@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use num_cpus;
+
+use loc::*;
+
+use super::git;
+use walk;
+
+const MONTH_SECS: i64 = 2_629_746; // 365.2425 days / 12
+
+// Code lines last touched less than 6 months ago, 6 to 24 months ago, and longer ago
+// than that.
+#[derive(Default, Clone)]
+pub struct AgeBuckets {
+    pub buckets: [u32; 3],
+}
+
+impl AgeBuckets {
+    fn add(&mut self, age_secs: i64) {
+        let i = match age_secs / MONTH_SECS {
+            0..=5  => 0,
+            6..=23 => 1,
+            _      => 2,
+        };
+        self.buckets[i] += 1;
+    }
+
+    pub fn merge(&mut self, o: &AgeBuckets) {
+        for (b, ob) in self.buckets.iter_mut().zip(o.buckets.iter()) {
+            *b += ob;
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.buckets.iter().sum()
+    }
+}
+
+// Code lines per author in one group (a language or a directory).
+#[derive(Default)]
+pub struct Authorship {
+    pub lines: HashMap<String, u32>,
+}
+
+impl Authorship {
+    pub fn merge(&mut self, o: &Authorship) {
+        for (author, n) in &o.lines {
+            *self.lines.entry(author.clone()).or_insert(0) += n;
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.lines.values().sum()
+    }
+
+    // Authors by lines owned, most first.
+    pub fn ranked(&self) -> Vec<(&str, u32)> {
+        let mut ranked: Vec<(&str, u32)> = self.lines.iter().map(|(a, &n)| (&**a, n)).collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        ranked
+    }
+
+    // The fewest authors who between them own more than half the code.
+    pub fn bus_factor(&self) -> usize {
+        let total = self.total();
+        let mut owned = 0;
+        for (i, (_, n)) in self.ranked().into_iter().enumerate() {
+            owned += n;
+            if owned * 2 > total {
+                return i + 1;
+            }
+        }
+        0
+    }
+}
+
+struct BlameLine {
+    time: i64,
+    author: String,
+    content: Vec<u8>,
+}
+
+// Buckets code lines by age, per language.  Also returns how many files git doesn't
+// know about (untracked, or outside a repo) and were skipped.
+pub fn ages(paths: Vec<String>) -> (HashMap<Lang, AgeBuckets>, usize) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let (per_thread, untracked) = blame_code_lines(paths, |ages: &mut HashMap<Lang, AgeBuckets>, _, lang, lines| {
+        let buckets = ages.entry(lang).or_default();
+        for line in lines {
+            buckets.add(now - line.time);
+        }
+    });
+
+    let mut ages: HashMap<Lang, AgeBuckets> = HashMap::new();
+    for thread_ages in per_thread {
+        for (lang, buckets) in thread_ages {
+            ages.entry(lang).or_default().merge(&buckets);
+        }
+    }
+    (ages, untracked)
+}
+
+// Who owns the code lines, grouped by language, or by_dir by the top-level directory
+// under each target.
+pub fn authorship(paths: Vec<String>, targets: &[&str], by_dir: bool) -> (HashMap<String, Authorship>, usize) {
+    let (per_thread, untracked) = blame_code_lines(paths, |groups: &mut HashMap<String, Authorship>, path, lang, lines| {
+        let key = if by_dir {
+            walk::dir_at_depth(path, targets, 1)
+        } else {
+            String::from(::output::lang_name(&lang))
+        };
+        let group = groups.entry(key).or_default();
+        for line in lines {
+            *group.lines.entry(line.author.clone()).or_insert(0) += 1;
+        }
+    });
+
+    let mut groups: HashMap<String, Authorship> = HashMap::new();
+    for thread_groups in per_thread {
+        for (key, group) in thread_groups {
+            groups.entry(key).or_default().merge(&group);
+        }
+    }
+    (groups, untracked)
+}
+
+// Blames every file, a few at a time, and hands each file's code lines to on_file along
+// with that thread's accumulator.  Returns every thread's accumulator, plus how many
+// files couldn't be blamed.
+fn blame_code_lines<T, F>(paths: Vec<String>, on_file: F) -> (Vec<T>, usize)
+    where T: Default + Send,
+          F: Fn(&mut T, &str, Lang, &[&BlameLine]) + Sync
+{
+    let queue = Mutex::new(paths);
+    let results = Mutex::new((vec![], 0));
+
+    thread::scope(|scope| {
+        for _ in 0..num_cpus::get() {
+            scope.spawn(|| {
+                let mut acc = T::default();
+                let mut untracked = 0;
+                loop {
+                    let path = match queue.lock().unwrap().pop() {
+                        Some(path) => path,
+                        None => break,
+                    };
+                    let lang = lang_from_ext(&path);
+                    if lang == Lang::Unrecognized {
+                        continue;
+                    }
+                    let lines = match blame(&path) {
+                        Ok(lines) => lines,
+                        Err(_) => {
+                            untracked += 1;
+                            continue;
+                        }
+                    };
+                    let contents = lines.iter().map(|l| &l.content[..]).collect::<Vec<_>>().join(&b'\n');
+                    let kinds = match line_kinds(lang, &contents) {
+                        Some(kinds) => kinds,
+                        None => continue,
+                    };
+                    let code: Vec<&BlameLine> = lines.iter()
+                                                     .zip(kinds)
+                                                     .filter(|&(_, kind)| kind == LineKind::Code)
+                                                     .map(|(line, _)| line)
+                                                     .collect();
+                    on_file(&mut acc, &path, lang, &code);
+                }
+
+                let mut results = results.lock().unwrap();
+                results.0.push(acc);
+                results.1 += untracked;
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+// git blame of the working tree copy, so uncommitted lines show up as brand new.
+fn blame(path: &str) -> io::Result<Vec<BlameLine>> {
+    let path = Path::new(path);
+    let dir = match path.parent().and_then(|p| p.to_str()) {
+        Some("") | None => ".",
+        Some(dir) => dir,
+    };
+    let name = path.file_name().and_then(|n| n.to_str()).expect("file name");
+    let out = git(dir, &["blame", "--line-porcelain", "--", name])?;
+
+    // Every line gets a full header with --line-porcelain; the content line is the
+    // only one starting with a tab.
+    let mut lines = vec![];
+    let mut time = 0;
+    let mut author = String::new();
+    for line in out.split(|&b| b == b'\n') {
+        if line.first() == Some(&b'\t') {
+            lines.push(BlameLine { time, author: author.clone(), content: line[1..].to_vec() });
+        } else if line.starts_with(b"author-time ") {
+            time = String::from_utf8_lossy(&line[12..]).trim().parse().unwrap_or(0);
+        } else if line.starts_with(b"author ") {
+            author = String::from_utf8_lossy(&line[7..]).into_owned();
+        }
+    }
+    Ok(lines)
+}
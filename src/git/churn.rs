@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use loc::*;
+
+use worker::FileCount;
+use super::git;
+
+#[derive(Default, Clone)]
+pub struct Churn {
+    pub added: u32,
+    pub deleted: u32,
+}
+
+impl Churn {
+    pub fn merge(&mut self, o: &Churn) {
+        self.added   += o.added;
+        self.deleted += o.deleted;
+    }
+
+    pub fn total(&self) -> u32 {
+        self.added + self.deleted
+    }
+}
+
+pub struct FileChurn {
+    pub path: String,
+    pub lang: Lang,
+    pub count: Count,
+    pub churn: Churn,
+}
+
+// Lines added and deleted per file according to git log --numstat, joined onto the
+// files we just counted.  since is anything git's --since understands ("6months").
+pub fn churn<I: IntoIterator<Item = FileCount>>(targets: &[&str], since: Option<&str>, filecounts: I) -> io::Result<Vec<FileChurn>> {
+    let mut by_path: HashMap<String, Churn> = HashMap::new();
+    for target in targets {
+        let path = Path::new(target);
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."))
+        };
+
+        let since_arg = since.map(|since| format!("--since={}", since));
+        let mut args = vec!["log", "--numstat", "--format=", "--no-renames", "--relative"];
+        if let Some(ref since_arg) = since_arg {
+            args.push(since_arg);
+        }
+        args.push("--");
+        args.push(".");
+        let log = git(dir.to_str().expect("to_str"), &args)?;
+
+        // <added> TAB <deleted> TAB <path>, with - for binary files
+        for line in String::from_utf8_lossy(&log).lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (added, deleted, file) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(a), Some(d), Some(f)) => (a, d, f),
+                _ => continue,
+            };
+            let key = dir.join(file).to_string_lossy().into_owned();
+            let entry = by_path.entry(key).or_default();
+            entry.added   += added.parse().unwrap_or(0);
+            entry.deleted += deleted.parse().unwrap_or(0);
+        }
+    }
+
+    Ok(filecounts.into_iter()
+                 .map(|fc| FileChurn {
+                     churn: by_path.get(&fc.path).cloned().unwrap_or_default(),
+                     path: fc.path,
+                     lang: fc.lang,
+                     count: fc.count,
+                 })
+                 .collect())
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// The real contents of an LFS pointer file.  `git lfs smudge` reads them from the local
+// LFS store, downloading them first if they aren't there.
+pub fn smudge(path: &str) -> io::Result<Vec<u8>> {
+    let pointer = fs::read(path)?;
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut child = Command::new("git").arg("-C").arg(dir)
+                                       .args(["lfs", "smudge", "--", path])
+                                       .stdin(Stdio::piped())
+                                       .stdout(Stdio::piped())
+                                       .stderr(Stdio::piped())
+                                       .spawn()?;
+    // Pointers are tiny, so this can't fill the pipe before we start reading.
+    child.stdin.take().expect("piped stdin").write_all(&pointer)?;
+    let out = child.wait_with_output()?;
+    if out.status.success() {
+        Ok(out.stdout)
+    } else {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        Err(io::Error::other(stderr.lines().next().unwrap_or("git lfs smudge failed").to_string()))
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+
+use clap::{Arg, ArgMatches};
+
+use loc::*;
+
+use filter::PathFilter;
+use worker::FileCount;
+
+mod blame;
+mod churn;
+mod lfs;
+mod revs;
+mod sparse;
+
+pub use self::blame::{AgeBuckets, Authorship};
+pub use self::churn::{Churn, FileChurn};
+pub use self::revs::{LangDelta, RevCount};
+use self::revs::{between, count_rev, resolve_branches};
+
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("branches")
+            .required(false)
+            .long("branches")
+            .value_name("BRANCHES")
+            .takes_value(true)
+            .multiple(true)
+            .use_delimiter(true)
+            .help("Compare counts across git branches, e.g. main,develop,release/*. \
+                   Counts what's committed on each branch, not the working tree"),
+        Arg::with_name("between")
+            .required(false)
+            .long("between")
+            .value_names(&["FROM", "TO"])
+            .number_of_values(2)
+            .conflicts_with("branches")
+            .help("Summarize per-language growth between two commits or tags, e.g. --between v1.0.0 v2.0.0"),
+        Arg::with_name("age")
+            .required(false)
+            .long("age")
+            .takes_value(false)
+            .conflicts_with_all(&["branches", "between"])
+            .help("Bucket code lines by when they were last changed, according to git blame"),
+        Arg::with_name("bus-factor")
+            .required(false)
+            .long("bus-factor")
+            .value_name("GROUP")
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .possible_values(&["language", "dir"])
+            .conflicts_with_all(&["branches", "between", "age"])
+            .help("Show how concentrated code ownership is, by git blame, per language (the default) \
+                   or per top-level directory with --bus-factor=dir"),
+        Arg::with_name("churn")
+            .required(false)
+            .long("churn")
+            .takes_value(false)
+            .conflicts_with_all(&["branches", "between", "age", "bus-factor"])
+            .help("Show lines added and deleted per language and the most churned files, from git log"),
+        Arg::with_name("since")
+            .required(false)
+            .long("since")
+            .value_name("DATE")
+            .takes_value(true)
+            .requires("churn")
+            .help("Only look at history since DATE (anything git understands, e.g. 6months)"),
+        Arg::with_name("include-sparse")
+            .required(false)
+            .long("include-sparse")
+            .takes_value(false)
+            .help("Also count files left out by a sparse checkout, read from git's index \
+                   (which fetches them in a partial clone)"),
+        Arg::with_name("lfs")
+            .required(false)
+            .long("lfs")
+            .takes_value(false)
+            .help("Count the real contents of Git LFS pointer files, fetching them if needed \
+                   (needs git-lfs installed)"),
+    ]
+}
+
+// Runs whichever git report was asked for.  Returns false if there was nothing to do.
+// files is the usual walk of the targets, for the reports that look at the working tree.
+pub fn run(matches: &ArgMatches,
+           targets: &[&str],
+           path_filter: &PathFilter,
+           files: &mut dyn Iterator<Item = String>) -> bool {
+    let dir = targets[0];
+    if matches.is_present("age") {
+        let (ages, untracked) = blame::ages(files.collect());
+        ::output::print_age(&ages, untracked);
+        return true;
+    }
+
+    if matches.is_present("bus-factor") {
+        let by_dir = matches.value_of("bus-factor") == Some("dir");
+        let (groups, untracked) = blame::authorship(files.collect(), targets, by_dir);
+        ::output::print_bus_factor(if by_dir { "Directory" } else { "Language" }, &groups, untracked);
+        return true;
+    }
+
+    if let Some(mut revs) = matches.values_of("between") {
+        let from = revs.next().expect("--between FROM");
+        let to   = revs.next().expect("--between TO");
+        match between(dir, from, to, path_filter) {
+            Ok(deltas) => ::output::print_between(from, to, &deltas),
+            Err(e) => {
+                println!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return true;
+    }
+
+    let patterns: Vec<&str> = match matches.values_of("branches") {
+        Some(patterns) => patterns.collect(),
+        None => return false,
+    };
+
+    let result = resolve_branches(dir, &patterns).and_then(|branches| {
+        let mut blob_cache = HashMap::new();
+        branches.into_iter()
+                .map(|branch| count_rev(dir, &branch, path_filter, &mut blob_cache))
+                .collect::<io::Result<Vec<RevCount>>>()
+    });
+    match result {
+        Ok(counts) => ::output::print_rev_comparison(&counts),
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    true
+}
+
+// With --lfs, counts what each LFS pointer points to, leaving any we couldn't get at in
+// pointers.
+pub fn resolve_lfs(matches: &ArgMatches, pointers: &mut Vec<String>, filecounts: &mut Vec<FileCount>) {
+    if !matches.is_present("lfs") {
+        return;
+    }
+    pointers.retain(|path| match lfs::smudge(path) {
+        Ok(bytes) => {
+            let lang = lang_from_ext(path);
+            let text = match strip_ignored(&bytes) {
+                Some(text) => text,
+                None => return false,
+            };
+            filecounts.push(FileCount {
+                lang,
+                count: count_bytes(lang, &text),
+                meta: if matches.is_present("file-meta") { Some(file_meta(&bytes)) } else { None },
+                path: path.clone(),
+                files: 1,
+                bytes: bytes.len() as u64,
+                max_line: longest_line(&text),
+            });
+            false
+        }
+        Err(e) => {
+            eprintln!("Warning: couldn't get LFS contents of {}: {}", path, e);
+            true
+        }
+    });
+}
+
+// Warns if a sparse checkout hid files from the walk, or with --include-sparse counts them
+// anyway.
+pub fn add_sparse(matches: &ArgMatches,
+                  targets: &[&str],
+                  path_filter: &PathFilter,
+                  filecounts: &mut Vec<FileCount>) {
+    let include = matches.is_present("include-sparse");
+    match sparse::skipped(targets, path_filter, include, matches.is_present("file-meta")) {
+        Ok((n, counts)) => {
+            if include {
+                filecounts.extend(counts);
+            } else if n > 0 {
+                eprintln!("Warning: {} files are outside the sparse checkout and weren't counted, \
+                           pass --include-sparse to count them", n);
+            }
+        }
+        Err(e) => eprintln!("Warning: couldn't check for a sparse checkout: {}", e),
+    }
+}
+
+// Runs the git reports that build on the usual counts.  Returns false if there was
+// nothing to do.
+pub fn run_on_counts<I: IntoIterator<Item = FileCount>>(matches: &ArgMatches, targets: &[&str], filecounts: I) -> bool {
+    if !matches.is_present("churn") {
+        return false;
+    }
+    match churn::churn(targets, matches.value_of("since"), filecounts) {
+        Ok(churn) => ::output::print_churn(&churn, matches.is_present("files"), matches.is_present("show-sensitive")),
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    true
+}
+
+pub fn git(dir: &str, args: &[&str]) -> io::Result<Vec<u8>> {
+    let out = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if out.status.success() {
+        Ok(out.stdout)
+    } else {
+        Err(io::Error::other(format!("git {} failed: {}",
+                                     args.join(" "),
+                                     String::from_utf8_lossy(&out.stderr).trim())))
+    }
+}
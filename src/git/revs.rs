@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use loc::*;
+
+use filter::PathFilter;
+use super::git;
+
+pub struct RevCount {
+    pub rev: String,
+    pub by_lang: HashMap<Lang, LangTotal>,
+    // path -> blob sha, for every counted file
+    pub files: HashMap<String, String>,
+}
+
+// What changed per language between two revs.  Files are attributed to their language
+// in the newer rev, or the older one if they were removed.
+#[derive(Default)]
+pub struct LangDelta {
+    pub added: u32,
+    pub removed: u32,
+    pub modified: u32,
+    pub before: Count,
+    pub after: Count,
+}
+
+pub fn between(dir: &str, from: &str, to: &str, path_filter: &PathFilter) -> io::Result<HashMap<Lang, LangDelta>> {
+    let mut blob_cache = HashMap::new();
+    let old = count_rev(dir, from, path_filter, &mut blob_cache)?;
+    let new = count_rev(dir, to, path_filter, &mut blob_cache)?;
+
+    let mut deltas: HashMap<Lang, LangDelta> = HashMap::new();
+    for (lang, total) in &old.by_lang {
+        deltas.entry(*lang).or_default().before = total.count.clone();
+    }
+    for (lang, total) in &new.by_lang {
+        deltas.entry(*lang).or_default().after = total.count.clone();
+    }
+
+    for (path, sha) in &new.files {
+        let delta = deltas.get_mut(&blob_cache[sha].0).expect("lang counted in new rev");
+        match old.files.get(path) {
+            None => delta.added += 1,
+            Some(old_sha) if old_sha != sha => delta.modified += 1,
+            Some(_) => {}
+        }
+    }
+    for (path, sha) in &old.files {
+        if !new.files.contains_key(path) {
+            deltas.get_mut(&blob_cache[sha].0).expect("lang counted in old rev").removed += 1;
+        }
+    }
+
+    Ok(deltas)
+}
+
+// Expands branch globs (release/*) against local and then remote branches.  Anything that
+// isn't a branch but still names a commit (a tag, a sha) is passed through as is.
+pub fn resolve_branches(dir: &str, patterns: &[&str]) -> io::Result<Vec<String>> {
+    let mut branches = vec![];
+    for pattern in patterns {
+        let mut found = vec![];
+        for prefix in &["refs/heads/", "refs/remotes/"] {
+            let refs = git(dir, &["for-each-ref", "--format=%(refname:short)",
+                                  &format!("{}{}", prefix, pattern)])?;
+            found.extend(String::from_utf8_lossy(&refs).lines().map(String::from));
+            if !found.is_empty() {
+                break;
+            }
+        }
+        if found.is_empty() {
+            if git(dir, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", pattern)]).is_err() {
+                return Err(io::Error::other(format!("no branch or commit matches '{}'", pattern)));
+            }
+            found.push(String::from(*pattern));
+        }
+        for branch in found {
+            if !branches.contains(&branch) {
+                branches.push(branch);
+            }
+        }
+    }
+    Ok(branches)
+}
+
+// Counts the tree at rev straight out of the object database, without checking anything
+// out.  Blobs are keyed by sha in blob_cache, so files shared between revs are only
+// read and counted once.
+pub fn count_rev(dir: &str,
+                 rev: &str,
+                 path_filter: &PathFilter,
+                 blob_cache: &mut HashMap<String, (Lang, Count)>) -> io::Result<RevCount> {
+    let tree = git(dir, &["ls-tree", "-r", "-z", rev])?;
+
+    let mut entries = vec![];
+    for entry in tree.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+        let entry = String::from_utf8_lossy(entry);
+        // <mode> SP <type> SP <sha> TAB <path>
+        let (meta, path) = match entry.find('\t') {
+            Some(i) => (&entry[..i], &entry[i + 1..]),
+            None => continue,
+        };
+        let meta: Vec<&str> = meta.split(' ').collect();
+        // Skip submodules (commit) and symlinks (mode 120000)
+        if meta.len() != 3 || meta[1] != "blob" || meta[0] == "120000" {
+            continue;
+        }
+        if !path_filter.is_match(path) {
+            continue;
+        }
+        entries.push((String::from(meta[2]), String::from(path)));
+    }
+
+    let missing: Vec<&(String, String)> = entries.iter()
+                                                 .filter(|(sha, _)| !blob_cache.contains_key(sha))
+                                                 .collect();
+    let shas: Vec<&str> = missing.iter().map(|(sha, _)| &**sha).collect();
+    let blobs = cat_blobs(dir, &shas)?;
+    for (&(sha, path), bytes) in missing.iter().zip(blobs) {
+        let text = strip_ignored(&bytes);
+        // files marked loc:ignore-file are left out like ones we can't count
+        let lang = if text.is_some() { lang_from_bytes(path, &bytes) } else { Lang::Unrecognized };
+        let count = match text {
+            Some(ref text) if lang != Lang::Unrecognized => count_bytes(lang, text),
+            _ => Count::default(),
+        };
+        blob_cache.insert(sha.clone(), (lang, count));
+    }
+
+    let mut by_lang: HashMap<Lang, LangTotal> = HashMap::new();
+    let mut files = HashMap::new();
+    for (sha, path) in entries {
+        let (lang, ref count) = blob_cache[&sha];
+        if lang == Lang::Unrecognized {
+            continue;
+        }
+        let total = by_lang.entry(lang).or_default();
+        total.files += 1;
+        total.count.merge(count);
+        files.insert(path, sha);
+    }
+
+    Ok(RevCount {
+        rev: String::from(rev),
+        by_lang,
+        files,
+    })
+}
+
+// Reads blobs through a single `git cat-file --batch`, returned in the order asked for.
+pub fn cat_blobs(dir: &str, shas: &[&str]) -> io::Result<Vec<Vec<u8>>> {
+    let mut child = Command::new("git").args(["-C", dir, "cat-file", "--batch"])
+                                       .stdin(Stdio::piped())
+                                       .stdout(Stdio::piped())
+                                       .spawn()?;
+
+    // Feed stdin from another thread so neither side blocks on a full pipe.
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let input = shas.iter().map(|sha| format!("{}\n", sha)).collect::<String>();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let mut out = BufReader::new(child.stdout.take().expect("piped stdout"));
+    let mut blobs = Vec::with_capacity(shas.len());
+    let mut header = String::new();
+    for _ in shas {
+        header.clear();
+        out.read_line(&mut header)?;
+        // <sha> SP <type> SP <size> LF <contents> LF, or <object> SP missing LF
+        let size = match header.split_whitespace().nth(2).map(str::parse::<usize>) {
+            Some(Ok(size)) => size,
+            _ => return Err(io::Error::other(format!("unexpected git cat-file output: {}", header.trim()))),
+        };
+        let mut blob = vec![0; size + 1];
+        out.read_exact(&mut blob)?;
+        blob.pop();
+        blobs.push(blob);
+    }
+
+    writer.join().expect("cat-file writer panicked")?;
+    child.wait()?;
+    Ok(blobs)
+}
+
@@ -0,0 +1,86 @@
+use std::io;
+use std::path::Path;
+
+use loc::*;
+
+use filter::PathFilter;
+use worker::FileCount;
+use super::git;
+use super::revs::cat_blobs;
+
+// Files under targets that a sparse checkout left out of the working tree, and so out of
+// the walk.  With include they're counted from the index instead, which in a partial clone
+// means git fetches the missing blobs first.  Returns how many files were skipped, and the
+// counts if include (with each file's FileMeta if meta).
+pub fn skipped(targets: &[&str], path_filter: &PathFilter, include: bool, meta: bool) -> io::Result<(usize, Vec<FileCount>)> {
+    let mut n = 0;
+    let mut counts = vec![];
+    for target in targets {
+        if !Path::new(target).is_dir() || !is_sparse(target) {
+            continue;
+        }
+
+        let entries = skip_worktree_entries(target, path_filter)?;
+        n += entries.len();
+        if !include {
+            continue;
+        }
+
+        let shas: Vec<&str> = entries.iter().map(|(_, sha)| &**sha).collect();
+        let blobs = cat_blobs(target, &shas)?;
+        for ((path, _), bytes) in entries.into_iter().zip(blobs) {
+            let lang = lang_from_bytes(&path, &bytes);
+            if lang == Lang::Unrecognized {
+                continue;
+            }
+            let text = match strip_ignored(&bytes) {
+                Some(text) => text,
+                None => continue,
+            };
+            counts.push(FileCount {
+                count: count_bytes(lang, &text),
+                meta: if meta { Some(file_meta(&bytes)) } else { None },
+                path,
+                lang,
+                files: 1,
+                bytes: bytes.len() as u64,
+                max_line: longest_line(&text),
+            });
+        }
+    }
+    Ok((n, counts))
+}
+
+fn is_sparse(dir: &str) -> bool {
+    match git(dir, &["config", "--bool", "core.sparseCheckout"]) {
+        Ok(out) => out.starts_with(b"true"),
+        // Not a repo, or not set
+        Err(_) => false,
+    }
+}
+
+// (path, blob sha) of every skip-worktree entry in the index under dir.  Paths are joined
+// onto dir so they look like the ones the walk produces.
+fn skip_worktree_entries(dir: &str, path_filter: &PathFilter) -> io::Result<Vec<(String, String)>> {
+    let out = git(dir, &["ls-files", "-t", "-s", "-z", "--", "."])?;
+
+    let mut entries = vec![];
+    for entry in out.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+        let entry = String::from_utf8_lossy(entry);
+        // <tag> SP <mode> SP <sha> SP <stage> TAB <path>
+        let (meta, path) = match entry.find('\t') {
+            Some(i) => (&entry[..i], &entry[i + 1..]),
+            None => continue,
+        };
+        let meta: Vec<&str> = meta.split(' ').collect();
+        // Skip submodules (160000) and symlinks (120000)
+        if meta.len() != 4 || meta[0] != "S" || meta[1] == "160000" || meta[1] == "120000" {
+            continue;
+        }
+        let path = Path::new(dir).join(path).to_string_lossy().into_owned();
+        if path_filter.is_match(&path) {
+            entries.push((path, String::from(meta[2])));
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,45 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use loc::*;
+
+const HEADER: &str = "timestamp,files,lines,blank,comment,code";
+
+// --append-history: adds a row with this run's totals to a CSV, writing the header first
+// if the file is new, so running it nightly builds up a growth chart.
+pub fn append(path: &str, files: u32, total: &Count) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{}", HEADER)?;
+    }
+    writeln!(file, "{},{},{},{},{},{}", now(), files, total.lines,
+             total.blank, total.comment, total.code)
+}
+
+pub fn now() -> String {
+    timestamp(SystemTime::now())
+}
+
+// UTC, ISO 8601 to the second ("2024-03-05T14:07:00Z"), which spreadsheets and pandas
+// parse without being told the format.
+fn timestamp(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let (y, m, d) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// Days since 1970-01-01 to a (year, month, day), from Howard Hinnant's date algorithms.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
@@ -0,0 +1,185 @@
+use std::sync::OnceLock;
+
+// --lang-ui: the language the human-readable reports (the tables, --format md and html) are
+// labelled in.  Formats meant for other programs keep their English keys.
+#[derive(Clone, Copy, PartialEq)]
+pub enum UiLang {
+    En,
+    De,
+    Fr,
+    Ja,
+}
+
+impl UiLang {
+    pub fn from_code(code: &str) -> Option<UiLang> {
+        match code.to_lowercase().as_str() {
+            "en" => Some(UiLang::En),
+            "de" => Some(UiLang::De),
+            "fr" => Some(UiLang::Fr),
+            "ja" => Some(UiLang::Ja),
+            _    => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            UiLang::En => "en",
+            UiLang::De => "de",
+            UiLang::Fr => "fr",
+            UiLang::Ja => "ja",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Text {
+    Language,
+    Files,
+    Lines,
+    Blank,
+    Comment,
+    Code,
+    Bytes,
+    AvgLine,
+    MaxLine,
+    Total,
+    Directory,
+    Path,
+    Group,
+    File,
+    Mostly,
+    // "{}" is the number of languages lumped together
+    Other,
+    // "{}" is the number of files left out
+    HiddenSensitive,
+    Summary,
+    Languages,
+    CodePerLanguage,
+    BackToTop,
+    // "{files}" and "{code}" are filled in
+    LangSummary,
+}
+
+// Set once, before any output
+static UI: OnceLock<UiLang> = OnceLock::new();
+
+pub fn set_ui(ui: UiLang) {
+    let _ = UI.set(ui);
+}
+
+pub fn ui() -> UiLang {
+    UI.get().cloned().unwrap_or(UiLang::En)
+}
+
+pub fn t(text: Text) -> &'static str {
+    use self::Text::*;
+    use self::UiLang::*;
+    match (ui(), text) {
+        (En, Language)        => "Language",
+        (En, Files)           => "Files",
+        (En, Lines)           => "Lines",
+        (En, Blank)           => "Blank",
+        (En, Comment)         => "Comment",
+        (En, Code)            => "Code",
+        (En, Bytes)           => "Bytes",
+        (En, AvgLine)         => "Avg line",
+        (En, MaxLine)         => "Max line",
+        (En, Total)           => "Total",
+        (En, Directory)       => "Directory",
+        (En, Path)            => "Path",
+        (En, Group)           => "Group",
+        (En, File)            => "File",
+        (En, Mostly)          => "Mostly",
+        (En, Other)           => "Other ({})",
+        (En, HiddenSensitive) => "{} sensitive files (keys, credentials, .env) aren't listed, pass --show-sensitive to list them",
+        (En, Summary)         => "Summary",
+        (En, Languages)       => "Languages",
+        (En, CodePerLanguage) => "Code per language",
+        (En, BackToTop)       => "Back to top",
+        (En, LangSummary)     => "{files} files, {code} lines of code",
+
+        (De, Language)        => "Sprache",
+        (De, Files)           => "Dateien",
+        (De, Lines)           => "Zeilen",
+        (De, Blank)           => "Leer",
+        (De, Comment)         => "Kommentar",
+        (De, Code)            => "Code",
+        (De, Bytes)           => "Bytes",
+        (De, AvgLine)         => "Ø Zeile",
+        (De, MaxLine)         => "Max. Zeile",
+        (De, Total)           => "Gesamt",
+        (De, Directory)       => "Verzeichnis",
+        (De, Path)            => "Pfad",
+        (De, Group)           => "Gruppe",
+        (De, File)            => "Datei",
+        (De, Mostly)          => "Überwiegend",
+        (De, Other)           => "Andere ({})",
+        (De, HiddenSensitive) => "{} sensible Dateien (Schlüssel, Zugangsdaten, .env) sind nicht aufgeführt, --show-sensitive zeigt sie an",
+        (De, Summary)         => "Zusammenfassung",
+        (De, Languages)       => "Sprachen",
+        (De, CodePerLanguage) => "Code pro Sprache",
+        (De, BackToTop)       => "Nach oben",
+        (De, LangSummary)     => "{files} Dateien, {code} Codezeilen",
+
+        (Fr, Language)        => "Langage",
+        (Fr, Files)           => "Fichiers",
+        (Fr, Lines)           => "Lignes",
+        (Fr, Blank)           => "Vides",
+        (Fr, Comment)         => "Commentaires",
+        (Fr, Code)            => "Code",
+        (Fr, Bytes)           => "Octets",
+        (Fr, AvgLine)         => "Ligne moy.",
+        (Fr, MaxLine)         => "Ligne max.",
+        (Fr, Total)           => "Total",
+        (Fr, Directory)       => "Répertoire",
+        (Fr, Path)            => "Chemin",
+        (Fr, Group)           => "Groupe",
+        (Fr, File)            => "Fichier",
+        (Fr, Mostly)          => "Surtout",
+        (Fr, Other)           => "Autres ({})",
+        (Fr, HiddenSensitive) => "{} fichiers sensibles (clés, identifiants, .env) ne sont pas listés, ajoutez --show-sensitive pour les voir",
+        (Fr, Summary)         => "Résumé",
+        (Fr, Languages)       => "Langages",
+        (Fr, CodePerLanguage) => "Code par langage",
+        (Fr, BackToTop)       => "Retour en haut",
+        (Fr, LangSummary)     => "{files} fichiers, {code} lignes de code",
+
+        (Ja, Language)        => "言語",
+        (Ja, Files)           => "ファイル",
+        (Ja, Lines)           => "行数",
+        (Ja, Blank)           => "空行",
+        (Ja, Comment)         => "コメント",
+        (Ja, Code)            => "コード",
+        (Ja, Bytes)           => "バイト",
+        (Ja, AvgLine)         => "平均行長",
+        (Ja, MaxLine)         => "最大行長",
+        (Ja, Total)           => "合計",
+        (Ja, Directory)       => "ディレクトリ",
+        (Ja, Path)            => "パス",
+        (Ja, Group)           => "グループ",
+        (Ja, File)            => "ファイル",
+        (Ja, Mostly)          => "主な言語",
+        (Ja, Other)           => "その他 ({})",
+        (Ja, HiddenSensitive) => "機密ファイル {} 件（鍵、認証情報、.env）は表示していません。--show-sensitive で表示します",
+        (Ja, Summary)         => "概要",
+        (Ja, Languages)       => "言語一覧",
+        (Ja, CodePerLanguage) => "言語別のコード行数",
+        (Ja, BackToTop)       => "先頭へ戻る",
+        (Ja, LangSummary)     => "{files} ファイル、コード {code} 行",
+    }
+}
+
+// s padded with spaces to width terminal columns, counting CJK characters as the two
+// columns they take up, which format!'s width doesn't.
+pub fn pad(s: &str, width: usize, right_align: bool) -> String {
+    let used: usize = s.chars().map(|c| if is_wide(c) { 2 } else { 1 }).sum();
+    let fill = " ".repeat(width.saturating_sub(used));
+    if right_align { fill + s } else { s.to_string() + &fill }
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+             0x1100..=0x115f | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff |
+             0xa000..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe30..=0xfe4f | 0xff00..=0xff60 |
+             0xffe0..=0xffe6)
+}
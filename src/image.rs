@@ -0,0 +1,251 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use flate2::read::GzDecoder;
+use serde_json::Value;
+use tar::{Archive, EntryType};
+
+use loc::*;
+
+use temp;
+
+const WHITEOUT: &str = ".wh.";
+const OPAQUE: &str = ".wh..wh..opq";
+
+// An image's filesystem, flattened into a temp dir that goes away when this does (or at
+// exit, for an exit that skips this).
+pub struct Extracted {
+    dir: PathBuf,
+    pub rootfs: PathBuf,
+}
+
+impl Drop for Extracted {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// image is a docker-save / OCI tarball, an unpacked OCI layout directory, or a name like
+// ubuntu:22.04, which we ask docker (or podman) to save for us, pulling it if need be.
+pub fn extract(image: &str) -> io::Result<Extracted> {
+    let dir = temp::dir("loc-image")?;
+    let extracted = Extracted { rootfs: dir.join("rootfs"), dir };
+    fs::create_dir_all(&extracted.rootfs)?;
+
+    let layout = if Path::new(image).is_dir() {
+        PathBuf::from(image)
+    } else {
+        let tarball = if Path::new(image).is_file() {
+            PathBuf::from(image)
+        } else {
+            let tarball = extracted.dir.join("image.tar");
+            save(image, &tarball)?;
+            tarball
+        };
+        let layout = extracted.dir.join("layout");
+        Archive::new(File::open(&tarball)?).unpack(&layout)?;
+        layout
+    };
+
+    for layer in layers(&layout)? {
+        apply_layer(&layout.join(layer), &extracted.rootfs)?;
+    }
+    Ok(extracted)
+}
+
+// A path under rootfs the way it would read inside the container, ./usr/lib/...
+pub fn shown_path(rootfs: &Path, path: &str) -> String {
+    match Path::new(path).strip_prefix(rootfs) {
+        Ok(rel) => Path::new(".").join(rel).to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+fn save(image: &str, tarball: &Path) -> io::Result<()> {
+    let mut last_err = None;
+    for tool in &["docker", "podman"] {
+        let save = || Command::new(tool).arg("save").arg("-o").arg(tarball).arg(image).output();
+        let out = match save() {
+            Ok(out) => out,
+            // not installed, try the next one
+            Err(e) => {
+                last_err = Some(e);
+                continue;
+            }
+        };
+        if out.status.success() {
+            return Ok(());
+        }
+        let pulled = Command::new(tool).arg("pull").arg("-q").arg(image).output()?;
+        let out = if pulled.status.success() { save()? } else { pulled };
+        if out.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let msg = stderr.lines().last().unwrap_or("failed").to_string();
+        return Err(io::Error::other(format!("{} save {}: {}", tool, image, msg)));
+    }
+    Err(io::Error::other(format!("need docker or podman to fetch {} ({})",
+                                 image, last_err.map(|e| e.to_string()).unwrap_or_default())))
+}
+
+// Layer paths within the layout, bottom first.  docker save writes a manifest.json
+// listing them; a plain OCI layout only has index.json pointing at a manifest blob.
+fn layers(layout: &Path) -> io::Result<Vec<String>> {
+    let manifest = layout.join("manifest.json");
+    if manifest.exists() {
+        let json = read_json(&manifest)?;
+        let layers = json[0]["Layers"].as_array().ok_or_else(|| bad("manifest.json has no Layers"))?;
+        return Ok(layers.iter().filter_map(|l| l.as_str()).map(String::from).collect());
+    }
+
+    let index = read_json(&layout.join("index.json"))?;
+    let mut digest = index["manifests"][0]["digest"].as_str().ok_or_else(|| bad("index.json has no manifests"))?.to_string();
+    loop {
+        let json = read_json(&layout.join(blob_path(&digest)?))?;
+        // multi-arch images have an index of per-platform manifests; take the first
+        if let Some(d) = json["manifests"][0]["digest"].as_str() {
+            digest = d.to_string();
+            continue;
+        }
+        let layers = json["layers"].as_array().ok_or_else(|| bad("image manifest has no layers"))?;
+        return layers.iter()
+                     .filter_map(|l| l["digest"].as_str())
+                     .map(blob_path)
+                     .collect();
+    }
+}
+
+fn blob_path(digest: &str) -> io::Result<String> {
+    let mut parts = digest.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(algo), Some(hex)) if !hex.contains('/') && !algo.contains('/') => Ok(format!("blobs/{}/{}", algo, hex)),
+        _ => Err(bad(&format!("odd digest {}", digest))),
+    }
+}
+
+fn read_json(path: &Path) -> io::Result<Value> {
+    serde_json::from_reader(BufReader::new(File::open(path)?))
+        .map_err(|e| bad(&format!("{}: {}", path.display(), e)))
+}
+
+fn bad(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Unpacks the files we can count from one layer over the ones below, applying its
+// whiteouts.  Links, devices and anything we don't recognize as source are skipped (but
+// still hide whatever was at that path before).
+fn apply_layer(layer: &Path, rootfs: &Path) -> io::Result<()> {
+    let mut file = BufReader::new(File::open(layer)?);
+    let mut magic = [0u8; 4];
+    let n = file.read(&mut magic)?;
+    let head = io::Cursor::new(magic[..n].to_vec()).chain(file);
+    let reader: Box<dyn Read> = match &magic[..n] {
+        [0x1f, 0x8b, ..]         => Box::new(GzDecoder::new(head)),
+        [0x28, 0xb5, 0x2f, 0xfd] => return Err(bad("zstd-compressed layers aren't supported")),
+        _                        => Box::new(head),
+    };
+
+    // Whiteouts only apply to lower layers, and can come after this layer's own files in
+    // the tarball, so unpack into a scratch dir first and merge once we've seen them all.
+    let upper = rootfs.with_file_name("upper");
+    let mut removed = Vec::new();
+    let mut opaque = Vec::new();
+    let mut archive = Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let rel = match safe_path(&entry.path()?) {
+            Some(rel) => rel,
+            None => continue,
+        };
+        let name = rel.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+        if name == OPAQUE {
+            opaque.push(rel.parent().expect("parent").to_path_buf());
+            continue;
+        }
+        if let Some(hidden) = name.strip_prefix(WHITEOUT) {
+            removed.push(rel.with_file_name(hidden));
+            continue;
+        }
+        let kind = entry.header().entry_type();
+        if kind == EntryType::Directory {
+            continue;
+        }
+        if kind == EntryType::Link {
+            // a hard link to a file earlier in this layer or a lower one
+            let linked = entry.link_name()?.and_then(|l| safe_path(&l));
+            let from = linked.map(|l| if upper.join(&l).is_file() { upper.join(&l) } else { rootfs.join(&l) });
+            match from {
+                Some(ref from) if from.is_file() => {
+                    let target = upper.join(&rel);
+                    fs::create_dir_all(target.parent().expect("parent"))?;
+                    fs::copy(from, &target)?;
+                }
+                _ => removed.push(rel),
+            }
+            continue;
+        }
+        if !kind.is_file() {
+            removed.push(rel);
+            continue;
+        }
+
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        if lang_from_bytes(rel.to_str().unwrap_or(""), &bytes) == Lang::Unrecognized {
+            removed.push(rel);
+            continue;
+        }
+        let target = upper.join(&rel);
+        fs::create_dir_all(target.parent().expect("parent"))?;
+        fs::write(&target, &bytes)?;
+    }
+
+    for rel in opaque.iter().chain(removed.iter()) {
+        let path = rootfs.join(rel);
+        let _ = fs::remove_dir_all(&path).or_else(|_| fs::remove_file(&path));
+    }
+    fs::create_dir_all(rootfs)?;
+    if upper.exists() {
+        merge(&upper, rootfs)?;
+        fs::remove_dir_all(&upper)?;
+    }
+    Ok(())
+}
+
+// Moves everything in from over the top of to.
+fn merge(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            if target.is_file() {
+                fs::remove_file(&target)?;
+            }
+            fs::create_dir_all(&target)?;
+            merge(&entry.path(), &target)?;
+        } else {
+            if target.is_dir() {
+                fs::remove_dir_all(&target)?;
+            }
+            fs::rename(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+// The entry's path relative to the image root, or None if it tries to climb out of it.
+fn safe_path(path: &Path) -> Option<PathBuf> {
+    let mut rel = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => rel.push(part),
+            Component::CurDir | Component::RootDir => {}
+            _ => return None,
+        }
+    }
+    if rel.as_os_str().is_empty() { None } else { Some(rel) }
+}
@@ -1,15 +1,24 @@
 extern crate memchr;
 extern crate smallvec;
+extern crate encoding_rs;
 
+use std::borrow::Cow;
 use std::path::Path;
 use std::fs::File;
 use std::cmp::{max, min};
 use std::fmt;
+use std::io;
 use std::io::prelude::*;
 
 use memchr::memchr;
+use memchr::memmem;
 use smallvec::*;
 
+pub mod charset;
+pub mod corpus;
+pub mod doc_coverage;
+pub mod sniff;
+
 // Why is it called partialEq?
 #[derive(Debug, PartialEq, Default, Clone)]
 pub struct Count {
@@ -20,17 +29,44 @@ pub struct Count {
 }
 
 impl Count {
+    // Counts source that's already a string, say code a test just generated, without
+    // going anywhere near the filesystem.
+    pub fn from_str(lang: Lang, src: &str) -> Count {
+        count_bytes(lang, src.as_bytes())
+    }
+
     pub fn merge(&mut self, o: &Count) {
         self.code    += o.code;
         self.comment += o.comment;
         self.blank   += o.blank;
         self.lines   += o.lines;
     }
+
+    pub fn add_line(&mut self, kind: LineKind) {
+        match kind {
+            LineKind::Code    => self.code    += 1,
+            LineKind::Comment => self.comment += 1,
+            LineKind::Blank   => self.blank   += 1,
+        }
+        self.lines += 1;
+    }
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum LineKind {
+    Code,
+    Comment,
+    Blank,
+}
+
+#[derive(Default)]
 pub struct LangTotal {
     pub files: u32,
     pub count: Count,
+    // The files' size on disk
+    pub bytes: u64,
+    // The longest line in any of them, in bytes
+    pub max_line: u32,
 }
 
 // Do any languages actually use utf8 chars as comment chars?
@@ -293,6 +329,24 @@ impl Lang {
             Unrecognized     => "Unrecognized",
         }
     }
+
+    // By display name ("C++") or variant name ("Cpp"), ignoring case.
+    pub fn from_name(name: &str) -> Option<Lang> {
+        LANGS.iter().cloned().find(|lang| {
+            lang.to_s().eq_ignore_ascii_case(name) || format!("{:?}", lang).eq_ignore_ascii_case(name)
+        })
+    }
+
+    // The family of languages this one is usually talked about as part of, if any: a C++
+    // codebase has its C headers and maybe some Objective-C, a frontend mixes JS and TS.
+    pub fn family(&self) -> Option<&'static str> {
+        match *self {
+            C | CCppHeader | Cpp | ObjectiveC | ObjectiveCpp => Some("C/C++/Obj-C"),
+            JavaScript | TypeScript | Jsx | Tsx              => Some("JS/TS"),
+            Java | Kotlin | Scala | Groovy                   => Some("JVM"),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Lang {
@@ -301,7 +355,98 @@ impl fmt::Display for Lang {
     }
 }
 
+// Every language we know about, for looking one up by name.
+pub const LANGS: &[Lang] = &[
+    ActionScript, Ada, Agda, AmbientTalk, Asp, AspNet, Assembly, Autoconf, Awk, Batch,
+    BourneShell, C, CCppHeader, CMake, CSharp, CShell, Clojure, ClojureScript, ClojureC,
+    CoffeeScript, ColdFusion, ColdFusionScript, Coq, Cpp, Crystal, Css, CUDA, CUDAHeader, D,
+    Dart, Dhall, DeviceTree, Docker, Elixir, Elm, Erlang, Forth, FortranLegacy,
+    FortranModern, FSharp, Gherkin, Glsl, Go, Groovy, Handlebars, Haskell, Hex, Html, INI,
+    Idris, IntelHex, Isabelle, Jai, Java, JavaScript, Json, Jsx, Julia, Kotlin, Less,
+    LinkerScript, Lean, Lisp, Lua, Make, Makefile, Markdown, Mustache, Nim, Nix, OCaml,
+    ObjectiveC, ObjectiveCpp, OpenCl, Oz, Pascal, Perl, Php, Polly, PowerShell, Prolog,
+    Protobuf, Puppet, PureScript, Pyret, Python, Qcl, Qml, R, Razor, Reason, Ron,
+    ReStructuredText, Ruby, RubyHtml, Rust, SaltStack, Sass, Scala, Sml, Solidity, Sql,
+    Stylus, Svelte, Swift, Tcl, Terraform, Tex, Text, Toml, TypeScript, Tsx, UnrealScript,
+    VimScript, Vue, Wolfram, XML, Yacc, Yaml, Zig, Zsh, Haxe,
+];
+
 pub fn lang_from_ext(filepath: &str) -> Lang {
+    lang_from_name(filepath, check_shebang)
+}
+
+// Same as lang_from_ext, but never opens the file, so anything that would need a look at
+// its shebang is Unrecognized.
+pub fn lang_from_path(filepath: &str) -> Lang {
+    lang_from_name(filepath, |_| None)
+}
+
+// Same as lang_from_ext, but takes the shebang from bytes we've already got (e.g. a git
+// blob) instead of opening the file.
+pub fn lang_from_bytes(filepath: &str, bytes: &[u8]) -> Lang {
+    lang_from_name(filepath, |_| shebang_ext(bytes))
+}
+
+// How sure lang_from_ext can be about a file.  Some extensions are shared by several
+// languages and we just pick the usual one, and files without an extension only get a
+// language from their shebang.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Guess {
+    Certain,
+    Shebang,
+    // The languages it could be, the one we pick first
+    SharedExt(&'static [&'static str]),
+}
+
+impl Guess {
+    pub fn confidence(&self) -> f64 {
+        match *self {
+            Guess::Certain            => 1.0,
+            Guess::Shebang            => 0.8,
+            Guess::SharedExt(langs)   => 1.0 / langs.len() as f64,
+        }
+    }
+}
+
+// Extensions more than one language claims.  Only the ones where you'd plausibly meet the
+// other language in the wild.
+const SHARED_EXTS: &[(&str, &[&str])] = &[
+    ("as",  &["ActionScript", "AngelScript"]),
+    ("cl",  &["OpenCL", "Common Lisp"]),
+    ("d",   &["D", "Makefile dependencies"]),
+    ("f",   &["FORTRAN Legacy", "Forth"]),
+    ("fs",  &["F#", "Forth", "GLSL"]),
+    ("h",   &["C/C++ Header", "Objective-C"]),
+    ("in",  &["Autoconf", "any template"]),
+    ("m",   &["Objective-C", "MATLAB", "Mercury"]),
+    ("p",   &["Prolog", "Pascal"]),
+    ("pl",  &["Perl", "Prolog"]),
+    ("pp",  &["Puppet", "Pascal"]),
+    ("pro", &["Prolog", "IDL", "QMake"]),
+    ("r",   &["R", "Rebol"]),
+    ("s",   &["Assembly", "R"]),
+    ("sc",  &["Scala", "SuperCollider"]),
+    ("sls", &["SaltStack", "Scheme"]),
+    ("ts",  &["TypeScript", "Qt Linguist"]),
+    ("v",   &["Coq", "Verilog"]),
+];
+
+// Doesn't open the file: anything that would have needed its shebang counts as a Shebang
+// guess.
+pub fn guess(filepath: &str) -> Guess {
+    let (ext, needs_shebang) = ext_of(filepath, |_| Some(String::new()));
+    if needs_shebang {
+        return Guess::Shebang;
+    }
+    match SHARED_EXTS.iter().find(|&&(e, _)| e == ext) {
+        Some(&(_, langs)) => Guess::SharedExt(langs),
+        None => Guess::Certain,
+    }
+}
+
+// The extension to look a file's language up by, and whether we had to ask its shebang.
+// Without a shebang, a file with no extension is looked up by its name.
+fn ext_of<F: FnOnce(&Path) -> Option<String>>(filepath: &str, shebang: F) -> (String, bool) {
     let path = Path::new(filepath);
     let file_name_lower = path.file_name()
         .expect("no filename?")
@@ -309,28 +454,35 @@ pub fn lang_from_ext(filepath: &str) -> Lang {
         .expect("to_str")
         .to_lowercase();
 
-    let ext = if file_name_lower.contains("makefile") {
-        String::from("makefile")
+    if file_name_lower.contains("makefile") {
+        (String::from("makefile"), false)
     } else if file_name_lower == "dockerfile" {
-        String::from("docker")
+        (String::from("docker"), false)
     } else if file_name_lower == "cmakelists.txt" {
-        String::from("cmake")
+        (String::from("cmake"), false)
     } else {
         match path.extension() {
-            Some(os_str) => os_str.to_str().expect("path to_str").to_lowercase(),
+            Some(os_str) => (os_str.to_str().expect("path to_str").to_lowercase(), false),
             None => {
-                if let Some(ext) = check_shebang(path) {
-                    ext
+                if let Some(ext) = shebang(path) {
+                    (ext, true)
                 } else {
-                    file_name_lower
+                    (file_name_lower, false)
                 }
             }
         }
-    };
+    }
+}
+
+fn lang_from_name<F: FnOnce(&Path) -> Option<String>>(filepath: &str, shebang: F) -> Lang {
+    let (ext, _) = ext_of(filepath, shebang);
+    lang_for_ext(&ext)
+}
 
+pub fn lang_for_ext(ext: &str) -> Lang {
     // NOTE(cgag): while we lifted most of this from tokei, we support a few
     // more extensions in some places, can't just assume it's the same.
-    match &*ext {
+    match ext {
         "4th" | "forth" | "fr" | "frt" | "fth" | "f83" | "fb" | "fpm" | "e4" | "rx" | "ft" => Forth,
         "ada" | "adb" | "ads" | "pad" => Ada,
         "agda" => Agda,
@@ -457,7 +609,9 @@ pub fn lang_from_ext(filepath: &str) -> Lang {
     }
 }
 
-pub fn counter_config_for_lang<'a>(lang: Lang) -> (SmallVec<[&'a str; 3]>, SmallVec<[(&'a str, &'a str); 3]>) {
+pub type CommentConfig<'a> = (SmallVec<[&'a str; 3]>, SmallVec<[(&'a str, &'a str); 3]>);
+
+pub fn counter_config_for_lang<'a>(lang: Lang) -> CommentConfig<'a> {
     let c_style      = (smallvec!["//"], smallvec![("/*", "*/")]);
     let html_style   = (smallvec![],     smallvec![("<!--", "-->")]);
     let ml_style     = (smallvec![],     smallvec![("(*", "*)")]);
@@ -561,7 +715,7 @@ struct ByteLinesState<'a> {
 struct ByteLines<'a>(&'a [u8]);
 
 impl<'a> ByteLines<'a> {
-    fn lines(&self) -> ByteLinesState {
+    fn lines(&self) -> ByteLinesState<'_> {
         ByteLinesState {
             buf: self.0,
             pos: 0,
@@ -595,36 +749,480 @@ impl<'a> Iterator for ByteLinesState<'a> {
 // TODO(cgag): do we have to worry about the case of single line comments being nested in multis?
 // I dn't think so but i should think about it.
 pub fn count(filepath: &str) -> Count {
+    try_count(filepath).unwrap_or_default()
+}
+
+// Like count, but reports files we couldn't open or read instead of counting them as empty.
+pub fn try_count(filepath: &str) -> io::Result<Count> {
     let lang = lang_from_ext(filepath);
-    let (singles, multis) = counter_config_for_lang(lang);
 
-    let mfile = File::open(filepath);
-    let mut file = match mfile {
-        Ok(file) => file,
-        Err(_) => {
-            return Count::default();
-        }
-    };
+    let mut file = File::open(filepath)?;
     // TODO(cgag): set the size of this vec to size of the file + a byte? a reddit comment
     // somewhere says fs::read will do this ofr you.
     let mut bytes = vec![];
-    file.read_to_end(&mut bytes).expect("nani?!");
+    file.read_to_end(&mut bytes)?;
+
+    Ok(count_bytes(lang, &bytes))
+}
 
+// Counts a file's contents that are already in memory.  Whatever's in there (it came
+// straight off disk, after all) this shouldn't panic: fuzz/ hammers on it with cargo-fuzz.
+pub fn count_bytes(lang: Lang, bytes: &[u8]) -> Count {
     let mut c = Count::default();
+    if classify(lang, bytes, |kind| c.add_line(kind)) {
+        c
+    } else {
+        // TODO(cgag): should we report when this happens?
+        Count::default()
+    }
+}
+
+// Git LFS keeps a small text pointer in the repo in place of the real file, which would
+// otherwise get counted as a three line file in whatever language its extension says.
+pub fn is_lfs_pointer(bytes: &[u8]) -> bool {
+    bytes.len() < 1024 && bytes.starts_with(b"version https://git-lfs.github.com/spec/v1\n")
+}
+
+// How many unresolved merge conflicts git left in bytes, going by the <<<<<<< that opens
+// each one.  Only counts them if there's a >>>>>>> to close one too, so a stray line of
+// angle brackets in some docs doesn't count.
+pub fn conflict_markers(bytes: &[u8]) -> usize {
+    let is_marker = |line: &[u8], marker: &[u8]| {
+        line.starts_with(marker) && matches!(line.get(7), None | Some(b' ') | Some(b'\r'))
+    };
+    let mut opened = 0;
+    let mut closed = false;
+    for line in bytes.split(|&b| b == b'\n') {
+        if is_marker(line, b"<<<<<<<") {
+            opened += 1;
+        } else if is_marker(line, b">>>>>>>") {
+            closed = true;
+        }
+    }
+    if closed { opened } else { 0 }
+}
+
+// How long the longest line in bytes is, in bytes, not counting its line ending.
+pub fn longest_line(bytes: &[u8]) -> u32 {
+    bytes.split(|&b| b == b'\n')
+         .map(|line| line.strip_suffix(b"\r").unwrap_or(line).len() as u32)
+         .max()
+         .unwrap_or(0)
+}
+
+// What's left to count of bytes once the regions between loc:ignore-start and
+// loc:ignore-end lines are taken out, markers included, or None if a loc:ignore-file in the
+// first few lines says to leave the whole file out.  The markers go in whatever comment
+// syntax the language has, but have to end their line (give or take a comment closer), so
+// a mention in the middle of some prose doesn't count.  A start with no end runs to the
+// end of the file.
+pub fn strip_ignored(bytes: &[u8]) -> Option<Cow<'_, [u8]>> {
+    if memmem::find(bytes, b"loc:ignore-").is_none() {
+        return Some(Cow::Borrowed(bytes));
+    }
+    let is_marker = |line: &[u8], marker: &[u8]| {
+        let at = match memmem::rfind(line, marker) {
+            Some(at) => at,
+            None => return false,
+        };
+        let rest = String::from_utf8_lossy(&line[at + marker.len()..]);
+        matches!(rest.trim(), "" | "*/" | "-->" | "*)" | "-}")
+    };
+    if bytes.split(|&b| b == b'\n').take(5).any(|line| is_marker(line, b"loc:ignore-file")) {
+        return None;
+    }
+    let mut kept = Vec::with_capacity(bytes.len());
+    let mut ignoring = false;
+    for line in bytes.split_inclusive(|&b| b == b'\n') {
+        if !ignoring && is_marker(line, b"loc:ignore-start") {
+            ignoring = true;
+        } else if ignoring && is_marker(line, b"loc:ignore-end") {
+            ignoring = false;
+        } else if !ignoring {
+            kept.extend_from_slice(line);
+        }
+    }
+    Some(Cow::Owned(kept))
+}
+
+// The sort of thing you'd want to know before a repo-wide cleanup: what a file is
+// encoded in, whether it starts with a byte order mark, and what its lines end with.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct FileMeta {
+    pub encoding: Encoding,
+    pub bom: bool,
+    pub line_endings: LineEndings,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Encoding {
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // Not utf8, probably latin-1 or some other code page
+    Other,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LineEndings {
+    Lf,
+    CrLf,
+    Cr,
+    Mixed,
+    // Empty, or a single unterminated line
+    None,
+}
+
+impl Encoding {
+    pub fn to_s(&self) -> &'static str {
+        match *self {
+            Encoding::Ascii   => "ascii",
+            Encoding::Utf8    => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Other   => "other",
+        }
+    }
+}
+
+impl LineEndings {
+    pub fn to_s(&self) -> &'static str {
+        match *self {
+            LineEndings::Lf    => "LF",
+            LineEndings::CrLf  => "CRLF",
+            LineEndings::Cr    => "CR",
+            LineEndings::Mixed => "mixed",
+            LineEndings::None  => "none",
+        }
+    }
+}
+
+pub fn file_meta(bytes: &[u8]) -> FileMeta {
+    let (encoding, bom) = if bytes.starts_with(b"\xEF\xBB\xBF") {
+        (Encoding::Utf8, true)
+    } else if bytes.starts_with(b"\xFF\xFE") {
+        (Encoding::Utf16Le, true)
+    } else if bytes.starts_with(b"\xFE\xFF") {
+        (Encoding::Utf16Be, true)
+    } else if bytes.is_ascii() {
+        (Encoding::Ascii, false)
+    } else if std::str::from_utf8(bytes).is_ok() {
+        (Encoding::Utf8, false)
+    } else {
+        (Encoding::Other, false)
+    };
+
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut i = 0;
+    while let Some(at) = bytes[i..].iter().position(|&b| b == b'\n' || b == b'\r') {
+        i += at;
+        if bytes[i] == b'\n' {
+            lf += 1;
+        } else if bytes.get(i + 1) == Some(&b'\n') {
+            crlf += 1;
+            i += 1;
+        } else {
+            cr += 1;
+        }
+        i += 1;
+    }
+    let line_endings = match (lf, crlf, cr) {
+        (0, 0, 0) => LineEndings::None,
+        (_, 0, 0) => LineEndings::Lf,
+        (0, _, 0) => LineEndings::CrLf,
+        (0, 0, _) => LineEndings::Cr,
+        _         => LineEndings::Mixed,
+    };
+
+    FileMeta { encoding, bom, line_endings }
+}
+
+// What each line of bytes is, in order.  None if the contents aren't utf8.
+pub fn line_kinds(lang: Lang, bytes: &[u8]) -> Option<Vec<LineKind>> {
+    let mut kinds = vec![];
+    if classify(lang, bytes, |kind| kinds.push(kind)) {
+        Some(kinds)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SpanKind {
+    Code,
+    Comment,
+    String,
+    Blank,
+}
+
+// Part of a line, by byte offsets into it (not counting the \n).
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub kind: SpanKind,
+}
+
+pub struct LineSpans {
+    // What the line counts as, same as line_kinds
+    pub kind: LineKind,
+    pub spans: Vec<Span>,
+}
+
+// Finer grained than line_kinds, for highlighting: which parts of each line are comments,
+// strings or code.  Leading indentation is Blank, other whitespace goes with whatever it's
+// next to.  Strings don't carry over onto the next line.  None if the contents aren't utf8.
+pub fn line_spans(lang: Lang, bytes: &[u8]) -> Option<Vec<LineSpans>> {
+    let kinds = line_kinds(lang, bytes)?;
+    let (singles, multis) = counter_config_for_lang(lang);
+    let quotes = string_quotes(lang);
+
+    let mut multi_stack: Vec<(&str, &str)> = vec![];
+    let mut lines = vec![];
+    for (byte_line, kind) in ByteLines(bytes).lines().zip(kinds) {
+        let line = std::str::from_utf8(byte_line).expect("checked by line_kinds");
+        let mut spans: Vec<Span> = vec![];
+
+        let indent = line.len() - line.trim_start().len();
+        push_span(&mut spans, 0, indent, SpanKind::Blank);
+        let mut pos = indent;
+        while pos < line.len() {
+            let rest = &line[pos..];
+            if let Some(&(_, end)) = multi_stack.last() {
+                let next = if rest.starts_with(end) {
+                    multi_stack.pop();
+                    end.len()
+                } else if let Some(multi) = multis.iter().find(|(start, _)| rest.starts_with(start)) {
+                    multi_stack.push(*multi);
+                    multi.0.len()
+                } else {
+                    rest.chars().next().expect("not empty").len_utf8()
+                };
+                push_span(&mut spans, pos, pos + next, SpanKind::Comment);
+                pos += next;
+            } else if let Some(multi) = multis.iter().find(|(start, _)| rest.starts_with(start)) {
+                multi_stack.push(*multi);
+                push_span(&mut spans, pos, pos + multi.0.len(), SpanKind::Comment);
+                pos += multi.0.len();
+            } else if singles.iter().any(|single| rest.starts_with(single)) {
+                push_span(&mut spans, pos, line.len(), SpanKind::Comment);
+                pos = line.len();
+            } else if let Some(quote) = rest.chars().next().filter(|c| quotes.contains(c)) {
+                let mut end = pos + 1;
+                let mut escaped = false;
+                for c in line[end..].chars() {
+                    end += c.len_utf8();
+                    match c {
+                        _ if escaped    => escaped = false,
+                        '\\'            => escaped = true,
+                        _ if c == quote => break,
+                        _               => {}
+                    }
+                }
+                push_span(&mut spans, pos, end, SpanKind::String);
+                pos = end;
+            } else {
+                let c = rest.chars().next().expect("not empty");
+                let kind = match spans.last() {
+                    Some(last) if c.is_whitespace() => last.kind,
+                    _ => SpanKind::Code,
+                };
+                push_span(&mut spans, pos, pos + c.len_utf8(), kind);
+                pos += c.len_utf8();
+            }
+        }
+        lines.push(LineSpans { kind, spans });
+    }
+    Some(lines)
+}
+
+// Adds start..end to the end of spans, merging it into the last span if it's the same kind.
+fn push_span(spans: &mut Vec<Span>, start: usize, end: usize, kind: SpanKind) {
+    if start == end {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.kind == kind && last.end == start => last.end = end,
+        _ => spans.push(Span { start, end, kind }),
+    }
+}
+
+// How many of the comment lines look like commented-out code rather than prose.  A guess
+// from each line's text alone (see looks_like_code), so it misses some and catches the odd
+// example in a doc comment, but it's enough to see where dead code piles up.  None if the
+// contents aren't utf8.
+pub fn commented_out_code(lang: Lang, bytes: &[u8]) -> Option<u32> {
+    let (singles, multis) = counter_config_for_lang(lang);
+    // Whatever a comment line starts with: //, ///, //!, ####, the * down the side of a
+    // block comment...
+    let markers: Vec<char> = singles.iter().chain(multis.iter().map(|(start, _)| start))
+        .flat_map(|marker| marker.chars())
+        .chain("*!".chars())
+        .collect();
+    let mut dead = 0;
+    for (byte_line, line) in ByteLines(bytes).lines().zip(line_spans(lang, bytes)?) {
+        if line.kind != LineKind::Comment {
+            continue;
+        }
+        let text = std::str::from_utf8(byte_line).expect("checked by line_spans");
+        let comment: String = line.spans.iter()
+            .filter(|span| span.kind == SpanKind::Comment)
+            .map(|span| &text[span.start..span.end])
+            .collect();
+        let mut comment = comment.trim();
+        for (_, end) in &multis {
+            comment = comment.strip_suffix(end).unwrap_or(comment);
+        }
+        if looks_like_code(comment.trim_start_matches(|c| markers.contains(&c))) {
+            dead += 1;
+        }
+    }
+    Some(dead)
+}
+
+// Whether a comment's text reads like a line of code: a statement ending in ; or a brace,
+// an assignment or comparison, or more brackets than prose ever has.  Prose mostly ends in
+// a full stop.
+pub fn looks_like_code(text: &str) -> bool {
+    let text = text.trim();
+    if text.is_empty() {
+        return false;
+    }
+    if text.ends_with(';') || text.ends_with('{') || text.ends_with('}') {
+        return true;
+    }
+    if text.ends_with('.') || text.ends_with('?') || text.ends_with('!') {
+        return false;
+    }
+    if [" = ", "==", "!=", "+=", "-=", ":=", "=>"].iter().any(|op| text.contains(op)) {
+        return true;
+    }
+    let brackets = text.chars().filter(|c| "()[]{}<>;".contains(*c)).count();
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    text.contains('(') && text.contains(')') && brackets * 8 >= chars
+}
+
+// What strings are quoted with, so comment markers inside them aren't highlighted as
+// comments.  Only the common cases: ' is a char literal or lifetime in most C-ish languages.
+fn string_quotes(lang: Lang) -> &'static [char] {
+    match lang {
+        Python | Ruby | Php | Perl | Lua | BourneShell | Zsh | CShell | Sql | Yaml | Toml
+      | CoffeeScript | Elixir | Groovy | Nix | PowerShell => &['"', '\''],
+        JavaScript | TypeScript | Jsx | Tsx | Vue | Svelte => &['"', '\'', '`'],
+        Markdown | Text | ReStructuredText => &[],
+        _ => &['"'],
+    }
+}
+
+// When a line of nothing but whitespace is blank.  In most languages it always is, but in a
+// few the indentation on an otherwise empty line means something, so it's counted as code.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BlankRule {
+    Whitespace,
+    // A tab-only line in a recipe is an empty command, as far as make is concerned
+    MakeRecipe,
+    // An indented line in a block scalar (key: | or key: >) is part of the text
+    YamlBlockScalar,
+}
+
+impl BlankRule {
+    pub fn for_lang(lang: Lang) -> BlankRule {
+        match lang {
+            Make | Makefile => BlankRule::MakeRecipe,
+            Yaml            => BlankRule::YamlBlockScalar,
+            _               => BlankRule::Whitespace,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            BlankRule::Whitespace      => "lines of only whitespace are blank",
+            BlankRule::MakeRecipe      => "lines of only whitespace are blank, except a tab-only line in a \
+                                           recipe, which is an empty command and counts as code",
+            BlankRule::YamlBlockScalar => "lines of only whitespace are blank, except indented ones inside a \
+                                           block scalar (| or >), which are part of its text and count as code",
+        }
+    }
+}
+
+// Follows along line by line to tell which whitespace-only lines the BlankRule makes code.
+struct Blanks {
+    rule: BlankRule,
+    // make: whether we're in a rule's recipe
+    in_recipe: bool,
+    // yaml: the indentation of the line that started the block scalar we're in
+    block: Option<usize>,
+}
+
+impl Blanks {
+    fn new(lang: Lang) -> Blanks {
+        Blanks { rule: BlankRule::for_lang(lang), in_recipe: false, block: None }
+    }
+
+    // Called with every line, untrimmed.  For a whitespace-only line, whether it's code.
+    fn significant(&mut self, line: &str) -> bool {
+        let content = line.trim();
+        match self.rule {
+            BlankRule::Whitespace => false,
+            BlankRule::MakeRecipe => {
+                if content.is_empty() {
+                    return self.in_recipe && line.starts_with('\t');
+                }
+                if !line.starts_with('\t') && !content.starts_with('#') {
+                    // A rule (target: prereqs) starts a recipe, anything else ends one
+                    self.in_recipe = match content.find(':') {
+                        Some(colon) => !content[colon..].trim_start_matches(':').starts_with('=') &&
+                                       content.find('=').map(|eq| colon < eq).unwrap_or(true),
+                        None => false,
+                    };
+                }
+                false
+            }
+            BlankRule::YamlBlockScalar => {
+                let indent = line.len() - line.trim_start().len();
+                if content.is_empty() {
+                    return self.block.is_some() && indent > 0;
+                }
+                if self.block.map(|parent| indent > parent).unwrap_or(false) {
+                    return false;
+                }
+                self.block = if starts_block_scalar(content) { Some(indent) } else { None };
+                false
+            }
+        }
+    }
+}
+
+// key: |, - >-, key: |2 and so on
+fn starts_block_scalar(line: &str) -> bool {
+    let line = line.trim_end_matches(|c: char| c == '-' || c == '+' || c.is_ascii_digit());
+    let before = match line.strip_suffix('|').or_else(|| line.strip_suffix('>')) {
+        Some(before) => before,
+        None => return false,
+    };
+    before.is_empty() || before.ends_with(char::is_whitespace)
+}
+
+// Calls on_line with the kind of each line.  Returns false, possibly partway through,
+// if the contents aren't utf8.
+fn classify<F: FnMut(LineKind)>(lang: Lang, bytes: &[u8], mut on_line: F) -> bool {
+    let (singles, multis) = counter_config_for_lang(lang);
+
     let mut multi_stack: Vec<(&str, &str)> = vec![];
+    let mut blanks = Blanks::new(lang);
 
-    'line: for byte_line in ByteLines(&bytes).lines() {
+    'line: for byte_line in ByteLines(bytes).lines() {
         let line = match std::str::from_utf8(byte_line) {
             Ok(s) => s,
-            // TODO(cgag): should we report when this happens?
-            Err(_) => return Count::default(),
+            Err(_) => return false,
         };
-        c.lines += 1;
 
+        let significant = blanks.significant(line);
         let line = line.trim_start();
         // should blanks within a comment count as blank or comment? This counts them as blank.
         if line.is_empty() {
-            c.blank += 1;
+            on_line(if significant { LineKind::Code } else { LineKind::Blank });
             continue;
         };
 
@@ -643,19 +1241,19 @@ pub fn count(filepath: &str) -> Count {
                         break;
                     }
 
-                    c.comment += 1;
+                    on_line(LineKind::Comment);
                     continue 'line;
                 }
             }
 
             if multis.is_empty() {
-                c.code += 1;
+                on_line(LineKind::Code);
                 continue 'line;
             }
         }
 
         if multi_stack.is_empty() && !multis.iter().any(|(start, end)| line.contains(start) || line.contains(end)) {
-            c.code += 1;
+            on_line(LineKind::Code);
             continue 'line;
         }
 
@@ -674,28 +1272,26 @@ pub fn count(filepath: &str) -> Count {
                 // first multi is the longest.  If we advance position because the final byte
                 // position of that multi hits unicode, we might have skipped over a perfectly
                 // valid comment start that was unaffected by the unicode.
-                if contains_utf8 {
-                    for i in pos..pos + min(max(start_len, end_len) + 1, line_len - pos) {
-                        if !line.is_char_boundary(i) {
-                            pos += 1;
-                            continue 'outer;
-                        }
-                    }
+                if contains_utf8 && (pos..pos + min(max(start_len, end_len) + 1, line_len - pos))
+                                        .any(|i| !line.is_char_boundary(i)) {
+                    pos += 1;
+                    continue 'outer;
                 }
 
-                if pos + start_len <= line_len && &line[pos..pos + start_len] == *start {
+                // Compared as bytes: pos isn't always on a char boundary
+                if line.as_bytes()[pos..].starts_with(start.as_bytes()) {
                     pos += start_len;
                     multi_stack.push(*multi);
                     continue;
                 }
 
                 if !multi_stack.is_empty() {
-                    let (_, mut end) = multi_stack.last().expect("stack last");
-                    if pos+end.len() <= line_len && &line[pos..pos+end.len()] == end {
+                    let &(_, end) = multi_stack.last().expect("stack last");
+                    if line.as_bytes()[pos..].starts_with(end.as_bytes()) {
                         let _ = multi_stack.pop();
                         pos += end.len();
                     }
-                } else if multi_stack.is_empty() && pos < line_len && !&line[pos..pos + 1].chars().next().expect("whitespace check").is_whitespace() {
+                } else if multi_stack.is_empty() && pos < line_len && !line.as_bytes()[pos].is_ascii_whitespace() {
                     found_code += 1;
                 }
             }
@@ -704,13 +1300,13 @@ pub fn count(filepath: &str) -> Count {
 
         // TODO(cgag): can this ever be greater or was that just defensive coding
         if found_code >= multis.len() {
-            c.code += 1;
+            on_line(LineKind::Code);
         } else {
-            c.comment += 1;
+            on_line(LineKind::Comment);
         }
     }
 
-    c
+    true
 }
 
 fn check_shebang(path: &Path) -> Option<String> {
@@ -724,20 +1320,23 @@ fn check_shebang(path: &Path) -> Option<String> {
     };
     let mut bytes = vec![];
     // TODO(cgag): don't need to read full file, just first line
-    file.read_to_end(&mut bytes).expect("nani?!");
-    let s = match std::str::from_utf8(&bytes) {
+    if file.read_to_end(&mut bytes).is_err() {
+        return None;
+    }
+    shebang_ext(&bytes)
+}
+
+fn shebang_ext(bytes: &[u8]) -> Option<String> {
+    let s = match std::str::from_utf8(bytes) {
         Ok(x) => x,
         // TODO(cgag): warning
         Err(_) => return None,
     };
 
-    let first_line = s.lines().next();
-    if first_line.is_none() {
-        return None;
-    }
+    let first_line = s.lines().next()?;
 
     // credit to polyglot (ats line counter) for these shebangs
-    let ext = match first_line.expect("it's some, i'm sure of it") {
+    let ext = match first_line {
         "#!python"
       | "#!python2"
       | "#!python3"
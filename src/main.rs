@@ -4,399 +4,273 @@ extern crate loc;
 extern crate clap;
 extern crate deque;
 extern crate num_cpus;
+#[cfg(feature = "regex-filters")]
 extern crate regex;
 extern crate ignore;
 extern crate edit_distance;
-
-use clap::{Arg, App, AppSettings};
-use ignore::WalkBuilder;
-
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+extern crate serde_json;
+extern crate serde_yaml;
+#[cfg(any(feature = "image", feature = "archives"))]
+extern crate tar;
+#[cfg(any(feature = "image", feature = "archives"))]
+extern crate flate2;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+mod aggregate;
+#[cfg(feature = "archives")]
+mod archive;
+mod badge;
+mod cache;
+mod capabilities;
+mod cocomo;
+mod config;
+mod conformance;
+mod counter;
+mod deps;
+mod detect;
+mod estimate;
+mod filter;
+#[cfg(feature = "git")]
+mod git;
+mod history;
+mod i18n;
+#[cfg(feature = "image")]
+mod image;
+mod memory;
+mod modes;
+mod natlang;
+mod notify;
+mod options;
+mod output;
+mod reload;
+mod render;
+mod sample;
+mod sort;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod throttle;
+mod timings;
+mod template;
+mod temp;
+mod tree;
+mod walk;
+mod worker;
+
+use clap::ArgMatches;
+
+use std::env;
+use std::io::{self, Write};
+use std::mem;
+use std::path::Path;
 use std::thread;
-use std::str::FromStr;
-
-use deque::{Stealer, Stolen};
-use regex::Regex;
-use edit_distance::edit_distance as distance;
-
-use loc::*;
+use std::sync::Arc;
+use std::sync::mpsc;
 
-enum Work {
-    File(String),
-    Quit,
-}
-
-struct Worker {
-    chan: Stealer<Work>,
-}
-
-#[derive(Clone)]
-struct FileCount {
-    path: String,
-    lang: Lang,
-    count: Count,
-}
-
-// This concurrency pattern ripped directly from ripgrep
-impl Worker {
-    fn run(self) -> Vec<FileCount> {
-        let mut v: Vec<FileCount> = vec![];
-        loop {
-            match self.chan.steal() {
-                // What causes these?
-                Stolen::Empty | Stolen::Abort => continue,
-                Stolen::Data(Work::Quit) => break,
-                Stolen::Data(Work::File(path)) => {
-                    let lang = lang_from_ext(&path);
-                    if lang != Lang::Unrecognized {
-                        let count = count(&path);
-                        v.push(FileCount {
-                            lang: lang,
-                            path: path,
-                            count: count,
-                        });
-                    }
-                }
-            };
-        }
-        v
-    }
-}
-
-#[derive(PartialEq)]
-enum Sort {
-    Code,
-    Comment,
-    Blank,
-    Lines,
-    // The following options are only used if the --files flag is NOT present
-    Language,
-    Files,
-}
-
-impl FromStr for Sort {
-    /// A Some variant indicates a suggested value -- the given value was close (perhaps
-    /// because of a typo) to a valid value. None indicates that the given value was not
-    /// close to a correct value.
-    type Err = Option<String>;
-    fn from_str(s: &str) -> Result<Sort, Self::Err> {
-        match s {
-            "blank"    | "Blank"    => Ok(Sort::Blank),
-            "code"     | "Code"     => Ok(Sort::Code),
-            "comment"  | "Comment"  => Ok(Sort::Comment),
-            "lines"    | "Lines"    => Ok(Sort::Lines),
-            "language" | "Language" => Ok(Sort::Language),
-            "files"    | "Files"    => Ok(Sort::Files),
-            s if distance(&s.to_lowercase(), "blank")    <= 2  => Err(Some("Blank".into())),
-            s if distance(&s.to_lowercase(), "code")     <= 2  => Err(Some("Code".into())),
-            s if distance(&s.to_lowercase(), "comment")  <= 2  => Err(Some("Comment".into())),
-            s if distance(&s.to_lowercase(), "lines")    <= 2  => Err(Some("Lines".into())),
-            s if distance(&s.to_lowercase(), "language") <= 2  => Err(Some("Language".into())),
-            s if distance(&s.to_lowercase(), "files")    <= 2  => Err(Some("Files".into())),
-            _ => Err(None)
-        }
-    }
-}
+use memory::{Budget, Overflow, Spill};
+use counter::CountOptions;
+use options::{Options, Scan};
+use output::Format;
+use render::Results;
+use timings::Timings;
+use worker::FileCount;
 
 // TODO(cgag): tune smallvec array sizes
 // TODO(cgag): try smallstring
 // TODO(cgag): more tests for nested comments
 fn main() {
-    let matches = App::new("loc")
-        .global_settings(&[AppSettings::ColoredHelp])
-        .version(crate_version!())
-        .author("Curtis Gagliardi <curtis@curtis.io>")
-        .about("counts things quickly hopefully")
-        .arg(Arg::with_name("exclude")
-            .required(false)
-            .multiple(true)
-            .long("exclude")
-            .value_name("REGEX")
-            .takes_value(true)
-            .help("Rust regex of files to exclude"))
-        .arg(Arg::with_name("include")
-            .required(false)
-            .multiple(true)
-            .long("include")
-            .value_name("REGEX")
-            .takes_value(true)
-            .help("Rust regex matching files to include. Anything not matched will be excluded"))
-        .arg(Arg::with_name("files")
-             .required(false)
-             .long("files")
-             .takes_value(false)
-             .help("Show stats for individual files"))
-        .arg(Arg::with_name("sort")
-            .required(false)
-            .long("sort")
-            .value_name("COLUMN")
-            .takes_value(true)
-            .help("Column to sort by"))
-        .arg(Arg::with_name("unrestricted")
-             .required(false)
-             .multiple(true)
-             .long("unrestricted")
-             .short("u")
-             .takes_value(false)
-             .help("A single -u won't respect .gitignore (etc.) files. Two -u flags will additionally count hidden files and directories."))
-        .arg(Arg::with_name("target")
-            .multiple(true)
-            .help("File or directory to count (multiple arguments accepted)"))
-        .get_matches();
+    let matches = options::app().get_matches_from(config::with_profile(env::args().collect()));
+    if modes::standalone(&matches) {
+        return
+    }
 
-    let targets = match matches.values_of("target") {
-        Some(targets) => targets.collect(),
-        None => vec!["."]
-    };
+    let mut timings = Timings::new(matches.is_present("timings"));
+    timings.phase("setup");
+    let mut opts = options::parse(&matches);
+    if modes::instead_of_walk(&matches, &mut opts) {
+        return
+    }
+    let scan = options::scan(&matches, &opts);
+    if modes::on_tree(&matches, &opts, &scan) {
+        return
+    }
 
-    let sort: Sort = match matches.value_of("sort") {
-        Some(string) => match Sort::from_str(string) {
-            Ok(sort) => sort,
-            Err(err) => {
-                if let Some(suggestion) = err {
-                    println!("Error: invalid value for --sort: '{}', perhaps you meant '{}'?",
-                             string, suggestion);
-                } else {
-                    println!("Error: invalid value for --sort: '{}'", string);
-                }
-                println!(" Hint: legal values are Code, Comment, Blank, Lines, Language, \
-                          and Files");
-                return
-            },
+    timings.phase("walk");
+    let targets = opts.targets();
+    let mut files: Box<dyn Iterator<Item = String>> = match matches.value_of("files-from") {
+        Some(source) => match walk::listed_files(source, matches.is_present("null"), &scan.walk_opts) {
+            Ok(files) => files,
+            Err(e) => {
+                println!("Error: --files-from {}: {}", source, e);
+                std::process::exit(1);
+            }
         },
-        // Default to sorting by lines of code
-        None => Sort::Code,
+        None => Box::new(walk::all_files(if scan.only.is_empty() { &targets } else { &scan.only }, &scan.walk_opts,
+                                         scan.max_files)),
     };
-
-    let by_file: bool = matches.is_present("files");
-
-    if by_file && (sort == Sort::Language || sort == Sort::Files) {
-        println!("Error: cannot sort by Language or Files when --files is present");
+    if modes::on_files(&matches, &opts, &scan, &mut files) {
         return
     }
 
-    let (use_ignore, ignore_hidden) = match matches.occurrences_of("unrestricted") {
-        0 => (true,  true),
-        1 => (false, true),
-        2 => (false, false),
-        _ => (false, false),
-    };
-    let exclude_regex = match matches.values_of("exclude") {
-        Some(regex_strs) => {
-            let combined_regex = regex_strs.map(|r| format!("({})", r)).collect::<Vec<String>>().join("|");
-            match Regex::new(&combined_regex) {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    println!("Error processing exclude regex: {}", e);
+    if let Some(results) = count(&matches, &opts, &scan, files, &mut timings) {
+        timings.phase("output");
+        render::render(&matches, &opts, &scan, results);
+    }
+}
+
+// Counts the files and adds them up by language.  None when that's all there is to do,
+// with the counts already gone out as they came in, or into a git report.
+fn count(matches: &ArgMatches, opts: &Options, scan: &Scan, files: Box<dyn Iterator<Item = String> + '_>,
+         timings: &mut Timings) -> Option<Results> {
+    let verbose = matches.is_present("verbose");
+
+    // --format ndjson can go out as the workers count, as long as it's the only report
+    // and nothing else needs all the counts at the end (--sort included)
+    let streaming = opts.outputs == [(Format::Ndjson, None)] &&
+        !["sort", "sample", "on-limit", "ordered", "append-history", "sqlite", "badge", "notify-webhook", "min-comment-ratio",
+          "lfs", "include-sparse", "churn", "only"].iter().any(|arg| matches.is_present(arg));
+    let (stream, printer) = if streaming {
+        // Bounded, so workers wait on a slow reader rather than the counts piling up
+        let (sender, receiver) = mpsc::sync_channel::<FileCount>(1024);
+        let show_sensitive = opts.show_sensitive;
+        let rootfs = opts.rootfs.clone();
+        let printer = thread::spawn(move || {
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            let shown = receiver.iter().map(|fc| FileCount { path: shown(rootfs.as_deref(), fc.path), ..fc });
+            for fc in shown.filter(|fc| show_sensitive || !filter::is_sensitive(&fc.path)) {
+                // Most likely a closed pipe, with nobody left to tell
+                if writeln!(out, "{}", output::ndjson_line(&fc)).is_err() {
                     std::process::exit(1);
                 }
             }
-        }
-        None => None,
+        });
+        (Some(sender), Some(printer))
+    } else {
+        (None, None)
     };
-    let include_regex = match matches.values_of("include") {
-        Some(regex_strs) => {
-            let combined_regex = regex_strs.map(|r| format!("({})", r)).collect::<Vec<String>>().join("|");
-            match Regex::new(&combined_regex) {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    println!("Error processing include regex: {}", e);
+
+    let budget = Arc::new(match matches.value_of("max-memory") {
+        Some(size) => {
+            let limit = match throttle::parse_size(size) {
+                Some(limit) => limit as usize,
+                None => {
+                    println!("Error: --max-memory takes a size like 2G, got '{}'", size);
                     std::process::exit(1);
                 }
-            }
+            };
+            // Whether anything's going to list the files, or only add them up
+            let listed = opts.by_file ||
+                opts.outputs.iter().any(|o| matches!(o.0, Format::Html | Format::Dot | Format::Treemap | Format::Ndjson)) ||
+                ["sample", "on-limit", "ordered", "sqlite", "min-comment-ratio", "lfs", "include-sparse", "churn", "by-dir",
+                 "group-by-path", "group-by", "ambiguous"].iter().any(|arg| matches.is_present(arg));
+            let overflow = if listed {
+                let rootfs = opts.rootfs.clone();
+                Overflow::Spill(Spill::new(limit / 2, Box::new(move |path| shown(rootfs.as_deref(), path))))
+            } else {
+                Overflow::RollUp
+            };
+            Budget::new(limit, overflow)
         }
-        None => None,
-    };
-
-    let threads = num_cpus::get();
-    let mut workers = vec![];
-    let (workq, stealer) = deque::new();
-    for _ in 0..threads {
-        let worker = Worker { chan: stealer.clone() };
-        workers.push(thread::spawn(|| worker.run()));
+        None => Budget::unlimited(),
+    });
+
+    let ordered = matches.is_present("ordered");
+    let (mut counted, mut walked) = counter::count(files, CountOptions {
+        configs: scan.walk_opts.configs.clone(),
+        meta: matches.is_present("file-meta"),
+        comment_lang: matches.is_present("comment-lang"),
+        dead_code: matches.is_present("dead-code"),
+        cache: scan.cache.clone(),
+        throttle: scan.throttle.clone(),
+        strict: matches.is_present("strict-detection"),
+        budget: budget.clone(),
+        stream,
+        // A --files-from list can name a file twice, or by two paths
+        dedupe: scan.walk_opts.follow || opts.targets().len() > 1 || matches.is_present("files-from"),
+        census: matches.is_present("skipped"),
+        ordered,
+        sample: scan.sample,
+        #[cfg(feature = "archives")]
+        archives: if matches.is_present("archives") { Some(scan.walk_opts.path_filter.clone()) } else { None },
+    }, timings);
+    // The workers' senders were the last ones
+    if let Some(printer) = printer {
+        printer.join().unwrap();
     }
-
-    for target in targets {
-        // TODO(cgag): use WalkParallel?
-        let walker = WalkBuilder::new(target).ignore(use_ignore)
-                                             .git_ignore(use_ignore)
-                                             .git_exclude(use_ignore)
-                                             .hidden(ignore_hidden)
-                                             .build();
-        let files = walker
-            .filter_map(Result::ok)
-            .filter(|entry| entry.file_type().expect("no filetype").is_file())
-            .map(|entry| String::from(entry.path().to_str().unwrap()))
-            .filter(|path| match include_regex {
-                None => true,
-                Some(ref include) => include.is_match(path),
-            })
-            .filter(|path| match exclude_regex {
-                None => true,
-                Some(ref exclude) => !exclude.is_match(path),
-            });
-
-        for path in files {
-            workq.push(Work::File(path));
+    scan.cache.save();
+    if verbose {
+        for line in scan.cache.report() {
+            eprintln!("{}", line);
         }
     }
 
-    for _ in 0..workers.len() {
-        workq.push(Work::Quit);
+    timings.phase("aggregate");
+    counted.relabel(|path| shown(opts.rootfs.as_deref(), path));
+    let roots = opts.counted_roots();
+    let mut filecounts = mem::take(&mut counted.files);
+    timings.workers(mem::take(&mut counted.stats));
+    filecounts.extend(mem::take(&mut counted.rolled_up));
+    if !scan.only.is_empty() {
+        filecounts.extend(scan.cache.counted_under(&roots, &scan.only));
     }
 
-    let mut filecounts: Vec<FileCount> = Vec::new();
-    for worker in workers {
-        filecounts.extend(worker.join().unwrap().iter().cloned())
+    #[cfg(feature = "git")]
+    git::resolve_lfs(matches, &mut counted.lfs_pointers, &mut filecounts);
+    if !counted.lfs_pointers.is_empty() {
+        eprintln!("Warning: {} files are Git LFS pointers and weren't counted{}",
+                  counted.lfs_pointers.len(),
+                  if cfg!(feature = "git") { ", pass --lfs to count what they point to" } else { "" });
     }
+    #[cfg(feature = "git")]
+    git::add_sparse(matches, &roots, &scan.walk_opts.path_filter, &mut filecounts);
 
-    // TODO(cgag): use insecure hashmaps or something
-    let mut by_lang: HashMap<Lang, Vec<FileCount>> = HashMap::new();
-    for fc in filecounts {
-        match by_lang.entry(fc.lang) {
-            Entry::Occupied(mut elem) => elem.get_mut().push(fc),
-            Entry::Vacant(elem) => {
-                elem.insert(vec![fc]);
-            }
-        };
-    }
-
-    let linesep = str_repeat("-", 80);
-
-    if by_file {
-        // print breakdown for each individual file
-        println!("{}", linesep);
-        println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
-                 "Language",
-                 "Files",
-                 "Lines",
-                 "Blank",
-                 "Comment",
-                 "Code");
-        println!("{}", linesep);
-
-        // TODO(cgag): do the summing first, so we can do additional sorting
-        // by totals.
-        for (lang, mut filecounts) in by_lang {
-            let mut total = Count::default();
-            for fc in &filecounts {
-                total.merge(&fc.count);
-            }
-
-            println!("{}", linesep);
-            println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
-                     lang,
-                     filecounts.len(),
-                     total.lines,
-                     total.blank,
-                     total.comment,
-                     total.code);
-
-            match sort {
-                Sort::Code    => filecounts.sort_by(|fc1, fc2| fc2.count.code.cmp(&fc1.count.code)),
-                Sort::Comment => filecounts.sort_by(|fc1, fc2| fc2.count.comment.cmp(&fc1.count.comment)),
-                Sort::Blank   => filecounts.sort_by(|fc1, fc2| fc2.count.blank.cmp(&fc1.count.blank)),
-                Sort::Lines   => filecounts.sort_by(|fc1, fc2| fc2.count.lines.cmp(&fc1.count.lines)),
-                // No sorting by language or files here when using --files. This should
-                // have been checked above.
-                Sort::Language | Sort::Files => panic!("Sorting by language or files \
-                                                        when using the --sort flag"),
-            }
+    // From here on, everything's drawn from by_lang, along with what --max-memory set aside
+    let by_lang = if ordered {
+        let order = mem::take(&mut walked.order).into_iter().map(|(path, i)| (shown(opts.rootfs.as_deref(), path), i)).collect();
+        aggregate::by_lang_in_order(filecounts, &budget.overflow, order)
+    } else {
+        aggregate::by_lang(filecounts, &budget.overflow, &opts.sort)
+    };
 
-            println!("{}", linesep);
-            for fc in filecounts {
-                println!("|{0: <25} {1: >12} {2: >12} {3: >12} {4: >12}",
-                         last_n_chars(&fc.path, 25),
-                         fc.count.lines,
-                         fc.count.blank,
-                         fc.count.comment,
-                         fc.count.code);
-            }
-        }
+    let ambiguous = if matches.is_present("ambiguous") {
+        detect::ambiguous(&scan.walk_opts.configs, by_lang.all())
     } else {
-        // print summary by language
-        let mut lang_totals: HashMap<&Lang, LangTotal> = HashMap::new();
-        for (lang, filecounts) in &by_lang {
-            let mut lang_total = Count::default();
-            for fc in filecounts {
-                lang_total.merge(&fc.count);
-            }
-            lang_totals.insert(lang,
-                               LangTotal {
-                                   files: filecounts.len() as u32,
-                                   count: lang_total,
-                               });
-        }
+        vec![]
+    };
 
-        let mut totals_by_lang = lang_totals.iter().collect::<Vec<(&&Lang, &LangTotal)>>();
-        match sort {
-            Sort::Language => totals_by_lang.sort_by(|&(l1, _), &(l2, _)| l1.to_s().cmp(l2.to_s())),
-            Sort::Files    => totals_by_lang.sort_by(|&(_, c1), &(_, c2)| c2.files.cmp(&c1.files)),
-            Sort::Code     => totals_by_lang.sort_by(|&(_, c1), &(_, c2)| c2.count.code.cmp(&c1.count.code)),
-            Sort::Comment  => totals_by_lang.sort_by(|&(_, c1), &(_, c2)| c2.count.comment.cmp(&c1.count.comment)),
-            Sort::Blank    => totals_by_lang.sort_by(|&(_, c1), &(_, c2)| c2.count.blank.cmp(&c1.count.blank)),
-            Sort::Lines    => totals_by_lang.sort_by(|&(_, c1), &(_, c2)| c2.count.lines.cmp(&c1.count.lines)),
+    #[cfg(feature = "git")]
+    {
+        if git::run_on_counts(matches, &roots, by_lang.all()) {
+            return None;
         }
-
-        print_totals_by_lang(&linesep, &totals_by_lang);
     }
 
-}
-
-// TODO(cgag): i think this is in the stdlib
-fn last_n_chars(s: &str, n: usize) -> String {
-    if s.len() <= n {
-        return String::from(s);
+    if streaming {
+        render::fail_on_unrecognized(&counted.unrecognized_source);
+        return None;
     }
-    s.chars().skip(s.len() - n).collect::<String>()
+    Some(Results { by_lang, counted, walked, ambiguous })
 }
 
-// TODO(cgag): i think this is in the stdlib
-fn str_repeat(s: &str, n: usize) -> String {
-    std::iter::repeat(s).take(n).collect::<Vec<_>>().join("")
-}
-
-fn print_totals_by_lang(linesep: &str, totals_by_lang: &[(&&Lang, &LangTotal)]) {
-    println!("{}", linesep);
-    println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
-             "Language",
-             "Files",
-             "Lines",
-             "Blank",
-             "Comment",
-             "Code");
-    println!("{}", linesep);
-
-    for &(lang, total) in totals_by_lang {
-        println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
-                 lang,
-                 total.files,
-                 total.count.lines,
-                 total.count.blank,
-                 total.count.comment,
-                 total.count.code);
-    }
-
-    let mut totals = LangTotal {
-        files: 0,
-        count: Count::default(),
-    };
-    for &(_, total) in totals_by_lang {
-        totals.files         += total.files;
-        totals.count.code    += total.count.code;
-        totals.count.blank   += total.count.blank;
-        totals.count.comment += total.count.comment;
-        totals.count.lines   += total.count.lines;
+// Paths under the unpacked --image, the way they'd read inside the container
+#[cfg(feature = "image")]
+fn shown(rootfs: Option<&Path>, path: String) -> String {
+    match rootfs {
+        Some(rootfs) => image::shown_path(rootfs, &path),
+        None => path,
     }
+}
 
-    println!("{}", linesep);
-    println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
-             "Total",
-             totals.files,
-             totals.count.lines,
-             totals.count.blank,
-             totals.count.comment,
-             totals.count.code);
-    println!("{}", linesep);
+#[cfg(not(feature = "image"))]
+fn shown(_rootfs: Option<&Path>, path: String) -> String {
+    path
 }
@@ -0,0 +1,255 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use loc::*;
+
+use temp;
+use worker::FileCount;
+
+// --max-memory: a soft ceiling on what a count holds on to, so a gigantic tree can't take
+// a CI runner down with it.  Soft because it goes by loc's own estimate of the paths and
+// counts it keeps, not by what the allocator says.  A quarter of it goes to the paths
+// waiting for a worker and half to the counts the workers keep, leaving the rest for the
+// files being read.  Past that, counts go to the Overflow.
+pub struct Budget {
+    // 0 for no limit
+    limit: usize,
+    queued: AtomicUsize,
+    kept: AtomicUsize,
+    pub overflow: Overflow,
+}
+
+pub enum Overflow {
+    // Nothing's going to list the files, so they only need adding up
+    RollUp,
+    // Something is, so they wait on disk until the count is done
+    Spill(Spill),
+}
+
+impl Budget {
+    pub fn unlimited() -> Budget {
+        Budget::new(0, Overflow::RollUp)
+    }
+
+    pub fn new(limit: usize, overflow: Overflow) -> Budget {
+        Budget {
+            limit,
+            queued: AtomicUsize::new(0),
+            kept: AtomicUsize::new(0),
+            overflow,
+        }
+    }
+
+    // Before the walk queues path: waits for the workers to catch up while the queue is
+    // over its share.
+    pub fn queue(&self, path: &str) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.queued.load(Ordering::Relaxed) > self.limit / 4 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        self.queued.fetch_add(path.len(), Ordering::Relaxed);
+    }
+
+    pub fn dequeue(&self, path: &str) {
+        if self.limit > 0 {
+            self.queued.fetch_sub(path.len(), Ordering::Relaxed);
+        }
+    }
+
+    // Whether a worker can keep fc, or it has to go to the overflow
+    pub fn keep(&self, fc: &FileCount) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+        let size = mem::size_of::<FileCount>() + fc.path.capacity();
+        if self.kept.fetch_add(size, Ordering::Relaxed) + size <= self.limit / 2 {
+            return true;
+        }
+        self.kept.fetch_sub(size, Ordering::Relaxed);
+        false
+    }
+}
+
+// Adds fc to the total for its language in rolled_up
+pub fn roll_up(rolled_up: &mut Vec<FileCount>, fc: FileCount) {
+    match rolled_up.iter_mut().find(|r| r.lang == fc.lang) {
+        Some(total) => {
+            total.count.merge(&fc.count);
+            total.files += fc.files;
+            total.bytes += fc.bytes;
+            total.max_line = total.max_line.max(fc.max_line);
+        }
+        None => rolled_up.push(FileCount { path: String::new(), meta: None, ..fc }),
+    }
+}
+
+// Counts kept in a private temporary file, a line each: the language, lines, blank, comment,
+// code, size, longest line and path, tab separated.  Without --file-meta, which --max-memory
+// doesn't go with.
+pub struct Spill {
+    // How much of it to sort at a time once the count's done, by the same estimate as Budget
+    chunk: usize,
+    // The path as it's to be listed, like Counted::relabel for the counts that were kept
+    shown: Box<dyn Fn(String) -> String + Send + Sync>,
+    file: Mutex<Option<(PathBuf, BufWriter<File>)>>,
+}
+
+impl Spill {
+    pub fn new(chunk: usize, shown: Box<dyn Fn(String) -> String + Send + Sync>) -> Spill {
+        Spill {
+            // Any less and a listing would be merging from a few too many places at once
+            chunk: chunk.max(1 << 16),
+            shown,
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn write(&self, fc: &FileCount) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            let (path, f) = temp::file("loc-spill")?;
+            *file = Some((path, BufWriter::new(f)));
+        }
+        let fc = FileCount { path: (self.shown)(fc.path.clone()), count: fc.count.clone(), meta: None, ..*fc };
+        file.as_mut().expect("just created").1.write_all(line(&fc).as_bytes())?;
+        Ok(())
+    }
+
+    // Everything written, sorted into runs a listing can merge back a language at a time: a
+    // chunk at a time, with each chunk's files grouped by language and put in order.  each
+    // sees every file on the way.
+    pub fn sort(&self, order: &dyn Fn(&FileCount, &FileCount) -> cmp::Ordering,
+                each: &mut dyn FnMut(&FileCount)) -> io::Result<Option<Runs>> {
+        let (spill_path, mut file) = match self.file.lock().unwrap().take() {
+            Some((path, file)) => (path, file.into_inner().map_err(|e| e.into_error())?),
+            None => return Ok(None),
+        };
+        file.seek(SeekFrom::Start(0))?;
+        let (path, runs_file) = temp::file("loc-runs")?;
+        let mut out = BufWriter::new(runs_file);
+        let mut runs = vec![];
+        let mut written = 0;
+        let mut lines = BufReader::new(file).lines();
+        loop {
+            let mut chunk = vec![];
+            let mut size = 0;
+            while size < self.chunk {
+                let fc = match lines.next() {
+                    Some(line) => parse(&line?)?,
+                    None => break,
+                };
+                each(&fc);
+                size += mem::size_of::<FileCount>() + fc.path.capacity();
+                chunk.push(fc);
+            }
+            if chunk.is_empty() {
+                break;
+            }
+            chunk.sort_by(|a, b| a.lang.cmp(&b.lang).then_with(|| order(a, b)));
+            let mut run: HashMap<Lang, (u64, u64)> = HashMap::new();
+            for fc in chunk {
+                let line = line(&fc);
+                out.write_all(line.as_bytes())?;
+                let section = run.entry(fc.lang).or_insert((written, written));
+                written += line.len() as u64;
+                section.1 = written;
+            }
+            runs.push(run);
+        }
+        out.flush()?;
+        drop(lines);
+        fs::remove_file(&spill_path)?;
+        Ok(Some(Runs { path, runs }))
+    }
+}
+
+// The spill, sorted.  For each run, where each language's files are in the file.
+pub struct Runs {
+    path: PathBuf,
+    runs: Vec<HashMap<Lang, (u64, u64)>>,
+}
+
+impl Runs {
+    // lang's files in each run, each in order
+    pub fn sections(&self, lang: Lang) -> io::Result<Vec<Section>> {
+        let mut sections = vec![];
+        for &(start, end) in self.runs.iter().filter_map(|run| run.get(&lang)) {
+            let mut file = File::open(&self.path)?;
+            file.seek(SeekFrom::Start(start))?;
+            sections.push(Section { lines: BufReader::new(file.take(end - start)).lines() });
+        }
+        Ok(sections)
+    }
+}
+
+pub struct Section {
+    lines: io::Lines<BufReader<io::Take<File>>>,
+}
+
+impl Iterator for Section {
+    type Item = FileCount;
+
+    fn next(&mut self) -> Option<FileCount> {
+        let line = self.lines.next()?;
+        match line.and_then(|line| parse(&line)) {
+            Ok(fc) => Some(fc),
+            Err(e) => {
+                println!("Error: --max-memory: reading back the counts set aside on disk: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn line(fc: &FileCount) -> String {
+    let c = &fc.count;
+    // The path's last, so tabs in it are fine, but not newlines
+    let path = fc.path.replace('\\', "\\\\").replace('\n', "\\n");
+    format!("{:?}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            fc.lang, c.lines, c.blank, c.comment, c.code, fc.bytes, fc.max_line, path)
+}
+
+fn parse(line: &str) -> io::Result<FileCount> {
+    let fields: Vec<&str> = line.splitn(8, '\t').collect();
+    let bad = || io::Error::new(io::ErrorKind::InvalidData, "bad line in the spill file");
+    if fields.len() < 8 {
+        return Err(bad());
+    }
+    let n = |i: usize| fields[i].parse::<u32>().map_err(|_| bad());
+    Ok(FileCount {
+        lang: Lang::from_name(fields[0]).ok_or_else(bad)?,
+        count: Count { lines: n(1)?, blank: n(2)?, comment: n(3)?, code: n(4)? },
+        path: unescape(fields[7]),
+        meta: None,
+        files: 1,
+        bytes: fields[5].parse::<u64>().map_err(|_| bad())?,
+        max_line: n(6)?,
+    })
+}
+
+fn unescape(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(c)   => out.push(c),
+            None      => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,296 @@
+// Everything loc does other than count a tree and report on it: each runs instead of the
+// usual count if its flag was given, and returns whether it did.
+use std::fs;
+use std::io::{self, Read};
+use std::mem;
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use loc::*;
+
+use aggregate;
+use capabilities;
+use conformance::{self, Snippets};
+use deps;
+use detect;
+use estimate;
+#[cfg(feature = "git")]
+use git;
+use memory::Overflow;
+use options::{self, Options, Scan};
+use output::{self, Format};
+use walk;
+use worker::FileCount;
+
+// The ones that don't count anything, or take any of the usual flags
+pub fn standalone(matches: &ArgMatches) -> bool {
+    if matches.is_present("capabilities") {
+        capabilities::print(&options::app());
+        return true;
+    }
+    if let Some(dir) = matches.value_of("gen-corpus") {
+        gen_corpus(matches, dir);
+        return true;
+    }
+    if matches.is_present("conformance") {
+        conformance(matches);
+        return true;
+    }
+    false
+}
+
+// Reports with nothing to walk: whatever's on stdin, or saved reports to print again
+pub fn instead_of_walk(matches: &ArgMatches, opts: &mut Options) -> bool {
+    // `loc -`: whatever's piped in, which has no file name to go by for its language
+    let targets = opts.targets();
+    if targets.contains(&"-") {
+        if targets.len() > 1 {
+            println!("Error: - (stdin) can't be counted along with other targets");
+            std::process::exit(1);
+        }
+        let mut bytes = vec![];
+        if let Err(e) = io::stdin().read_to_end(&mut bytes) {
+            println!("Error: reading stdin: {}", e);
+            std::process::exit(1);
+        }
+        let lang = match matches.value_of("stdin-lang") {
+            Some(name) => match Lang::from_name(name).or_else(|| Some(lang_for_ext(name)).filter(|&l| l != Lang::Unrecognized)) {
+                Some(lang) => lang,
+                None => {
+                    println!("Error: unknown language for --stdin-lang: '{}'", name);
+                    std::process::exit(1);
+                }
+            },
+            // Unless there's a shebang
+            None => match lang_from_bytes("-", &bytes) {
+                Lang::Unrecognized => {
+                    println!("Error: pass --stdin-lang to say what language stdin is in");
+                    std::process::exit(1);
+                }
+                lang => lang,
+            },
+        };
+        let count = strip_ignored(&bytes).map(|text| count_bytes(lang, &text)).unwrap_or_default();
+        let fc = FileCount {
+            path: "-".to_string(),
+            lang,
+            count,
+            meta: None,
+            files: 1,
+            bytes: bytes.len() as u64,
+            max_line: longest_line(&bytes),
+        };
+        output::write_reports(&opts.outputs, &output::Counts {
+            by_lang: aggregate::by_lang(vec![fc], &Overflow::RollUp, &opts.sort),
+            by_file: opts.by_file,
+            show_sensitive: true,
+            others: &opts.others,
+            template: opts.template.as_ref(),
+            percent: opts.percent,
+            totals_only: opts.totals_only,
+            cocomo: opts.cocomo,
+        }, opts.colors);
+        return true;
+    }
+
+    if !opts.reloaded.is_empty() {
+        if opts.reloaded.len() < targets.len() || ["by-dir", "group-by-path", "group-by"].iter().any(|arg| matches.is_present(arg)) {
+            println!("Error: a saved report can only be printed again in another --format, not \
+                      counted along with other targets or split up by directory or path");
+            std::process::exit(1);
+        }
+        let per_file = opts.by_file || opts.outputs.iter().any(|o| matches!(o.0, Format::Html | Format::Dot | Format::Treemap));
+        if per_file && opts.reloaded.iter().any(|r| !r.listed) {
+            println!("Error: a report without every file in it (--format json --files) can only be \
+                      printed as totals per language");
+            std::process::exit(1);
+        }
+        let hidden: usize = opts.reloaded.iter().map(|r| r.hidden).sum();
+        if hidden > 0 {
+            eprintln!("Warning: the reports left out {} sensitive files, so their lines aren't in \
+                       the totals", hidden);
+        }
+        let by_lang = aggregate::by_lang(mem::take(&mut opts.reloaded).into_iter().flat_map(|r| r.filecounts).collect(), &Overflow::RollUp, &opts.sort);
+        if matches.is_present("family") {
+            if output::print_families(&mut io::stdout().lock(), &by_lang, &opts.sort).is_err() {
+                std::process::exit(1);
+            }
+            return true;
+        }
+        output::write_reports(&opts.outputs, &output::Counts {
+            by_lang,
+            by_file: opts.by_file,
+            show_sensitive: opts.show_sensitive,
+            others: &opts.others,
+            template: opts.template.as_ref(),
+            percent: opts.percent,
+            totals_only: opts.totals_only,
+            cocomo: opts.cocomo,
+        }, opts.colors);
+        return true;
+    }
+    false
+}
+
+// The ones that look at the tree some other way than counting its lines
+pub fn on_tree(matches: &ArgMatches, opts: &Options, scan: &Scan) -> bool {
+    if matches.is_present("explain-ignores") {
+        for root in opts.roots() {
+            walk::explain_ignores(root, &scan.walk_opts);
+        }
+        return true;
+    }
+
+    if let Some(path) = matches.value_of("explain") {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                println!("Error: --explain {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        let lang = detect::lang(&scan.walk_opts.configs, path);
+        let decoded = scan.walk_opts.configs.encoding(path, lang).map(|charset| charset.decode(&bytes));
+        let text = decoded.as_deref().unwrap_or(&bytes);
+        match line_kinds(lang, text) {
+            Some(kinds) => output::print_explain(path, lang, &kinds, &String::from_utf8_lossy(text)),
+            None => {
+                println!("Error: --explain {}: not utf-8, or in an encoding .loc.toml doesn't name", path);
+                std::process::exit(1);
+            }
+        }
+        return true;
+    }
+
+    if matches.is_present("cargo-deps") {
+        let top = matches.value_of("cargo-deps").unwrap_or("0");
+        let top = match top.parse::<usize>() {
+            Ok(top) => top,
+            Err(_) => {
+                println!("Error: --cargo-deps takes how many crates to list, got '{}'", top);
+                std::process::exit(1);
+            }
+        };
+        match deps::cargo_deps(opts.roots()[0], &scan.walk_opts) {
+            Ok(deps) => output::print_deps("Crate", deps, top),
+            Err(e) => {
+                println!("Error: --cargo-deps: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return true;
+    }
+
+    if matches.is_present("doc-coverage") {
+        let files = opts.targets().into_iter().flat_map(|target| walk::files(target, &scan.walk_opts));
+        let crates = loc::doc_coverage::by_crate(files);
+        if crates.is_empty() {
+            println!("Error: --doc-coverage found no Rust crates (a Cargo.toml with a src/) to check");
+            std::process::exit(1);
+        }
+        output::print_doc_coverage(&crates);
+        return true;
+    }
+
+    if matches.is_present("npm-deps") {
+        let top = matches.value_of("npm-deps").unwrap_or("20");
+        let top = match top.parse::<usize>() {
+            Ok(top) => top,
+            Err(_) => {
+                println!("Error: --npm-deps takes how many packages to list, got '{}'", top);
+                std::process::exit(1);
+            }
+        };
+        match deps::npm_deps(opts.roots()[0], &scan.walk_opts) {
+            Ok(deps) => output::print_deps("Package", deps, top),
+            Err(e) => {
+                println!("Error: --npm-deps: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return true;
+    }
+    false
+}
+
+// The ones that take the walk's files but do something else with them
+#[cfg_attr(not(feature = "git"), allow(unused_variables))]
+pub fn on_files(matches: &ArgMatches, opts: &Options, scan: &Scan, files: &mut dyn Iterator<Item = String>) -> bool {
+    #[cfg(feature = "git")]
+    {
+        if git::run(matches, &opts.roots(), &scan.walk_opts.path_filter, files) {
+            return true;
+        }
+    }
+
+    if matches.is_present("count-files-only") {
+        output::print_file_counts(&estimate::file_counts(files, &scan.walk_opts.configs));
+        return true;
+    }
+
+    if matches.is_present("estimate") {
+        output::print_size_estimates(&estimate::estimate(files, &scan.walk_opts.configs));
+        return true;
+    }
+    false
+}
+
+// loc --gen-corpus DIR [--corpus-files N] [--corpus-lang rust] [--corpus-seed SEED]: a
+// synthetic tree whose counts are known in advance, for benchmarks.  Not in --help.
+fn gen_corpus(matches: &ArgMatches, dir: &str) {
+    let files = match matches.value_of("corpus-files").unwrap_or("1000").parse::<usize>() {
+        Ok(files) => files,
+        Err(_) => {
+            println!("Error: --corpus-files takes a number of files");
+            std::process::exit(1);
+        }
+    };
+    let seed = match matches.value_of("corpus-seed").unwrap_or("0").parse::<u64>() {
+        Ok(seed) => seed,
+        Err(_) => {
+            println!("Error: --corpus-seed takes a number");
+            std::process::exit(1);
+        }
+    };
+    let lang = matches.value_of("corpus-lang").unwrap_or("rust");
+    let lang = match Lang::from_name(lang) {
+        Some(lang) => lang,
+        None => {
+            println!("Error: unknown language: '{}'", lang);
+            std::process::exit(1);
+        }
+    };
+    match loc::corpus::generate(Path::new(dir), lang, files, seed) {
+        Ok(count) => output::print_corpus(dir, lang, &LangTotal { files: files as u32, count, ..LangTotal::default() }),
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// loc --conformance [DIR]: counts the snippets built in from tests/conformance (or the ones
+// in DIR) and checks they come out as expected
+fn conformance(matches: &ArgMatches) {
+    let dirs: Vec<&str> = matches.values_of("target").map(Iterator::collect).unwrap_or_default();
+    let snippets = match dirs[..] {
+        [] => Snippets::BuiltIn,
+        [dir] => Snippets::Dir(Path::new(dir)),
+        _ => {
+            println!("Error: --conformance takes at most one directory of snippets");
+            std::process::exit(1);
+        }
+    };
+    match conformance::run(&snippets) {
+        Ok(cases) => {
+            if !output::print_conformance(&cases) {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            println!("Error: --conformance: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use loc::*;
+
+// --comment-lang: what (human) language a file's comments are written in.  Nothing clever:
+// scripts other than Latin mostly give themselves away, and for Latin ones we count
+// common short words.  Good enough to find the Spanish corner of a legacy codebase.
+
+// Each language's most common little words, picked to overlap as little as possible.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("English",    &["the", "and", "is", "of", "to", "this", "that", "it", "for", "with", "be", "are", "if", "we", "not", "an"]),
+    ("Spanish",    &["el", "los", "las", "que", "y", "es", "por", "para", "con", "una", "se", "del", "si", "esta", "como", "lo"]),
+    ("French",     &["le", "les", "des", "et", "est", "pour", "une", "dans", "pas", "du", "ce", "sur", "avec", "qui", "au", "sont"]),
+    ("German",     &["der", "die", "das", "und", "ist", "nicht", "ein", "eine", "zu", "mit", "für", "den", "auf", "wird", "sich", "auch"]),
+    ("Portuguese", &["os", "em", "um", "uma", "não", "do", "da", "é", "são", "ao", "dos", "das", "isso", "este", "pelo", "mais"]),
+    ("Italian",    &["il", "di", "che", "è", "per", "un", "non", "della", "sono", "questo", "gli", "nel", "alla", "anche", "come", "essere"]),
+    ("Dutch",      &["het", "een", "en", "van", "niet", "dat", "voor", "met", "op", "te", "zijn", "wordt", "deze", "bij", "ook", "naar"]),
+];
+
+// Fewer stopwords than this and we don't guess
+const MIN_HITS: usize = 3;
+
+// The language of the comments in bytes, if there are enough of them to tell.
+pub fn comment_lang(lang: Lang, bytes: &[u8]) -> Option<&'static str> {
+    let mut text = String::new();
+    // Same lines line_spans sees, apart from an empty one after a trailing \n
+    for (line, spans) in bytes.split(|&b| b == b'\n').zip(line_spans(lang, bytes)?) {
+        for span in spans.spans.iter().filter(|s| s.kind == SpanKind::Comment) {
+            text.push_str(&String::from_utf8_lossy(&line[span.start..span.end]));
+            text.push('\n');
+        }
+    }
+    detect(&text)
+}
+
+pub fn detect(text: &str) -> Option<&'static str> {
+    if let Some(script) = script(text) {
+        return Some(script);
+    }
+
+    let mut hits = vec![0; STOPWORDS.len()];
+    for word in text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()) {
+        let word = word.to_lowercase();
+        for (i, &(_, words)) in STOPWORDS.iter().enumerate() {
+            if words.contains(&&*word) {
+                hits[i] += 1;
+            }
+        }
+    }
+    let (best, &n) = hits.iter().enumerate().max_by_key(|&(_, n)| n)?;
+    if n < MIN_HITS {
+        return None;
+    }
+    Some(STOPWORDS[best].0)
+}
+
+// Languages with their own script.  Whichever script has the most letters wins, as long
+// as it's a good chunk of them: code in comments is always full of Latin letters.
+fn script(text: &str) -> Option<&'static str> {
+    let mut latin = 0;
+    let mut counts: Vec<(&'static str, usize)> = vec![];
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        let name = match c as u32 {
+            0x3040..=0x30FF => "Japanese",
+            0x4E00..=0x9FFF => "Chinese",
+            0xAC00..=0xD7AF => "Korean",
+            0x0400..=0x04FF => "Russian",
+            0x0370..=0x03FF => "Greek",
+            0x0590..=0x05FF => "Hebrew",
+            0x0600..=0x06FF => "Arabic",
+            0x0900..=0x097F => "Hindi",
+            0x0E00..=0x0E7F => "Thai",
+            _ => {
+                latin += 1;
+                continue;
+            }
+        };
+        match counts.iter_mut().find(|&&mut (n, _)| n == name) {
+            Some(count) => count.1 += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+    // Kanji are Chinese characters, so any kana at all means Japanese
+    if let Some(kanji) = counts.iter().position(|&(name, _)| name == "Chinese") {
+        if let Some(kana) = counts.iter().position(|&(name, _)| name == "Japanese") {
+            counts[kana].1 += counts[kanji].1;
+            counts.remove(kanji);
+        }
+    }
+    let &(name, n) = counts.iter().max_by_key(|&&(_, n)| n)?;
+    // CJK says far more per character than an alphabet does
+    let weight = if matches!(name, "Japanese" | "Chinese" | "Korean") { 4 } else { 1 };
+    if n * weight * 4 >= latin {
+        Some(name)
+    } else {
+        None
+    }
+}
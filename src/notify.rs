@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use serde_json::{self, Value};
+
+use aggregate::ByLang;
+use output::lang_name;
+
+// How many languages the message lists before lumping the rest together
+const TOP: usize = 8;
+
+// Files and code per language (by the name it's printed under) from an earlier run's
+// --format json report, plus the totals.
+pub struct Baseline {
+    files: u32,
+    code: u32,
+    langs: HashMap<String, (u32, u32)>,
+}
+
+pub fn load_baseline(path: &str) -> io::Result<Baseline> {
+    let json: Value = serde_json::from_slice(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let num = |v: &Value, key: &str| v[key].as_u64().unwrap_or(0) as u32;
+    let langs = json["languages"].as_array()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a --format json report"))?
+        .iter()
+        .filter_map(|l| Some((l["language"].as_str()?.to_string(), (num(l, "files"), num(l, "code")))))
+        .collect();
+    Ok(Baseline { files: num(&json["total"], "files"), code: num(&json["total"], "code"), langs })
+}
+
+// A few lines for a chat channel: the totals, then code per language, biggest first, with
+// the change since the baseline when there is one.  In a code block, so the columns line
+// up in Slack and Teams alike.
+pub fn message(by_lang: &ByLang, baseline: Option<&Baseline>) -> String {
+    let mut rows: Vec<(&str, u32, u32)> = by_lang.langs().iter()
+        .map(|(lang, total)| (lang_name(lang), total.files, total.count.code))
+        .collect();
+    rows.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+
+    let files = by_lang.total().files;
+    let code: u32 = rows.iter().map(|r| r.2).sum();
+    let mut text = format!("loc: {} lines of code in {} files", code, files);
+    if let Some(base) = baseline {
+        text += &format!(" ({} code, {} files since the baseline)", delta(code, base.code), delta(files, base.files));
+    }
+    text += "\n```\n";
+    let width = rows.iter().take(TOP).map(|r| r.0.len()).max().unwrap_or(0);
+    for &(name, _, code) in rows.iter().take(TOP) {
+        text += &format!("{:<width$} {:>10}", name, code, width = width);
+        if let Some(base) = baseline {
+            let before = base.langs.get(name).map(|l| l.1).unwrap_or(0);
+            text += &format!(" {:>8}", delta(code, before));
+        }
+        text += "\n";
+    }
+    if rows.len() > TOP {
+        text += &format!("({} more)\n", rows.len() - TOP);
+    }
+    if let Some(base) = baseline {
+        let mut gone: Vec<&String> = base.langs.iter()
+            .filter(|&(name, &(_, code))| code > 0 && !rows.iter().any(|r| r.0 == name))
+            .map(|(name, _)| name)
+            .collect();
+        gone.sort();
+        for name in gone {
+            text += &format!("{} gone ({})\n", name, delta(0, base.langs[name].1));
+        }
+    }
+    text += "```";
+    text
+}
+
+fn delta(now: u32, before: u32) -> String {
+    let d = i64::from(now) - i64::from(before);
+    if d > 0 { format!("+{}", d) } else { d.to_string() }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    // Slack and Teams incoming webhooks both take {"text": ...}
+    text: &'a str,
+}
+
+// Posts with curl rather than pulling an http client (and tls) into every build.  The url
+// goes in as --url's value, so one starting with - can't be taken for another option.
+pub fn post(url: &str, text: &str) -> io::Result<()> {
+    let body = serde_json::to_vec(&Payload { text }).expect("serialize webhook payload");
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "30",
+               "-H", "Content-Type: application/json", "--data-binary", "@-", "--url", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("couldn't run curl: {}", e)))?;
+    curl.stdin.take().expect("curl stdin").write_all(&body)?;
+    let out = curl.wait_with_output()?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,1061 @@
+// Turning the command line into what each stage needs: Options for what to report and how,
+// parsed before anything else so a bad flag fails fast, and Scan for what to walk and count,
+// parsed only once we know there's a tree to walk.
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::{Arg, App, AppSettings, ArgMatches};
+
+use loc::*;
+
+use cache::{self, Cache};
+use cocomo;
+use config::{self, Configs};
+use filter::{self, PathFilter};
+#[cfg(feature = "git")]
+use git;
+use i18n;
+#[cfg(feature = "image")]
+use image;
+use notify::{self, Baseline};
+use output::{self, Colors, Column, Format, Others, TotalsOnly};
+use reload::{self, Reloaded};
+use sample::Sample;
+use sort::Sort;
+use template::{self, Template};
+use throttle::{self, Throttle};
+use walk::{self, WalkOptions};
+
+pub struct Options<'a> {
+    // Kept for the whole run, since the unpacked image goes when this does
+    #[cfg(feature = "image")]
+    _image: Option<image::Extracted>,
+    pub rootfs: Option<PathBuf>,
+    targets: Vec<String>,
+    // Earlier --format json reports, to print again in some other format instead of counting
+    pub reloaded: Vec<Reloaded>,
+    pub sort: Sort,
+    pub others: Others,
+    pub by_file: bool,
+    pub template: Option<Template>,
+    pub outputs: Vec<(Format, Option<&'a str>)>,
+    pub table_only: bool,
+    pub colors: Colors,
+    pub cocomo: Option<f64>,
+    pub totals_only: Option<TotalsOnly>,
+    pub baseline: Option<Baseline>,
+    pub show_sensitive: bool,
+    pub percent: bool,
+}
+
+impl<'a> Options<'a> {
+    pub fn targets(&self) -> Vec<&str> {
+        self.targets.iter().map(String::as_str).collect()
+    }
+
+    // The directories the targets live in, for anything that can't take a glob target.
+    pub fn roots(&self) -> Vec<&str> {
+        self.targets.iter().map(|target| walk::split_glob(target).0).collect()
+    }
+
+    // What the counted paths are relative to, once they're shown the way they'd read
+    // inside an --image
+    pub fn counted_roots(&self) -> Vec<&str> {
+        if self.rootfs.is_some() { vec!["."] } else { self.roots() }
+    }
+}
+
+pub fn parse<'a>(matches: &'a ArgMatches) -> Options<'a> {
+    let repo_root = if matches.is_present("repo-root") {
+        match walk::repo_root() {
+            Some(root) => Some(root),
+            None => {
+                println!("Error: --repo-root: not inside a git repository");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(feature = "image")]
+    let image = matches.value_of("image").map(|image| match image::extract(image) {
+        Ok(extracted) => extracted,
+        Err(e) => {
+            println!("Error: --image {}: {}", image, e);
+            std::process::exit(1);
+        }
+    });
+    #[cfg(feature = "image")]
+    let rootfs = image.as_ref().map(|image| image.rootfs.clone());
+    #[cfg(not(feature = "image"))]
+    let rootfs: Option<PathBuf> = None;
+    let image_root = rootfs.as_ref().map(|rootfs| rootfs.to_string_lossy().into_owned());
+
+    // With @name for a set of targets from .loc.toml, and its default set when there are none
+    let given: Option<Vec<String>> = match (matches.values_of("target"), &repo_root, &image_root) {
+        (Some(targets), _, _) => Some(targets.flat_map(config::expand_target).collect()),
+        (None, None, None) => config::target_set("default"),
+        _ => None,
+    };
+    let targets: Vec<String> = match (given, repo_root, image_root) {
+        (Some(targets), _, _) => targets,
+        (None, Some(root), _) => vec![root],
+        (None, None, Some(root)) => vec![root],
+        (None, None, None) => vec![".".to_string()]
+    };
+
+    let mut reloaded = vec![];
+    for target in &targets {
+        match reload::load(target) {
+            Ok(Some(report)) => reloaded.push(report),
+            Ok(None) => {}
+            Err(e) => {
+                println!("Error: {}: {}", target, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let sort: Sort = match matches.value_of("sort") {
+        Some(string) => match Sort::from_str(string) {
+            Ok(sort) => sort,
+            Err((key, suggestion)) => {
+                if let Some(suggestion) = suggestion {
+                    println!("Error: invalid value for --sort: '{}', perhaps you meant '{}'?",
+                             key, suggestion);
+                } else {
+                    println!("Error: invalid value for --sort: '{}'", key);
+                }
+                println!(" Hint: legal values are Code, Comment, Blank, Lines, Language, \
+                          and Files, each optionally followed by :asc or :desc, separated by commas");
+                std::process::exit(1);
+            },
+        },
+        None => Sort::default(),
+    };
+
+    let mut renames = config::renamed_langs();
+    for rename in matches.values_of("rename-lang").into_iter().flatten() {
+        match rename.find('=') {
+            Some(i) => renames.insert(rename[..i].to_string(), rename[i + 1..].to_string()),
+            None => {
+                println!("Error: --rename-lang takes LANG=NAME, got '{}'", rename);
+                std::process::exit(1);
+            }
+        };
+    }
+    // A saved report keeps the names it was printed with, unless they're renamed again
+    for (lang, name) in reloaded.iter().flat_map(|r| &r.renames) {
+        renames.entry(lang.clone()).or_insert_with(|| name.clone());
+    }
+    let mut lang_names = HashMap::new();
+    for (lang, name) in renames {
+        match Lang::from_name(&lang) {
+            Some(lang) => lang_names.insert(lang, name),
+            None => {
+                println!("Error: unknown language to rename: '{}'", lang);
+                std::process::exit(1);
+            }
+        };
+    }
+    output::set_lang_names(lang_names);
+    if let Some(ui) = matches.value_of("lang-ui").and_then(i18n::UiLang::from_code) {
+        i18n::set_ui(ui);
+    }
+    if let Some(names) = matches.value_of("columns") {
+        let mut columns = vec![];
+        for name in names.split(',').map(str::trim) {
+            match Column::from_name(name) {
+                Some(col) if columns.contains(&col) => {
+                    println!("Error: --columns lists '{}' twice", name);
+                    std::process::exit(1);
+                }
+                Some(col) => columns.push(col),
+                None => {
+                    println!("Error: unknown column '{}', --columns takes files, lines, blank, comment, code, bytes, avg-line and max-line", name);
+                    std::process::exit(1);
+                }
+            }
+        }
+        output::set_columns(columns);
+    }
+
+    let others = Others {
+        min_lines: match matches.value_of("min-lines").map(str::parse) {
+            Some(Ok(n)) => n,
+            Some(Err(_)) => {
+                println!("Error: --min-lines takes a number of lines");
+                std::process::exit(1);
+            }
+            None => 0,
+        },
+        min_share: match matches.value_of("others-threshold").map(|p| p.trim_end_matches('%').parse::<f64>()) {
+            Some(Ok(percent)) if (0.0..=100.0).contains(&percent) => percent / 100.0,
+            Some(_) => {
+                println!("Error: --others-threshold takes a percentage, e.g. 1%");
+                std::process::exit(1);
+            }
+            None => 0.0,
+        },
+        top: match matches.value_of("top").map(str::parse) {
+            Some(Ok(n)) => n,
+            Some(Err(_)) => {
+                println!("Error: --top takes a number of languages");
+                std::process::exit(1);
+            }
+            None => 0,
+        },
+    };
+
+    let by_file: bool = matches.is_present("files");
+    let template = matches.value_of("template").map(|template| match Template::parse(template) {
+        Ok(template) => template,
+        Err(e) => {
+            println!("Error: --template: {}", e);
+            std::process::exit(1);
+        }
+    });
+    let explicit_format = matches.value_of("format").and_then(Format::from_name);
+    let format = match explicit_format {
+        Some(format) => format,
+        None if template.is_some() => Format::Template,
+        None => Format::Table,
+    };
+
+    // Where each report goes, None for stdout.  Without a FORMAT: or --format, the file's
+    // extension picks the format.
+    let outputs: Vec<(Format, Option<&str>)> = match matches.values_of("output") {
+        Some(outputs) => outputs.map(|output| {
+            let named = output.split_once(':').and_then(|(name, path)| Some((Format::from_name(name)?, path)));
+            let (format, path) = match named {
+                Some(named) => named,
+                None if explicit_format.is_none() && template.is_none() =>
+                    (Format::from_ext(output).unwrap_or(format), output),
+                None => (format, output),
+            };
+            (format, if path == "-" { None } else { Some(path) })
+        }).collect(),
+        None => vec![(format, None)],
+    };
+    if outputs.iter().any(|o| o.0 == Format::Template) {
+        match template {
+            None => {
+                println!("Error: --output template:FILE needs a --template");
+                std::process::exit(1);
+            }
+            Some(ref template) if template.uses(template::Field::Path) && !matches.is_present("files") => {
+                println!("Error: --template: {{path}} needs --files");
+                std::process::exit(1);
+            }
+            Some(_) => {}
+        }
+    }
+    // The notes after the table only make sense when nothing but tables go to stdout
+    let to_stdout: Vec<Format> = outputs.iter().filter(|o| o.1.is_none()).map(|o| o.0).collect();
+    let table_only = !to_stdout.is_empty() && to_stdout.iter().all(|&f| f == Format::Table);
+    // Only the summary table folds languages into Other, and anything else would quietly
+    // list them all
+    if let Some(arg) = ["top", "min-lines", "others-threshold"].iter().find(|arg| matches.is_present(arg)) {
+        let elsewhere = by_file || outputs.iter().any(|o| o.0 != Format::Table) ||
+            ["by-dir", "group-by-path", "group-by", "family"].iter().any(|arg| matches.is_present(arg));
+        if elsewhere {
+            println!("Error: --{} only folds languages in the summary table, not with --files, --by-dir, \
+                      --family or another --format", arg);
+            std::process::exit(1);
+        }
+    }
+    // Only the summary table is colored, and only on a terminal unless asked
+    let color = match matches.value_of("color").unwrap_or("auto") {
+        "always" => true,
+        "never"  => false,
+        _        => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() &&
+                    env::var("TERM").map(|term| term != "dumb").unwrap_or(true),
+    };
+    let colors = match (color, matches.is_present("heat")) {
+        (false, _)    => Colors::Off,
+        (true, false) => Colors::On,
+        (true, true)  => Colors::Heat,
+    };
+    let cocomo = if matches.is_present("cocomo") {
+        match matches.value_of("avg-wage").map(str::parse::<f64>) {
+            Some(Ok(wage)) if wage >= 0.0 => Some(wage),
+            Some(_) => {
+                println!("Error: --avg-wage takes a yearly wage, like 80000");
+                std::process::exit(1);
+            }
+            None => Some(cocomo::DEFAULT_WAGE),
+        }
+    } else {
+        None
+    };
+    let totals_only = if matches.is_present("code-only") {
+        Some(TotalsOnly::Code)
+    } else if matches.is_present("totals-only") {
+        Some(TotalsOnly::Row)
+    } else {
+        None
+    };
+
+    // Read up front, so a bad path doesn't waste a whole count
+    let baseline = matches.value_of("baseline").map(|path| match notify::load_baseline(path) {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            println!("Error: --baseline {}: {}", path, e);
+            std::process::exit(1);
+        }
+    });
+
+    Options {
+        #[cfg(feature = "image")]
+        _image: image,
+        rootfs,
+        targets,
+        reloaded,
+        sort,
+        others,
+        by_file,
+        template,
+        outputs,
+        table_only,
+        colors,
+        cocomo,
+        totals_only,
+        baseline,
+        show_sensitive: matches.is_present("show-sensitive"),
+        percent: matches.is_present("percent"),
+    }
+}
+
+// What to walk, and how to count what's found
+pub struct Scan<'a> {
+    pub walk_opts: WalkOptions,
+    pub throttle: Arc<Throttle>,
+    pub cache: Arc<Cache>,
+    // --only: just these get walked and counted, and the cache stands in for the rest
+    pub only: Vec<&'a str>,
+    // How many files the walk gives up after, 0 for no limit
+    pub max_files: usize,
+    pub sample: Option<Sample>,
+}
+
+pub fn scan<'a>(matches: &'a ArgMatches, opts: &Options) -> Scan<'a> {
+    if matches.is_present("nice") {
+        throttle::be_nice();
+    }
+    let throttle = Arc::new(match matches.value_of("throttle") {
+        Some(rate) => match throttle::parse_rate(rate) {
+            Some(rate) => Throttle::new(rate),
+            None => {
+                println!("Error: --throttle takes a rate like 50MB/s, got '{}'", rate);
+                std::process::exit(1);
+            }
+        },
+        None => Throttle::unlimited(),
+    });
+
+    let (use_ignore, ignore_hidden) = match matches.occurrences_of("unrestricted") {
+        0 => (true,  true),
+        1 => (false, true),
+        2 => (false, false),
+        _ => (false, false),
+    };
+    let owner = match matches.value_of("owned-by") {
+        Some(user) => match walk::lookup_uid(user) {
+            Some(uid) => Some(uid),
+            None if cfg!(unix) => {
+                println!("Error: no such user for --owned-by: '{}'", user);
+                std::process::exit(1);
+            }
+            None => {
+                println!("Error: --owned-by is only supported on unix");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let path_filter = Arc::new(PathFilter::from_matches(matches));
+    let mut configs = Configs::new();
+    for force in matches.values_of("force-ext").into_iter().flatten() {
+        let (ext, lang) = match force.find('=') {
+            Some(i) => (&force[..i], &force[i + 1..]),
+            None => {
+                println!("Error: --force-ext takes EXT=LANG, got '{}'", force);
+                std::process::exit(1);
+            }
+        };
+        match Lang::from_name(lang) {
+            Some(lang) => configs.force_ext(ext, lang),
+            None => {
+                println!("Error: unknown language for --force-ext: '{}'", lang);
+                std::process::exit(1);
+            }
+        }
+    }
+    for force in matches.values_of("force-lang").into_iter().flatten() {
+        let (lang, pattern) = match force.find(':') {
+            Some(i) => (&force[..i], &force[i + 1..]),
+            None => {
+                println!("Error: --force-lang takes LANG:PATH, got '{}'", force);
+                std::process::exit(1);
+            }
+        };
+        let lang = match Lang::from_name(lang) {
+            Some(lang) => lang,
+            None => {
+                println!("Error: unknown language for --force-lang: '{}'", lang);
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = configs.force_lang(pattern, lang) {
+            println!("Error: bad --force-lang pattern '{}': {}", pattern, e);
+            std::process::exit(1);
+        }
+    }
+    let configs = Arc::new(configs);
+    // Whatever this run writes, so it isn't counted when it's inside a target
+    let mut own_files: Vec<PathBuf> = opts.outputs.iter().filter_map(|o| o.1)
+        .chain(["append-history", "sqlite", "badge"].iter().filter_map(|&arg| matches.value_of(arg)))
+        .filter_map(|path| walk::own_file(Path::new(path)))
+        .collect();
+    if matches.is_present("cache") {
+        own_files.extend(cache::cache_dir().and_then(|dir| walk::own_file(&dir)));
+    }
+    let walk_opts = WalkOptions {
+        use_ignore,
+        ignore_hidden,
+        git_global: !matches.is_present("no-ignore-global"),
+        git_exclude: !matches.is_present("no-ignore-exclude"),
+        follow: matches.is_present("follow"),
+        owner,
+        path_filter,
+        configs,
+        own_files,
+    };
+    let max_files = match matches.value_of("max-files").map(str::parse::<usize>) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            println!("Error: --max-files takes a number of files");
+            std::process::exit(1);
+        }
+        None => 0,
+    };
+    // --on-limit sample: past --max-files, estimate from a random that many instead of giving up.
+    // The walk goes on to the end, for how many there are to scale up to, but holds no more
+    // paths than that.
+    let sample_over = matches.value_of("on-limit") == Some("sample") && max_files > 0;
+    if sample_over {
+        if let Some(arg) = ["files", "by-dir", "min-comment-ratio"].iter().find(|arg| matches.is_present(arg)) {
+            println!("Error: --on-limit sample doesn't go with --{}, which needs every file counted", arg);
+            std::process::exit(1);
+        }
+    }
+    let cache = Arc::new(if matches.is_present("cache") { Cache::open() } else { Cache::disabled() });
+    // --only: just these get walked and counted, and the cache stands in for the rest
+    let only: Vec<&str> = matches.values_of("only").map(|v| v.collect()).unwrap_or_default();
+    for &path in &only {
+        if !opts.roots().iter().any(|&root| cache.is_under(path, root)) {
+            println!("Error: --only {}: not under any of the targets", path);
+            std::process::exit(1);
+        }
+    }
+    let sample: Option<Sample> = match matches.value_of("sample").map(|s| (s, s.parse())) {
+        Some((_, Ok(sample))) => Some(sample),
+        Some((s, Err(_))) => {
+            println!("Error: --sample takes a percentage (e.g. 10%) or a number of files, got '{}'", s);
+            std::process::exit(1);
+        }
+        None if sample_over => Some(Sample::Over(max_files)),
+        None => None,
+    };
+
+    Scan {
+        walk_opts,
+        throttle,
+        cache,
+        only,
+        max_files: if sample_over { 0 } else { max_files },
+        sample,
+    }
+}
+
+// Every flag loc takes.  A function so --capabilities can describe the same ones.
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("loc")
+        .global_settings(&[AppSettings::ColoredHelp])
+        .version(crate_version!())
+        .author("Curtis Gagliardi <curtis@curtis.io>")
+        .about("counts things quickly hopefully")
+        .args(&filter::args())
+        .args(&git_args())
+        .args(&image_args())
+        .args(&archive_args())
+        .args(&sqlite_args())
+        .arg(Arg::with_name("files")
+             .required(false)
+             .long("files")
+             .takes_value(false)
+             .help("Show stats for individual files"))
+        .arg(Arg::with_name("format")
+             .required(false)
+             .long("format")
+             .value_name("FORMAT")
+             .takes_value(true)
+             .possible_values(&["table", "json", "yaml", "xml", "csv", "md", "plain72", "prometheus", "html", "dot", "treemap-json", "ndjson"])
+             .conflicts_with_all(&["by-dir", "comment-lang", "ambiguous", "skipped"])
+             .help("How to print the counts: table (the default), json, yaml, xml (like cloc's), csv, \
+                    md (a Markdown table), plain72 (72 columns of ASCII, for email), prometheus \
+                    (gauges per language for node_exporter's textfile collector), html (a page \
+                    with every file, grouped by language), dot (a Graphviz map of the directories), \
+                    treemap-json (the directory tree in d3's flare format) or ndjson (a json object \
+                    per file, printed as soon as it's counted)"))
+        .arg(Arg::with_name("output")
+             .required(false)
+             .long("output")
+             .value_name("[FORMAT:]FILE")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .conflicts_with("by-dir")
+             .help("Write the report to FILE (- for stdout) instead of stdout, in FORMAT, or else \
+                    --format's, or else the one FILE's extension says (.json, .csv, .html, .md...). \
+                    Repeat it to get several reports from the one count, like --output table:- \
+                    --output json:report.json"))
+        .arg(Arg::with_name("template")
+             .required(false)
+             .long("template")
+             .value_name("TEMPLATE")
+             .takes_value(true)
+             .conflicts_with_all(&["format", "by-dir"])
+             .help("Print a line per language (per file with --files) filled in from TEMPLATE, \
+                    like '{language}: {code}'. It takes {language}, {path}, {files}, {lines}, \
+                    {blank}, {comment} and {code}. Also for --output template:FILE"))
+        .arg(Arg::with_name("lang-ui")
+             .required(false)
+             .long("lang-ui")
+             .value_name("LANG")
+             .takes_value(true)
+             .possible_values(&["en", "de", "fr", "ja"])
+             .help("Label the tables and the md and html reports in this language"))
+        .arg(Arg::with_name("append-history")
+             .required(false)
+             .long("append-history")
+             .value_name("CSV")
+             .takes_value(true)
+             .conflicts_with("sample")
+             .help("Also append a timestamped row of this run's totals to CSV, e.g. from a nightly \
+                    job to chart growth over time"))
+        .arg(Arg::with_name("badge")
+             .required(false)
+             .long("badge")
+             .value_name("SVG")
+             .takes_value(true)
+             .conflicts_with("sample")
+             .help("Also write a shields.io-style badge with the lines of code to SVG, for the README"))
+        .arg(Arg::with_name("badge-lang")
+             .required(false)
+             .long("badge-lang")
+             .value_name("LANG")
+             .takes_value(true)
+             .requires("badge")
+             .help("Put just LANG's lines of code on the badge"))
+        .arg(Arg::with_name("notify-webhook")
+             .required(false)
+             .long("notify-webhook")
+             .value_name("URL")
+             .takes_value(true)
+             .conflicts_with("sample")
+             .help("After counting, post a short summary to a Slack or Teams incoming webhook (uses curl)"))
+        .arg(Arg::with_name("baseline")
+             .required(false)
+             .long("baseline")
+             .value_name("JSON")
+             .takes_value(true)
+             .requires("notify-webhook")
+             .help("A --format json report from an earlier run, so --notify-webhook can say what changed"))
+        .arg(Arg::with_name("count-files-only")
+             .required(false)
+             .long("count-files-only")
+             .takes_value(false)
+             .conflicts_with("estimate")
+             .help("Just count files per language and extension, without opening any of them"))
+        .arg(Arg::with_name("max-files")
+             .required(false)
+             .long("max-files")
+             .value_name("N")
+             .takes_value(true)
+             .help("Give up if there are more than N files to count"))
+        .arg(Arg::with_name("on-limit")
+             .required(false)
+             .long("on-limit")
+             .value_name("WHAT")
+             .takes_value(true)
+             .possible_values(&["fail", "sample"])
+             .requires("max-files")
+             .conflicts_with("sample")
+             .help("What to do with more than --max-files files: fail (the default), or count a random \
+                    sample of that many and estimate the totals from it"))
+        .arg(Arg::with_name("sample")
+             .required(false)
+             .long("sample")
+             .value_name("AMOUNT")
+             .takes_value(true)
+             .conflicts_with_all(&["files", "by-dir", "min-comment-ratio"])
+             .help("Only count a random sample of the files, either a percentage (10%) or a number \
+                    of files (5000), and estimate the totals from it"))
+        .arg(Arg::with_name("estimate")
+             .required(false)
+             .long("estimate")
+             .takes_value(false)
+             .conflicts_with_all(&["files", "by-dir", "min-comment-ratio", "sample"])
+             .help("Don't read any files, just estimate line counts from file sizes. Fast, but rough"))
+        .arg(Arg::with_name("file-meta")
+             .required(false)
+             .long("file-meta")
+             .takes_value(false)
+             .requires("files")
+             .help("With --files, also show each file's encoding, whether it has a BOM, and its line endings"))
+        .arg(Arg::with_name("ordered")
+             .required(false)
+             .long("ordered")
+             .takes_value(false)
+             .requires("files")
+             .conflicts_with("sort")
+             .help("With --files, list files in the order they were found instead of sorting them, \
+                    so runs over the same tree can be diffed"))
+        .arg(Arg::with_name("show-sensitive")
+             .required(false)
+             .long("show-sensitive")
+             .takes_value(false)
+             .help("List private keys, certificates and .env files in per-file output, which are hidden by default"))
+        .arg(Arg::with_name("by-dir")
+             .required(false)
+             .long("by-dir")
+             .takes_value(false)
+             .conflicts_with("files")
+             .help("Show stats for each top-level directory, and the language most of it is written in"))
+        .arg(Arg::with_name("dir-depth")
+             .required(false)
+             .long("dir-depth")
+             .value_name("N")
+             .takes_value(true)
+             .requires("by-dir")
+             .help("With --by-dir, go N directories down instead of just the top-level ones"))
+        .arg(Arg::with_name("family")
+             .required(false)
+             .long("family")
+             .takes_value(false)
+             .conflicts_with_all(&["files", "by-dir", "group-by-path", "group-by", "format", "output", "template",
+                                   "percent", "totals-only"])
+             .help("Roll related languages up into families (C/C++/Obj-C, JS/TS, JVM), each with its languages underneath"))
+        .arg(Arg::with_name("percent")
+             .required(false)
+             .long("percent")
+             .takes_value(false)
+             .conflicts_with("files")
+             .help("Add each language's share of all the lines and all the code to the summary"))
+        .arg(Arg::with_name("cocomo")
+             .required(false)
+             .long("cocomo")
+             .takes_value(false)
+             .help("Add a basic COCOMO estimate of what writing the code would cost, in time and money"))
+        .arg(Arg::with_name("avg-wage")
+             .required(false)
+             .long("avg-wage")
+             .value_name("WAGE")
+             .takes_value(true)
+             .requires("cocomo")
+             .help("The yearly wage --cocomo costs the effort at [default: 56286]"))
+        .arg(Arg::with_name("totals-only")
+             .required(false)
+             .long("totals-only")
+             .takes_value(false)
+             .conflicts_with_all(&["files", "by-dir", "group-by-path", "group-by", "format", "output", "template", "percent", "sample"])
+             .help("Print just the Total row, for scripts"))
+        .arg(Arg::with_name("code-only")
+             .required(false)
+             .long("code-only")
+             .takes_value(false)
+             .requires("totals-only")
+             .help("With --totals-only, print just the total lines of code, as a bare number"))
+        .arg(Arg::with_name("columns")
+             .required(false)
+             .long("columns")
+             .value_name("COLUMNS")
+             .takes_value(true)
+             .help("Which count columns the table shows, in order, e.g. lines,code,comment [files, lines, blank, comment, code, bytes, avg-line, max-line]"))
+        .arg(Arg::with_name("group-by-path")
+             .required(false)
+             .long("group-by-path")
+             .value_name("PATTERN")
+             .takes_value(true)
+             .multiple(true)
+             .conflicts_with_all(&["files", "by-dir", "format", "output", "template"])
+             .help("Show stats for the files under each PATTERN (.gitignore syntax, like 'src/*' \
+                    'tests/*' 'docs/*'), and the language most of them are written in. A file goes \
+                    under the first PATTERN it matches"))
+        .arg(Arg::with_name("min-comment-ratio")
+             .required(false)
+             .long("min-comment-ratio")
+             .value_name("RATIO")
+             .takes_value(true)
+             .conflicts_with_all(&["files", "by-dir"])
+             .help("List languages and files where comments make up less than RATIO (e.g. 0.05) of \
+                    comments + code, and exit with status 1 if there are any"))
+        .arg(Arg::with_name("comment-ratio-path")
+             .required(false)
+             .multiple(true)
+             .number_of_values(1)
+             .long("comment-ratio-path")
+             .value_name("PATH")
+             .takes_value(true)
+             .requires("min-comment-ratio")
+             .help("Only hold files under PATH to --min-comment-ratio (multiple accepted)"))
+        .arg(Arg::with_name("sort")
+            .required(false)
+            .long("sort")
+            .value_name("COLUMN")
+            .takes_value(true)
+            .help("Column to sort by, or several separated by commas for ties, each with an \
+                   optional :asc or :desc, e.g. language:asc,code:desc"))
+        .arg(Arg::with_name("unrestricted")
+             .required(false)
+             .multiple(true)
+             .long("unrestricted")
+             .short("u")
+             .takes_value(false)
+             .help("A single -u won't respect .gitignore (etc.) files. Two -u flags will additionally count hidden files and directories."))
+        .arg(Arg::with_name("no-ignore-global")
+             .required(false)
+             .long("no-ignore-global")
+             .takes_value(false)
+             .help("Don't respect your global gitignore (git's core.excludesFile)"))
+        .arg(Arg::with_name("no-ignore-exclude")
+             .required(false)
+             .long("no-ignore-exclude")
+             .takes_value(false)
+             .help("Don't respect the repository's .git/info/exclude"))
+        .arg(Arg::with_name("explain-ignores")
+             .required(false)
+             .long("explain-ignores")
+             .takes_value(false)
+             .help("Print which ignore files apply to each target and exit"))
+        .arg(Arg::with_name("explain")
+             .required(false)
+             .long("explain")
+             .value_name("FILE")
+             .takes_value(true)
+             .help("Print what language FILE is counted as and what each of its lines counts as, \
+                    and exit"))
+        .arg(Arg::with_name("follow")
+             .required(false)
+             .long("follow")
+             .short("L")
+             .takes_value(false)
+             .help("Follow symlinks. On Windows this also follows directory junctions and reads cloud placeholder (e.g. OneDrive) files"))
+        .arg(Arg::with_name("owned-by")
+             .required(false)
+             .long("owned-by")
+             .value_name("USER")
+             .takes_value(true)
+             .help("Only count files owned by USER (a user name or uid)"))
+        .arg(Arg::with_name("strict-detection")
+             .required(false)
+             .long("strict-detection")
+             .takes_value(false)
+             .conflicts_with_all(&["sample", "estimate", "count-files-only"])
+             .help("Fail, listing them, if any files we can't tell the language of look like source code"))
+        .arg(Arg::with_name("skipped")
+             .required(false)
+             .long("skipped")
+             .takes_value(false)
+             .help("Also show how many files weren't counted and why (ignored, hidden, filtered out, ...)"))
+        .arg(Arg::with_name("min-lines")
+             .required(false)
+             .long("min-lines")
+             .value_name("N")
+             .takes_value(true)
+             .help("Fold languages with fewer than N lines into an \"Other\" row in the summary table"))
+        .arg(Arg::with_name("others-threshold")
+             .required(false)
+             .long("others-threshold")
+             .value_name("PERCENT")
+             .takes_value(true)
+             .help("Fold languages with less than PERCENT (e.g. 1%) of all lines into an \"Other\" row in the summary table"))
+        .arg(Arg::with_name("color")
+             .required(false)
+             .long("color")
+             .value_name("WHEN")
+             .takes_value(true)
+             .possible_values(&["auto", "always", "never"])
+             .help("Color the summary table: auto (the default) does when printing to a terminal and NO_COLOR isn't set"))
+        .arg(Arg::with_name("heat")
+             .required(false)
+             .long("heat")
+             .takes_value(false)
+             .help("When coloring, also color each language's code by its share of all the code"))
+        .arg(Arg::with_name("top")
+             .required(false)
+             .long("top")
+             .value_name("N")
+             .takes_value(true)
+             .help("Show only the N languages with the most lines, and fold the rest into an \"Other\" row in the summary table"))
+        .arg(Arg::with_name("rename-lang")
+             .required(false)
+             .multiple(true)
+             .long("rename-lang")
+             .value_name("LANG=NAME")
+             .takes_value(true)
+             .help("Call a language something else in the output, e.g. --rename-lang \"Cpp=C++\" \"CSharp=C#\""))
+        .arg(Arg::with_name("force-ext")
+             .required(false)
+             .multiple(true)
+             .number_of_values(1)
+             .long("force-ext")
+             .value_name("EXT=LANG")
+             .takes_value(true)
+             .help("Count files with this extension as LANG, e.g. --force-ext h=cpp"))
+        .arg(Arg::with_name("force-lang")
+             .required(false)
+             .multiple(true)
+             .number_of_values(1)
+             .long("force-lang")
+             .value_name("LANG:PATH")
+             .takes_value(true)
+             .help("Count files under a path prefix or matching a glob as LANG, e.g. --force-lang rust:vendor/patched/"))
+        .arg(Arg::with_name("comment-lang")
+             .required(false)
+             .long("comment-lang")
+             .takes_value(false)
+             .help("Work out what (human) language each file's comments are written in"))
+        .arg(Arg::with_name("dead-code")
+             .required(false)
+             .long("dead-code")
+             .takes_value(false)
+             .help("Show how many comment lines in each language look like commented-out code"))
+        .arg(Arg::with_name("ambiguous")
+             .required(false)
+             .long("ambiguous")
+             .takes_value(false)
+             .help("List files whose language was a guess, from a shared extension or a shebang"))
+        .arg(Arg::with_name("profile")
+             .required(false)
+             .long("profile")
+             .value_name("NAME")
+             .takes_value(true)
+             .help("Use the flags from [profiles.NAME] in .loc.toml. Flags given on the command line win"))
+        .arg(Arg::with_name("cargo-deps")
+             .required(false)
+             .long("cargo-deps")
+             .value_name("N")
+             .takes_value(true)
+             .min_values(0)
+             .require_equals(true)
+             .help("Count the source of every crate the project depends on (from cargo metadata), \
+                    biggest first.  --cargo-deps=N only lists the N biggest"))
+        .arg(Arg::with_name("doc-coverage")
+             .required(false)
+             .long("doc-coverage")
+             .takes_value(false)
+             .help("Instead of counting lines, count the pub items in each Rust crate and module \
+                    and how many have doc comments"))
+        .arg(Arg::with_name("npm-deps")
+             .required(false)
+             .long("npm-deps")
+             .value_name("N")
+             .takes_value(true)
+             .min_values(0)
+             .require_equals(true)
+             .conflicts_with("cargo-deps")
+             .help("Count what's in node_modules per package and version, and list the 20 biggest \
+                    (--npm-deps=N for N, 0 for all)"))
+        .arg(Arg::with_name("throttle")
+             .required(false)
+             .long("throttle")
+             .value_name("RATE")
+             .takes_value(true)
+             .help("Read files no faster than RATE, e.g. 50MB/s, to go easy on a busy disk"))
+        .arg(Arg::with_name("max-memory")
+             .required(false)
+             .long("max-memory")
+             .value_name("SIZE")
+             .takes_value(true)
+             .conflicts_with("file-meta")
+             .help("Keep memory use to roughly SIZE, e.g. 2G, by adding files up as they're counted, or setting \
+                    them aside on disk when they'll be listed, once it gets close"))
+        .arg(Arg::with_name("nice")
+             .required(false)
+             .long("nice")
+             .takes_value(false)
+             .help("Run at the lowest cpu and (on Linux) io priority, so anything else on the \
+                    machine comes first"))
+        .arg(Arg::with_name("cache")
+             .required(false)
+             .long("cache")
+             .takes_value(false)
+             .help("Remember counts between runs, so only files that changed are read again"))
+        .arg(Arg::with_name("stdin-lang")
+             .required(false)
+             .long("stdin-lang")
+             .value_name("LANG")
+             .takes_value(true)
+             .help("The language of what's piped in to count with - as the target, by name or extension, \
+                    e.g. rust or rs"))
+        .arg(Arg::with_name("files-from")
+             .required(false)
+             .long("files-from")
+             .value_name("FILE")
+             .takes_value(true)
+             .conflicts_with_all(&["target", "only"])
+             .help("Count the files listed in FILE (- for stdin), one per line, instead of walking any \
+                    targets, e.g. from git ls-files"))
+        .arg(Arg::with_name("null")
+             .short("0")
+             .long("null")
+             .takes_value(false)
+             .requires("files-from")
+             .help("With --files-from, paths are separated by nul bytes instead of newlines, as from \
+                    find -print0 or git ls-files -z"))
+        .arg(Arg::with_name("only")
+             .required(false)
+             .long("only")
+             .value_name("PATH")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .requires("cache")
+             .help("With --cache, count just what's under PATH again and take everything else in the \
+                    targets from the cache, without looking at it"))
+        .arg(Arg::with_name("verbose")
+             .required(false)
+             .long("verbose")
+             .takes_value(false)
+             .help("Say more about what's going on (e.g. the cache) on stderr"))
+        .arg(Arg::with_name("timings")
+             .required(false)
+             .long("timings")
+             .takes_value(false)
+             .help("Print how long each phase (walking, counting, ...) took to stderr"))
+        .arg(Arg::with_name("capabilities")
+             .required(false)
+             .long("capabilities")
+             .takes_value(false)
+             .help("Print what this build supports (languages, formats and flags) as json, for tools that wrap loc"))
+        .arg(Arg::with_name("conformance")
+             .required(false)
+             .long("conformance")
+             .takes_value(false)
+             .conflicts_with("gen-corpus")
+             .help("Check loc's counts against snippets with known counts: the ones built in, or a directory \
+                    of them per language given as the target"))
+        .arg(Arg::with_name("gen-corpus")
+             .required(false)
+             .hidden(true)
+             .long("gen-corpus")
+             .value_name("DIR")
+             .takes_value(true)
+             .conflicts_with("target")
+             .help("Write a synthetic source tree with known line counts to DIR, for benchmarking"))
+        .arg(Arg::with_name("corpus-files")
+             .required(false)
+             .hidden(true)
+             .long("corpus-files")
+             .value_name("N")
+             .takes_value(true)
+             .requires("gen-corpus")
+             .help("How many files --gen-corpus writes (default 1000)"))
+        .arg(Arg::with_name("corpus-lang")
+             .required(false)
+             .hidden(true)
+             .long("corpus-lang")
+             .value_name("LANG")
+             .takes_value(true)
+             .requires("gen-corpus")
+             .help("What language --gen-corpus writes (default rust)"))
+        .arg(Arg::with_name("corpus-seed")
+             .required(false)
+             .hidden(true)
+             .long("corpus-seed")
+             .value_name("SEED")
+             .takes_value(true)
+             .requires("gen-corpus")
+             .help("Writes a different, but just as reproducible, --gen-corpus tree (default 0)"))
+        .arg(Arg::with_name("repo-root")
+             .required(false)
+             .long("repo-root")
+             .takes_value(false)
+             .conflicts_with("target")
+             .help("Count the whole git repository you're in, wherever in it you are"))
+        .arg(Arg::with_name("target")
+            .multiple(true)
+            .help("File or directory to count (multiple arguments accepted), or @NAME for a set of \
+                   them from .loc.toml's [targets]"))
+}
+
+#[cfg(feature = "git")]
+fn git_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    git::args()
+}
+
+#[cfg(not(feature = "git"))]
+fn git_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![]
+}
+
+#[cfg(feature = "image")]
+fn image_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("image")
+            .required(false)
+            .long("image")
+            .value_name("IMAGE")
+            .takes_value(true)
+            .conflicts_with_all(&["target", "repo-root"])
+            .help("Count the source files inside a container image: a docker save or OCI tarball, \
+                   or an image name like ubuntu:22.04 (fetched with docker or podman)"),
+    ]
+}
+
+#[cfg(not(feature = "image"))]
+fn image_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![]
+}
+
+#[cfg(feature = "archives")]
+fn archive_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("archives")
+            .required(false)
+            .long("archives")
+            .takes_value(false)
+            .help("Count the files inside .zip, .tar and .tar.gz archives, as if each archive were a \
+                   directory of them"),
+    ]
+}
+
+#[cfg(not(feature = "archives"))]
+fn archive_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![]
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("sqlite")
+            .required(false)
+            .long("sqlite")
+            .value_name("DB")
+            .takes_value(true)
+            .conflicts_with("sample")
+            .help("Also add this run, with every file's counts, to the SQLite database DB (created if \
+                   need be), to query with SQL and compare runs over time"),
+    ]
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![]
+}
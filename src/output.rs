@@ -0,0 +1,2132 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use serde::{Serialize, Serializer};
+use serde_json;
+use serde_yaml;
+
+use loc::*;
+use loc::doc_coverage::{CrateDocs, DocCoverage};
+
+use aggregate::ByLang;
+use cocomo::{self, Estimate};
+#[cfg(feature = "git")]
+use git::{AgeBuckets, Authorship, Churn, FileChurn, LangDelta, RevCount};
+use conformance::Case;
+use config;
+use deps::Dep;
+use estimate::SizeEstimate;
+use filter::is_sensitive;
+use i18n::{pad, t, ui, Text};
+use sample::LangEstimate;
+use sort::Sort;
+use template::{Row, Template};
+use tree::{self, Dir};
+use walk::Skipped;
+use worker::{Conflicted, DeadCode, FileCount, LongLines};
+
+// Display names from --rename-lang, set once before any output.
+static LANG_NAMES: OnceLock<HashMap<Lang, String>> = OnceLock::new();
+
+pub fn set_lang_names(names: HashMap<Lang, String>) {
+    LANG_NAMES.set(names).expect("language names already set");
+}
+
+pub fn lang_name(lang: &Lang) -> &str {
+    match LANG_NAMES.get().and_then(|names| names.get(lang)) {
+        Some(name) => name,
+        None => lang.to_s(),
+    }
+}
+
+// A count column of the summary and --files tables, for --columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Files,
+    Lines,
+    Blank,
+    Comment,
+    Code,
+    // Size on disk, which says more than lines for minified and data-heavy languages
+    Bytes,
+    // Bytes per line, and the longest line: generated and minified code stand out
+    AvgLine,
+    MaxLine,
+}
+
+const DEFAULT_COLUMNS: &[Column] = &[Column::Files, Column::Lines, Column::Blank, Column::Comment, Column::Code];
+
+impl Column {
+    pub fn from_name(name: &str) -> Option<Column> {
+        match name {
+            "files"    => Some(Column::Files),
+            "lines"    => Some(Column::Lines),
+            "blank"    => Some(Column::Blank),
+            "comment"  => Some(Column::Comment),
+            "code"     => Some(Column::Code),
+            "bytes"    => Some(Column::Bytes),
+            "avg-line" => Some(Column::AvgLine),
+            "max-line" => Some(Column::MaxLine),
+            _          => None,
+        }
+    }
+
+    fn heading(self) -> Text {
+        match self {
+            Column::Files   => Text::Files,
+            Column::Lines   => Text::Lines,
+            Column::Blank   => Text::Blank,
+            Column::Comment => Text::Comment,
+            Column::Code    => Text::Code,
+            Column::Bytes   => Text::Bytes,
+            Column::AvgLine => Text::AvgLine,
+            Column::MaxLine => Text::MaxLine,
+        }
+    }
+
+    fn width(self) -> usize {
+        if self == Column::Files { 8 } else { 12 }
+    }
+
+    fn value(self, total: &LangTotal) -> String {
+        match self {
+            Column::Files   => total.files.to_string(),
+            Column::Lines   => total.count.lines.to_string(),
+            Column::Blank   => total.count.blank.to_string(),
+            Column::Comment => total.count.comment.to_string(),
+            Column::Code    => total.count.code.to_string(),
+            Column::Bytes   => human_size(total.bytes as usize),
+            // Each line's newline taken back off
+            Column::AvgLine => {
+                let lines = u64::from(total.count.lines);
+                (total.bytes.saturating_sub(lines) / lines.max(1)).to_string()
+            }
+            Column::MaxLine => total.max_line.to_string(),
+        }
+    }
+}
+
+// Columns from --columns, set once before any output.  All but bytes and line lengths otherwise.
+static COLUMNS: OnceLock<Vec<Column>> = OnceLock::new();
+
+pub fn set_columns(columns: Vec<Column>) {
+    COLUMNS.set(columns).expect("columns already set");
+}
+
+fn columns() -> &'static [Column] {
+    COLUMNS.get().map(Vec::as_slice).unwrap_or(DEFAULT_COLUMNS)
+}
+
+// The table width that fits the name column and the chosen count columns
+fn table_width(extra: usize) -> usize {
+    19 + columns().iter().map(|c| c.width() + 1).sum::<usize>() + extra
+}
+
+fn write_cells(out: &mut dyn Write, total: &LangTotal) -> io::Result<()> {
+    write_colored_cells(out, total, None)
+}
+
+// With the code column in code_color, when there is one
+fn write_colored_cells(out: &mut dyn Write, total: &LangTotal, code_color: Option<&str>) -> io::Result<()> {
+    for &col in columns() {
+        match code_color {
+            // Back to the default color only, so a bold row stays bold
+            Some(color) if col == Column::Code => write!(out, " {}{:>w$}\x1b[39m", color, total.count.code, w = col.width())?,
+            _ => write!(out, " {:>w$}", col.value(total), w = col.width())?,
+        }
+    }
+    Ok(())
+}
+
+// With no sort, languages and files are printed in the order given.
+pub fn print_by_file(out: &mut dyn Write, by_lang: &ByLang, show_sensitive: bool) -> io::Result<()> {
+    let linesep = str_repeat("-", table_width(0));
+    // Files don't have a file count, so a leading files column goes to the path instead,
+    // and one anywhere else is left blank.
+    let (path_width, file_columns) = match columns().split_first() {
+        Some((&Column::Files, rest)) => (25, rest),
+        _                            => (16, columns()),
+    };
+
+    // print breakdown for each individual file
+    writeln!(out, "{}", linesep)?;
+    print_count_header(out, false)?;
+    writeln!(out, "{}", linesep)?;
+
+    let mut hidden = 0;
+    for (lang, total, filecounts) in by_lang.iter() {
+        writeln!(out, "{}", linesep)?;
+        write!(out, " {0: <17}", lang_name(&lang))?;
+        write_cells(out, total)?;
+        writeln!(out)?;
+
+        writeln!(out, "{}", linesep)?;
+        for fc in filecounts {
+            if !show_sensitive && is_sensitive(&fc.path) {
+                hidden += 1;
+                continue;
+            }
+            write!(out, "|{0: <1$}", last_n_chars(&fc.path, path_width), path_width)?;
+            let file = LangTotal { files: 0, count: fc.count.clone(), bytes: fc.bytes, max_line: fc.max_line };
+            for &col in file_columns {
+                match col {
+                    Column::Files => write!(out, " {:>8}", "")?,
+                    col           => write!(out, " {:>12}", col.value(&file))?,
+                }
+            }
+            match fc.meta {
+                Some(meta) => writeln!(out, "  {0: <8} {1: <3} {2}",
+                                            meta.encoding.to_s(),
+                                            if meta.bom { "BOM" } else { "" },
+                                            meta.line_endings.to_s())?,
+                None => writeln!(out)?,
+            }
+        }
+    }
+    write_hidden_note(out, hidden)
+}
+
+// Files where the line count is meaningless, so nobody goes looking for why their
+// JavaScript is only 12 lines.
+pub fn print_long_lines(files: &[LongLines], show_sensitive: bool) {
+    let mut files: Vec<&LongLines> = files.iter().collect();
+    let before = files.len();
+    files.retain(|f| show_sensitive || !is_sensitive(&f.path));
+    let hidden = before - files.len();
+    if files.is_empty() {
+        print_hidden_note(hidden);
+        return;
+    }
+    files.sort_by_key(|f| (Reverse(f.size), f.path.clone()));
+
+    let linesep = str_repeat("-", 80);
+    println!();
+    println!(" These files are mostly very long lines (minified or generated?), so their line");
+    println!(" counts don't say much:");
+    println!("{}", linesep);
+    println!(" {0: <47} {1: >10} {2: >8} {3: >10}",
+             "File",
+             "Size",
+             "Lines",
+             "Avg line");
+    println!("{}", linesep);
+    for f in files {
+        println!(" {0: <47} {1: >10} {2: >8} {3: >10}",
+                 last_n_chars(&f.path, 47),
+                 human_size(f.size),
+                 f.lines,
+                 human_size(f.size / f.lines.max(1) as usize));
+    }
+    println!("{}", linesep);
+    print_hidden_note(hidden);
+}
+
+// --cocomo, after the table
+pub fn print_cocomo(estimate: &Estimate) {
+    let linesep = str_repeat("-", 80);
+    println!();
+    println!(" What basic COCOMO (organic) reckons this code would take to write from scratch:");
+    println!("{}", linesep);
+    println!(" {0: <40} {1: >38}", "Estimated cost to develop", format!("${}", thousands(estimate.cost.round() as u64)));
+    println!(" {0: <40} {1: >38}", "Estimated schedule", format!("{:.2} months", estimate.schedule_months));
+    println!(" {0: <40} {1: >38}", "Estimated effort", format!("{:.2} person-months", estimate.effort_months));
+    println!(" {0: <40} {1: >38}", "Estimated people required", format!("{:.2}", estimate.people));
+    println!("{}", linesep);
+}
+
+// 1234567 -> 1,234,567
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn print_conflicted(files: &[Conflicted], show_sensitive: bool) {
+    let mut files: Vec<&Conflicted> = files.iter().collect();
+    let before = files.len();
+    files.retain(|f| show_sensitive || !is_sensitive(&f.path));
+    let hidden = before - files.len();
+    if files.is_empty() {
+        print_hidden_note(hidden);
+        return;
+    }
+    files.sort_by_key(|f| (Reverse(f.conflicts), f.path.clone()));
+
+    let linesep = str_repeat("-", 80);
+    println!();
+    println!(" These files still have merge conflict markers in them, and both sides of each");
+    println!(" conflict were counted:");
+    println!("{}", linesep);
+    println!(" {0: <66} {1: >12}", "File", "Conflicts");
+    println!("{}", linesep);
+    for f in files {
+        println!(" {0: <66} {1: >12}", last_n_chars(&f.path, 66), f.conflicts);
+    }
+    println!("{}", linesep);
+    print_hidden_note(hidden);
+}
+
+// How many files have comments in each language, then every file that isn't in the most
+// common one, since those are the ones an audit is after.
+// --dead-code, by language, the most dead code first
+pub fn print_dead_code(dead_code: &HashMap<Lang, DeadCode>) {
+    let mut by_lang: Vec<(&str, &DeadCode)> = dead_code.iter().map(|(lang, d)| (lang_name(lang), d)).collect();
+    by_lang.sort_by_key(|&(lang, d)| (Reverse(d.dead), lang));
+    let mut total = DeadCode::default();
+
+    let linesep = str_repeat("-", 80);
+    println!();
+    println!(" Comment lines that look like commented-out code (a guess from how they read):");
+    println!("{}", linesep);
+    println!(" {0: <41} {1: >12} {2: >12} {3: >10}", "Language", "Comment", "Dead code", "Share");
+    println!("{}", linesep);
+    for (lang, d) in by_lang {
+        println!(" {0: <41} {1: >12} {2: >12} {3: >9.1}%", lang, d.comment, d.dead, percent(d.dead, d.comment));
+        total.comment += d.comment;
+        total.dead += d.dead;
+    }
+    println!("{}", linesep);
+    println!(" {0: <41} {1: >12} {2: >12} {3: >9.1}%", "Total", total.comment, total.dead, percent(total.dead, total.comment));
+    println!("{}", linesep);
+}
+
+fn percent(n: u32, of: u32) -> f64 {
+    if of == 0 { 0.0 } else { 100.0 * f64::from(n) / f64::from(of) }
+}
+
+pub fn print_comment_langs(mut files: Vec<(String, Option<&'static str>)>, show_sensitive: bool) {
+    let mut by_lang: Vec<(&str, usize)> = vec![];
+    for &(_, lang) in &files {
+        let lang = lang.unwrap_or("Can't tell");
+        match by_lang.iter_mut().find(|&&mut (l, _)| l == lang) {
+            Some(row) => row.1 += 1,
+            None => by_lang.push((lang, 1)),
+        }
+    }
+    by_lang.sort_by_key(|&(lang, n)| (Reverse(n), lang));
+
+    let linesep = str_repeat("-", 80);
+    println!();
+    println!("{}", linesep);
+    println!(" {0: <66} {1: >12}", "Comments written in", "Files");
+    println!("{}", linesep);
+    for &(lang, n) in &by_lang {
+        println!(" {0: <66} {1: >12}", lang, n);
+    }
+    println!("{}", linesep);
+
+    // The most common language we could actually make out
+    let usual = by_lang.iter().map(|&(lang, _)| lang).find(|&lang| lang != "Can't tell");
+    let mut hidden = 0;
+    files.retain(|(path, lang)| {
+        if lang.is_none() || *lang == usual {
+            return false;
+        }
+        if !show_sensitive && is_sensitive(path) {
+            hidden += 1;
+            return false;
+        }
+        true
+    });
+    if !files.is_empty() {
+        files.sort();
+        println!(" {0: <66} {1: >12}", "Not in the usual language", "Comments");
+        println!("{}", linesep);
+        for (path, lang) in files {
+            println!(" {0: <66} {1: >12}", last_n_chars(&path, 66), lang.unwrap_or(""));
+        }
+        println!("{}", linesep);
+    }
+    print_hidden_note(hidden);
+}
+
+pub fn print_ambiguous(mut files: Vec<(String, Lang, Guess)>, show_sensitive: bool) {
+    let before = files.len();
+    files.retain(|(path, _, _)| show_sensitive || !is_sensitive(path));
+    let hidden = before - files.len();
+    if files.is_empty() {
+        print_hidden_note(hidden);
+        return;
+    }
+    files.sort_by(|a, b| a.2.confidence().total_cmp(&b.2.confidence()).then_with(|| a.0.cmp(&b.0)));
+
+    let linesep = str_repeat("-", 80);
+    println!();
+    println!(" The language of these files was a guess. Pin them down with --force-ext EXT=LANG");
+    println!(" or a [languages] section in {}:", config::CONFIG_FILE);
+    println!("{}", linesep);
+    println!(" {0: <35} {1: <15} {2: >5}  Because", "File", "Language", "Conf");
+    println!("{}", linesep);
+    for (path, lang, guess) in files {
+        let because = match guess {
+            Guess::SharedExt(langs) => format!("could be {}", langs[1..].join(", ")),
+            _                       => String::from("shebang, no extension"),
+        };
+        println!(" {0: <35} {1: <15} {2: >4.0}%  {3}",
+                 last_n_chars(&path, 35),
+                 last_n_chars(lang_name(&lang), 15),
+                 guess.confidence() * 100.0,
+                 because);
+    }
+    println!("{}", linesep);
+    print_hidden_note(hidden);
+}
+
+// Where did my files go?
+pub fn print_skipped(skipped: &Skipped, owner: bool) {
+    let linesep = str_repeat("-", 80);
+    let row = |why: &str, n: usize| println!(" {0: <66} {1: >12}", why, n);
+
+    println!();
+    println!("{}", linesep);
+    println!(" {0: <66} {1: >12}", "Not counted", "Files");
+    println!("{}", linesep);
+    row("Ignored by .gitignore, .ignore, etc. (-u to count)", skipped.ignored);
+    row("Hidden (-uu to count)", skipped.hidden);
+    row("Excluded by --include/--exclude, globs or .loc.toml", skipped.filtered);
+    if owner {
+        row("Not owned by --owned-by user", skipped.not_owned);
+    }
+    row("Unrecognized language, but text", skipped.unrecognized_text);
+    row("Binary (executables, archives, images, ...)", skipped.binary);
+    row("Data (binary, in no format we know)", skipped.data);
+    if skipped.unrecognized > 0 {
+        row("Unrecognized language, couldn't look inside", skipped.unrecognized);
+    }
+    row("Marked loc:ignore-file", skipped.marked_ignored);
+    row("Git LFS pointers", skipped.lfs_pointers);
+    row("Unreadable", skipped.unreadable);
+    row("Duplicates (symlinks, overlapping targets)", skipped.duplicates);
+    println!("{}", linesep);
+}
+
+pub fn human_size(bytes: usize) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1}M", b as f64 / f64::from(1 << 20)),
+        b if b >= 1 << 10 => format!("{:.1}K", b as f64 / f64::from(1 << 10)),
+        b                 => format!("{}B", b),
+    }
+}
+
+fn print_hidden_note(hidden: usize) {
+    let _ = write_hidden_note(&mut io::stdout(), hidden);
+}
+
+fn write_hidden_note(out: &mut dyn Write, hidden: usize) -> io::Result<()> {
+    if hidden > 0 {
+        writeln!(out, " {}", t(Text::HiddenSensitive).replace("{}", &hidden.to_string()))?;
+    }
+    Ok(())
+}
+
+// Small languages to fold into a single "Other" row in the summary: anything with fewer
+// than min_lines lines, less than min_share of all lines, or outside the top (by lines)
+// when top isn't 0.
+#[derive(Default)]
+pub struct Others {
+    pub min_lines: u32,
+    pub min_share: f64,
+    pub top: usize,
+}
+
+pub fn print_totals_only(out: &mut dyn Write, by_lang: &ByLang, only: TotalsOnly) -> io::Result<()> {
+    let total = by_lang.total();
+    match only {
+        TotalsOnly::Code => writeln!(out, "{}", total.count.code),
+        TotalsOnly::Row  => {
+            write!(out, " {}", pad(t(Text::Total), 17, false))?;
+            write_cells(out, &total)?;
+            writeln!(out)
+        }
+    }
+}
+
+pub fn print_summary(out: &mut dyn Write, by_lang: &ByLang, others: &Others, percent: bool,
+                     colors: Colors) -> io::Result<()> {
+    // print summary by language
+    let totals_by_lang: Vec<(&Lang, &LangTotal)> = by_lang.langs().iter().map(|(l, t)| (l, t)).collect();
+
+    let all_lines: u32 = totals_by_lang.iter().map(|&(_, t)| t.count.lines).sum();
+    let mut by_lines = totals_by_lang.clone();
+    by_lines.sort_by_key(|&(lang, t)| (Reverse(t.count.lines), lang_name(lang)));
+    let top: Vec<&Lang> = by_lines.into_iter().take(others.top).map(|(lang, _)| lang).collect();
+    let is_small = |lang: &Lang, t: &LangTotal| {
+        t.count.lines < others.min_lines || f64::from(t.count.lines) < others.min_share * f64::from(all_lines) ||
+            (others.top > 0 && !top.contains(&lang))
+    };
+    let mut other = LangTotal::default();
+    let mut n_other = 0;
+    let mut rows: Vec<(&str, &LangTotal)> = vec![];
+    for (lang, total) in totals_by_lang {
+        if is_small(lang, total) {
+            other.files += total.files;
+            other.bytes += total.bytes;
+            other.max_line = other.max_line.max(total.max_line);
+            other.count.merge(&total.count);
+            n_other += 1;
+        } else {
+            rows.push((lang_name(lang), total));
+        }
+    }
+    let other_name = t(Text::Other).replace("{}", &n_other.to_string());
+    if n_other > 0 {
+        rows.push((&other_name, &other));
+    }
+
+    print_totals_by_lang(out, &rows, percent, colors)
+}
+
+struct FamilyRow<'a> {
+    name: &'a str,
+    total: LangTotal,
+    // Empty for a language that isn't in a family
+    members: Vec<(&'a Lang, &'a LangTotal)>,
+}
+
+// --family: related languages rolled up into a row for the family, with a row for each of
+// them underneath.  Languages outside any family get a row of their own, as usual.
+pub fn print_families(out: &mut dyn Write, by_lang: &ByLang, sort: &Sort) -> io::Result<()> {
+    let mut rows: Vec<FamilyRow> = vec![];
+    for (lang, total) in by_lang.langs() {
+        let family = match lang.family() {
+            Some(family) => family,
+            None => {
+                let total = LangTotal { files: total.files, count: total.count.clone(), bytes: total.bytes, max_line: total.max_line };
+                rows.push(FamilyRow { name: lang_name(lang), total, members: vec![] });
+                continue;
+            }
+        };
+        let i = match rows.iter().position(|r| r.name == family && !r.members.is_empty()) {
+            Some(i) => i,
+            None => {
+                rows.push(FamilyRow { name: family, total: LangTotal::default(), members: vec![] });
+                rows.len() - 1
+            }
+        };
+        let row = &mut rows[i];
+        row.total.files += total.files;
+        row.total.bytes += total.bytes;
+        row.total.max_line = row.total.max_line.max(total.max_line);
+        row.total.count.merge(&total.count);
+        row.members.push((lang, total));
+    }
+
+    sort.rows(&mut rows, |r| r.name, |r| &r.total);
+
+    let linesep = str_repeat("-", table_width(0));
+    writeln!(out, "{}", linesep)?;
+    print_count_header(out, false)?;
+    writeln!(out, "{}", linesep)?;
+
+    let mut totals = LangTotal::default();
+    for row in &mut rows {
+        write!(out, " {: <17}", row.name)?;
+        write_cells(out, &row.total)?;
+        writeln!(out)?;
+        sort.langs(&mut row.members);
+        for &(lang, total) in &row.members {
+            write!(out, "   {: <15}", lang_name(lang))?;
+            write_cells(out, total)?;
+            writeln!(out)?;
+        }
+        totals.files += row.total.files;
+        totals.bytes += row.total.bytes;
+        totals.max_line = totals.max_line.max(row.total.max_line);
+        totals.count.merge(&row.total.count);
+    }
+
+    writeln!(out, "{}", linesep)?;
+    write!(out, " {}", pad(t(Text::Total), 17, false))?;
+    write_cells(out, &totals)?;
+    writeln!(out)?;
+    writeln!(out, "{}", linesep)
+}
+
+struct DirTotal<'a> {
+    dir: &'a str,
+    total: LangTotal,
+    // The language with the most code, and its share of the directory's code.
+    mostly: Option<(Lang, f64)>,
+}
+
+// Totals scaled up from --sample, with 95% confidence intervals.
+pub fn print_estimates(by_lang: &HashMap<Lang, LangEstimate>, total: &LangEstimate, sampled: usize, all: usize) {
+    let linesep = str_repeat("-", 80);
+    let row = |name: &str, e: &LangEstimate| {
+        println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
+                 name,
+                 e.files.total.round(),
+                 e.lines.total.round(),
+                 format!("±{}", e.lines.margin.round()),
+                 e.code.total.round(),
+                 format!("±{}", e.code.margin.round()));
+    };
+
+    println!("Estimated from {} of {} files ({:.1}%)",
+             sampled, all, 100.0 * sampled as f64 / all.max(1) as f64);
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
+             "Language",
+             "Files",
+             "Lines",
+             "± 95%",
+             "Code",
+             "± 95%");
+    println!("{}", linesep);
+
+    let mut langs: Vec<(&Lang, &LangEstimate)> = by_lang.iter().collect();
+    langs.sort_by(|a, b| b.1.code.total.total_cmp(&a.1.code.total).then(lang_name(a.0).cmp(lang_name(b.0))));
+    for (lang, e) in langs {
+        row(lang_name(lang), e);
+    }
+
+    println!("{}", linesep);
+    row("Total", total);
+    println!("{}", linesep);
+}
+
+pub fn print_size_estimates(by_lang: &HashMap<Lang, SizeEstimate>) {
+    let linesep = str_repeat("-", 80);
+    let row = |name: &str, e: &SizeEstimate| {
+        println!(" {0: <17} {1: >8} {2: >12} {3: >12}", name, e.files, human_size(e.bytes as usize), e.lines);
+    };
+
+    println!("Estimated from file sizes only, so expect these to be off by a third either way");
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >8} {2: >12} {3: >12}", "Language", "Files", "Size", "Lines");
+    println!("{}", linesep);
+
+    let mut langs: Vec<(&Lang, &SizeEstimate)> = by_lang.iter().collect();
+    langs.sort_by_key(|&(lang, e)| (Reverse(e.lines), lang_name(lang)));
+    let mut total = SizeEstimate::default();
+    for (lang, e) in langs {
+        row(lang_name(lang), e);
+        total.files += e.files;
+        total.bytes += e.bytes;
+        total.lines += e.lines;
+    }
+
+    println!("{}", linesep);
+    row("Total", &total);
+    println!("{}", linesep);
+}
+
+pub fn print_file_counts(by_lang: &HashMap<Lang, HashMap<String, u32>>) {
+    let linesep = str_repeat("-", 80);
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >8}  Extensions", "Language", "Files");
+    println!("{}", linesep);
+
+    let mut langs: Vec<(&Lang, u32)> = by_lang.iter().map(|(lang, exts)| (lang, exts.values().sum())).collect();
+    langs.sort_by_key(|&(lang, files)| (Reverse(files), lang_name(lang)));
+    let mut total = 0;
+    for (lang, files) in langs {
+        let mut exts: Vec<(&String, &u32)> = by_lang[lang].iter().collect();
+        exts.sort_by_key(|&(ext, n)| (Reverse(*n), ext.clone()));
+        let mut shown: Vec<String> = exts.iter().take(4).map(|(ext, n)| format!("{} {}", ext, n)).collect();
+        if exts.len() > 4 {
+            shown.push(format!("{} more", exts.len() - 4));
+        }
+        println!(" {0: <17} {1: >8}  {2}", lang_name(lang), files, shown.join(", "));
+        total += files;
+    }
+
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >8}", "Total", total);
+    println!("{}", linesep);
+}
+
+// Totals per directory (or per --group-by-path pattern, under a different heading), tagged
+// with the language most of its code is written in.
+pub fn print_by_dir(heading: Text, by_dir: &HashMap<String, Vec<FileCount>>, sort: &Sort) {
+    let linesep = str_repeat("-", 80);
+
+    let mut dirs: Vec<DirTotal> = by_dir.iter().map(|(dir, filecounts)| {
+        let mut total = Count::default();
+        let mut code_by_lang: HashMap<Lang, u32> = HashMap::new();
+        for fc in filecounts {
+            total.merge(&fc.count);
+            *code_by_lang.entry(fc.lang).or_insert(0) += fc.count.code;
+        }
+        let mostly = code_by_lang.into_iter()
+            .filter(|&(_, code)| code > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| lang_name(&b.0).cmp(lang_name(&a.0))))
+            .map(|(lang, code)| (lang, f64::from(code) / f64::from(total.code)));
+        DirTotal {
+            dir,
+            total: LangTotal {
+                files: file_total(filecounts),
+                count: total,
+                bytes: byte_total(filecounts),
+                max_line: max_line(filecounts),
+            },
+            mostly,
+        }
+    }).collect();
+
+    sort.rows(&mut dirs, |d| d.dir, |d| &d.total);
+
+    println!("{}", linesep);
+    println!(" {} {} {} {} {}  {}",
+             pad(t(heading), 17, false),
+             pad(t(Text::Files), 8, true),
+             pad(t(Text::Lines), 10, true),
+             pad(t(Text::Comment), 10, true),
+             pad(t(Text::Code), 10, true),
+             t(Text::Mostly));
+    println!("{}", linesep);
+
+    let mut totals = LangTotal::default();
+    for d in &dirs {
+        let mostly = match d.mostly {
+            Some((lang, share)) => format!("{} ({:.0}%)", lang_name(&lang), 100.0 * share),
+            None => String::new(),
+        };
+        println!(" {0: <17} {1: >8} {2: >10} {3: >10} {4: >10}  {5}",
+                 last_n_chars(d.dir, 17),
+                 d.total.files,
+                 d.total.count.lines,
+                 d.total.count.comment,
+                 d.total.count.code,
+                 mostly);
+        totals.files += d.total.files;
+        totals.count.merge(&d.total.count);
+    }
+
+    println!("{}", linesep);
+    println!(" {0} {1: >8} {2: >10} {3: >10} {4: >10}",
+             pad(t(Text::Total), 17, false),
+             totals.files,
+             totals.count.lines,
+             totals.count.comment,
+             totals.count.code);
+    println!("{}", linesep);
+}
+
+// How many files filecounts stands for, which a rolled up total can make more than there are
+pub fn file_total(filecounts: &[FileCount]) -> u32 {
+    filecounts.iter().map(|fc| fc.files).sum()
+}
+
+fn byte_total(filecounts: &[FileCount]) -> u64 {
+    filecounts.iter().map(|fc| fc.bytes).sum()
+}
+
+fn max_line(filecounts: &[FileCount]) -> u32 {
+    filecounts.iter().map(|fc| fc.max_line).max().unwrap_or(0)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Yaml,
+    Xml,
+    Html,
+    Csv,
+    Markdown,
+    Plain72,
+    Prometheus,
+    Dot,
+    Treemap,
+    Template,
+    Ndjson,
+}
+
+// Every name Format::from_name takes, for --capabilities
+pub const FORMAT_NAMES: &[&str] = &[
+    "table", "json", "yaml", "xml", "html", "csv", "md", "plain72", "prometheus", "dot",
+    "treemap-json", "template", "ndjson",
+];
+
+impl Format {
+    // By the name --format and --output know it by
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "table" => Some(Format::Table),
+            "json"  => Some(Format::Json),
+            "yaml"  => Some(Format::Yaml),
+            "xml"   => Some(Format::Xml),
+            "html"  => Some(Format::Html),
+            "csv"   => Some(Format::Csv),
+            "md"    => Some(Format::Markdown),
+            "plain72"    => Some(Format::Plain72),
+            "prometheus" => Some(Format::Prometheus),
+            "dot"   => Some(Format::Dot),
+            "treemap-json" => Some(Format::Treemap),
+            "template" => Some(Format::Template),
+            "ndjson" => Some(Format::Ndjson),
+            _       => None,
+        }
+    }
+
+    // For --output FILE without a format: report.json, report.csv...
+    pub fn from_ext(path: &str) -> Option<Format> {
+        let ext = path.rsplit_once('.')?.1.to_lowercase();
+        match ext.as_str() {
+            "json"         => Some(Format::Json),
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "xml"          => Some(Format::Xml),
+            "html" | "htm" => Some(Format::Html),
+            "csv"          => Some(Format::Csv),
+            "md"           => Some(Format::Markdown),
+            "dot"          => Some(Format::Dot),
+            _              => None,
+        }
+    }
+}
+
+// One scan's counts, grouped by language, for writing out in any number of formats.
+pub struct Counts<'a> {
+    pub by_lang: ByLang,
+    pub by_file: bool,
+    pub show_sensitive: bool,
+    pub others: &'a Others,
+    // For Format::Template, from --template
+    pub template: Option<&'a Template>,
+    // --percent: each language's share of the lines and code in the summary table
+    pub percent: bool,
+    // --totals-only, for the table
+    pub totals_only: Option<TotalsOnly>,
+    // --cocomo, with the yearly wage to cost the effort at, for json and yaml.  The table
+    // gets it as a note afterwards.
+    pub cocomo: Option<f64>,
+}
+
+// --color and --heat, for the summary table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Colors {
+    Off,
+    // The biggest languages and the total in bold
+    On,
+    // And the code column colored by each language's share of it
+    Heat,
+}
+
+// Just the Total row, or with --code-only just the lines of code, for scripts that want
+// one value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TotalsOnly {
+    Row,
+    Code,
+}
+
+// Colors only ever go to a terminal, so they're up to the caller rather than in Counts
+pub fn write_report(out: &mut dyn Write, format: Format, counts: &Counts, colors: Colors) -> io::Result<()> {
+    let by_lang = &counts.by_lang;
+    let (by_file, show_sensitive) = (counts.by_file, counts.show_sensitive);
+    let tree = || tree::build(|| by_lang.all());
+    match format {
+        Format::Json  => print_json(out, by_lang, by_file, show_sensitive, counts.cocomo),
+        Format::Yaml  => print_yaml(out, by_lang, by_file, show_sensitive, counts.cocomo),
+        Format::Xml   => print_xml(out, by_lang, by_file, show_sensitive),
+        Format::Html  => print_html(out, by_lang, show_sensitive),
+        Format::Csv   => print_csv(out, by_lang, by_file, show_sensitive),
+        Format::Markdown => print_markdown(out, by_lang, by_file, show_sensitive),
+        Format::Plain72  => print_plain72(out, by_lang, by_file, show_sensitive),
+        Format::Prometheus => print_prometheus(out, by_lang),
+        Format::Dot     => print_dot(out, &tree()),
+        Format::Treemap => print_treemap(out, &tree(), show_sensitive),
+        Format::Ndjson => print_ndjson(out, by_lang, show_sensitive),
+        Format::Template => print_template(out, by_lang, by_file, show_sensitive,
+                                           counts.template.expect("checked when --template was parsed")),
+        Format::Table => match (counts.totals_only, by_file) {
+            (Some(only), _) => print_totals_only(out, by_lang, only),
+            (None, true)    => print_by_file(out, by_lang, show_sensitive),
+            (None, false)   => print_summary(out, by_lang, counts.others, counts.percent, colors),
+        },
+    }
+}
+
+// Each --output, or just stdout without any.  path is None for stdout.
+pub fn write_reports(outputs: &[(Format, Option<&str>)], counts: &Counts, colors: Colors) {
+    // Files first, so they're written even if whatever's reading stdout goes away early
+    for &(format, path) in outputs.iter().filter(|o| o.1.is_some()) {
+        let path = path.expect("filtered");
+        let written = fs::File::create(path).and_then(|f| {
+            let mut out = io::BufWriter::new(f);
+            write_report(&mut out, format, counts, Colors::Off)?;
+            out.flush()
+        });
+        if let Err(e) = written {
+            println!("Error: writing the report to {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    for &(format, _) in outputs.iter().filter(|o| o.1.is_none()) {
+        // Most likely a closed pipe, with nobody left to tell
+        if write_report(&mut io::stdout().lock(), format, counts, colors).is_err() {
+            std::process::exit(1);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    languages: Vec<ReportLang<'a>>,
+    total: ReportTotal,
+    // sensitive files left out of by_file
+    #[serde(skip_serializing_if = "is_zero")]
+    hidden: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cocomo: Option<Estimate>,
+}
+
+#[derive(Serialize)]
+struct ReportLang<'a> {
+    language: String,
+    // The language's own name, when --rename-lang or [rename-languages] called it something
+    // else, so the report can still be read back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    renamed_from: Option<String>,
+    files: u32,
+    #[serde(flatten)]
+    count: ReportCount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    by_file: Option<ReportFiles<'a>>,
+}
+
+#[derive(Serialize)]
+struct ReportTotal {
+    files: u32,
+    #[serde(flatten)]
+    count: ReportCount,
+}
+
+// A language's files, read out as they're written rather than all gathered up first
+struct ReportFiles<'a> {
+    by_lang: &'a ByLang,
+    lang: Lang,
+    show_sensitive: bool,
+}
+
+impl<'a> Serialize for ReportFiles<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let show_sensitive = self.show_sensitive;
+        serializer.collect_seq(self.by_lang.files(self.lang)
+            .filter(|fc| show_sensitive || !is_sensitive(&fc.path))
+            .map(|fc| ReportFile {
+                count: ReportCount::from(&fc.count),
+                meta: fc.meta.as_ref().map(ReportMeta::from),
+                path: fc.path,
+            }))
+    }
+}
+
+#[derive(Serialize)]
+struct ReportFile {
+    path: String,
+    #[serde(flatten)]
+    count: ReportCount,
+    #[serde(flatten)]
+    meta: Option<ReportMeta>,
+}
+
+#[derive(Serialize)]
+struct ReportCount {
+    lines: u32,
+    blank: u32,
+    comment: u32,
+    code: u32,
+}
+
+#[derive(Serialize)]
+struct ReportMeta {
+    encoding: &'static str,
+    bom: bool,
+    line_endings: &'static str,
+}
+
+impl From<&Count> for ReportCount {
+    fn from(c: &Count) -> ReportCount {
+        ReportCount { lines: c.lines, blank: c.blank, comment: c.comment, code: c.code }
+    }
+}
+
+impl From<&FileMeta> for ReportMeta {
+    fn from(meta: &FileMeta) -> ReportMeta {
+        ReportMeta {
+            encoding: meta.encoding.to_s(),
+            bom: meta.bom,
+            line_endings: meta.line_endings.to_s(),
+        }
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+// What --format json and yaml print: the same numbers as the tables (every language,
+// nothing folded into Other), with each file under its language if by_file.
+fn report(by_lang: &ByLang, by_file: bool, show_sensitive: bool) -> Report<'_> {
+    let total = by_lang.total();
+    // Counted up front, since they're only left out as the files are written
+    let hidden = if by_file && !show_sensitive {
+        by_lang.all().filter(|fc| is_sensitive(&fc.path)).count()
+    } else {
+        0
+    };
+    let languages = by_lang.langs().iter().map(|&(lang, ref lang_total)| {
+        let language = lang_name(&lang).to_string();
+        ReportLang {
+            renamed_from: if language != lang.to_s() { Some(lang.to_s().to_string()) } else { None },
+            language,
+            files: lang_total.files,
+            count: ReportCount::from(&lang_total.count),
+            by_file: if by_file { Some(ReportFiles { by_lang, lang, show_sensitive }) } else { None },
+        }
+    }).collect();
+    Report {
+        languages,
+        total: ReportTotal { files: total.files, count: ReportCount::from(&total.count) },
+        hidden,
+        cocomo: None,
+    }
+}
+
+pub fn print_json(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool,
+                  cocomo: Option<f64>) -> io::Result<()> {
+    let mut report = report(by_lang, by_file, show_sensitive);
+    report.cocomo = cocomo.map(|wage| cocomo::estimate(report.total.count.code, wage));
+    serde_json::to_writer_pretty(&mut *out, &report).map_err(io::Error::from)?;
+    writeln!(out)
+}
+
+#[derive(Serialize)]
+struct NdjsonFile<'a> {
+    path: &'a str,
+    language: &'a str,
+    #[serde(flatten)]
+    count: ReportCount,
+    #[serde(flatten)]
+    meta: Option<ReportMeta>,
+}
+
+pub fn ndjson_line(fc: &FileCount) -> String {
+    serde_json::to_string(&NdjsonFile {
+        path: &fc.path,
+        language: lang_name(&fc.lang),
+        count: ReportCount::from(&fc.count),
+        meta: fc.meta.as_ref().map(ReportMeta::from),
+    }).expect("json")
+}
+
+// The same lines after the fact, for when the count can't be streamed
+pub fn print_ndjson(out: &mut dyn Write, by_lang: &ByLang, show_sensitive: bool) -> io::Result<()> {
+    for fc in by_lang.all().filter(|fc| show_sensitive || !is_sensitive(&fc.path)) {
+        writeln!(out, "{}", ndjson_line(&fc))?;
+    }
+    Ok(())
+}
+
+pub fn print_yaml(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool,
+                  cocomo: Option<f64>) -> io::Result<()> {
+    let mut report = report(by_lang, by_file, show_sensitive);
+    report.cocomo = cocomo.map(|wage| cocomo::estimate(report.total.count.code, wage));
+    serde_yaml::to_writer(out, &report).map_err(io::Error::other)
+}
+
+// --format csv: a header and a row per language, or with by_file a row per file.  No total
+// row, so the rows can go straight into a spreadsheet or a dataframe.
+pub fn print_csv(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool) -> io::Result<()> {
+    let row = |out: &mut dyn Write, first: &[&str], c: &Count| {
+        let mut fields: Vec<String> = first.iter().map(|f| escape_csv(f)).collect();
+        fields.extend([c.lines, c.blank, c.comment, c.code].iter().map(u32::to_string));
+        writeln!(out, "{}", fields.join(","))
+    };
+    if by_file {
+        writeln!(out, "language,path,lines,blank,comment,code")?;
+    } else {
+        writeln!(out, "language,files,lines,blank,comment,code")?;
+    }
+    for (lang, total, filecounts) in by_lang.iter() {
+        if !by_file {
+            row(out, &[lang_name(&lang), &total.files.to_string()], &total.count)?;
+            continue;
+        }
+        // Like the table, sensitive files aren't listed unless asked for
+        for fc in filecounts.filter(|fc| show_sensitive || !is_sensitive(&fc.path)) {
+            row(out, &[lang_name(&lang), &fc.path], &fc.count)?;
+        }
+    }
+    Ok(())
+}
+
+// --template: a line per language, or per file with by_file, filled in from the template
+pub fn print_template(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool, template: &Template) -> io::Result<()> {
+    for (lang, total, filecounts) in by_lang.iter() {
+        let language = lang_name(&lang);
+        if !by_file {
+            writeln!(out, "{}", template.render(&Row { language, path: "", files: total.files, count: &total.count }))?;
+            continue;
+        }
+        for fc in filecounts.filter(|fc| show_sensitive || !is_sensitive(&fc.path)) {
+            writeln!(out, "{}", template.render(&Row { language, path: &fc.path, files: 1, count: &fc.count }))?;
+        }
+    }
+    Ok(())
+}
+
+// --format prometheus: gauges per language in the text exposition format, for node_exporter's
+// textfile collector.  No per-file series, there'd be far too many.  node_exporter already
+// reports when the file was written (node_textfile_mtime_seconds), so there's no timestamp.
+pub fn print_prometheus(out: &mut dyn Write, by_lang: &ByLang) -> io::Result<()> {
+    let mut by_lang: Vec<&(Lang, LangTotal)> = by_lang.langs().iter().collect();
+    // by name, so the file only changes when the numbers do
+    by_lang.sort_by(|a, b| lang_name(&a.0).cmp(lang_name(&b.0)));
+    let metrics = [
+        ("loc_files",         "Files counted"),
+        ("loc_lines",         "Lines, of any kind"),
+        ("loc_blank_lines",   "Blank lines"),
+        ("loc_comment_lines", "Lines that are comments"),
+        ("loc_code_lines",    "Lines of code"),
+    ];
+    for (i, (name, help)) in metrics.iter().enumerate() {
+        writeln!(out, "# HELP {} {}", name, help)?;
+        writeln!(out, "# TYPE {} gauge", name)?;
+        for &(lang, total) in &by_lang {
+            let c = &total.count;
+            let value = [total.files, c.lines, c.blank, c.comment, c.code][i];
+            writeln!(out, "{}{{language=\"{}\"}} {}", name, escape_prometheus(lang_name(lang)), value)?;
+        }
+    }
+    Ok(())
+}
+
+fn escape_prometheus(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// --format xml: laid out like cloc's --xml, so tools written for that can read it.
+//
+//     <results>
+//       <header>...</header>
+//       <files>                          (with by_file)
+//         <file name=".." language=".." blank=".." comment=".." code=".." />
+//         <total blank=".." comment=".." code=".." />
+//       </files>
+//       <languages>
+//         <language name=".." files_count=".." blank=".." comment=".." code=".." />
+//         <total sum_files=".." blank=".." comment=".." code=".." />
+//       </languages>
+//     </results>
+pub fn print_xml(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool) -> io::Result<()> {
+    let total = by_lang.total();
+    let counts = |c: &Count| format!("blank=\"{}\" comment=\"{}\" code=\"{}\"", c.blank, c.comment, c.code);
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<results>")?;
+    writeln!(out, "  <header>")?;
+    writeln!(out, "    <loc_version>{}</loc_version>", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "    <n_files>{}</n_files>", total.files)?;
+    writeln!(out, "    <n_lines>{}</n_lines>", total.count.lines)?;
+    writeln!(out, "  </header>")?;
+    if by_file {
+        writeln!(out, "  <files>")?;
+        let mut shown = Count::default();
+        for (lang, _, filecounts) in by_lang.iter() {
+            for fc in filecounts.filter(|fc| show_sensitive || !is_sensitive(&fc.path)) {
+                writeln!(out, "    <file name=\"{}\" language=\"{}\" {} />",
+                              escape_html(&fc.path), escape_html(lang_name(&lang)), counts(&fc.count))?;
+                shown.merge(&fc.count);
+            }
+        }
+        writeln!(out, "    <total {} />", counts(&shown))?;
+        writeln!(out, "  </files>")?;
+    }
+    writeln!(out, "  <languages>")?;
+    for (lang, lang_total) in by_lang.langs() {
+        writeln!(out, "    <language name=\"{}\" files_count=\"{}\" {} />",
+                      escape_html(lang_name(lang)), lang_total.files, counts(&lang_total.count))?;
+    }
+    writeln!(out, "    <total sum_files=\"{}\" {} />", total.files, counts(&total.count))?;
+    writeln!(out, "  </languages>")?;
+    writeln!(out, "</results>")
+}
+
+// --format md: the summary as a GitHub-flavored Markdown table, for pasting into PRs and
+// wikis, and with by_file a table of the files after it.
+pub fn print_markdown(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool) -> io::Result<()> {
+    let row = |out: &mut dyn Write, first: &[&str], c: &Count| {
+        let mut cells: Vec<String> = first.iter().map(|f| f.replace('|', "\\|")).collect();
+        cells.extend([c.lines, c.blank, c.comment, c.code].iter().map(u32::to_string));
+        writeln!(out, "| {} |", cells.join(" | "))
+    };
+
+    writeln!(out, "| {} | {} | {} | {} | {} | {} |", t(Text::Language), t(Text::Files), t(Text::Lines),
+                  t(Text::Blank), t(Text::Comment), t(Text::Code))?;
+    writeln!(out, "|:---------|------:|------:|------:|--------:|-----:|")?;
+    for (lang, lang_total) in by_lang.langs() {
+        row(out, &[lang_name(lang), &lang_total.files.to_string()], &lang_total.count)?;
+    }
+    let total = by_lang.total();
+    row(out, &[&format!("**{}**", t(Text::Total)), &total.files.to_string()], &total.count)?;
+
+    if !by_file {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "| {} | {} | {} | {} | {} | {} |", t(Text::File), t(Text::Language), t(Text::Lines),
+                  t(Text::Blank), t(Text::Comment), t(Text::Code))?;
+    writeln!(out, "|:-----|:---------|------:|------:|--------:|-----:|")?;
+    let mut hidden = 0;
+    for (lang, _, filecounts) in by_lang.iter() {
+        for fc in filecounts {
+            if !show_sensitive && is_sensitive(&fc.path) {
+                hidden += 1;
+                continue;
+            }
+            row(out, &[&format!("`{}`", fc.path), lang_name(&lang)], &fc.count)?;
+        }
+    }
+    if hidden > 0 {
+        writeln!(out)?;
+        write_hidden_note(out, hidden)?;
+    }
+    Ok(())
+}
+
+// --format plain72: for plain-text email and commit messages, so never wider than 72
+// columns and only ASCII, whatever the terminal or the names.  The columns are wide enough
+// for any u32, so they line up the same from run to run.
+pub fn print_plain72(out: &mut dyn Write, by_lang: &ByLang, by_file: bool, show_sensitive: bool) -> io::Result<()> {
+    let heavy = str_repeat("=", 72);
+    let light = str_repeat("-", 72);
+    let row = |out: &mut dyn Write, name: &str, files: &str, c: &Count| {
+        writeln!(out, "{0: <17}{1: >11}{2: >11}{3: >11}{4: >11}{5: >11}",
+                      ascii_fit(name, 17, false), files, c.lines, c.blank, c.comment, c.code)
+    };
+
+    writeln!(out, "{}", heavy)?;
+    writeln!(out, "{0: <17}{1: >11}{2: >11}{3: >11}{4: >11}{5: >11}",
+                  "Language", "Files", "Lines", "Blank", "Comment", "Code")?;
+    writeln!(out, "{}", light)?;
+    let mut total = LangTotal::default();
+    let mut hidden = 0;
+    for (lang, lang_total, filecounts) in by_lang.iter() {
+        row(out, lang_name(&lang), &lang_total.files.to_string(), &lang_total.count)?;
+        total.files += lang_total.files;
+        total.count.merge(&lang_total.count);
+        if !by_file {
+            continue;
+        }
+        for fc in filecounts {
+            if !show_sensitive && is_sensitive(&fc.path) {
+                hidden += 1;
+                continue;
+            }
+            // indented under the language, in the name and files columns
+            writeln!(out, "  {0: <26}{1: >11}{2: >11}{3: >11}{4: >11}",
+                          ascii_fit(&fc.path, 26, true), fc.count.lines, fc.count.blank, fc.count.comment, fc.count.code)?;
+        }
+    }
+    writeln!(out, "{}", light)?;
+    row(out, "Total", &total.files.to_string(), &total.count)?;
+    writeln!(out, "{}", heavy)?;
+    if hidden > 0 {
+        writeln!(out, "{} sensitive files (keys, credentials, .env) aren't listed.", hidden)?;
+    }
+    Ok(())
+}
+
+// s with anything outside ASCII as '?', cut to width with a '~' where it was cut: the end
+// for names, the start for paths, since that's the part that tells files apart.
+fn ascii_fit(s: &str, width: usize, keep_end: bool) -> String {
+    let chars: Vec<char> = s.chars().map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' }).collect();
+    if chars.len() <= width {
+        return chars.into_iter().collect();
+    }
+    if keep_end {
+        let start = chars.len() - (width - 1);
+        format!("~{}", chars[start..].iter().collect::<String>())
+    } else {
+        format!("{}~", chars[..width - 1].iter().collect::<String>())
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// --format dot: the directory tree as a Graphviz graph.  Each directory is a box sized by
+// how much code is under it and colored by the language most of that code is in.
+//
+//     loc --format dot | dot -Tsvg > map.svg
+pub fn print_dot(out: &mut dyn Write, root: &Dir) -> io::Result<()> {
+    writeln!(out, "digraph loc {{")?;
+    writeln!(out, "  graph [rankdir=LR];")?;
+    writeln!(out, "  node [shape=box, style=\"filled,rounded\", fontname=\"sans-serif\"];")?;
+    dot_dir(out, root, &root.name, root.count.code.max(1))?;
+    writeln!(out, "}}")
+}
+
+fn dot_dir(out: &mut dyn Write, dir: &Dir, id: &str, max_code: u32) -> io::Result<()> {
+    // By area, so a directory with 4x the code looks 4x as big
+    let scale = (f64::from(dir.count.code) / f64::from(max_code)).sqrt();
+    let (color, mostly) = match dir.mostly() {
+        Some(lang) => (lang_color(&lang), lang_name(&lang).to_string()),
+        None => (String::from("white"), String::new()),
+    };
+    writeln!(out, "  \"{}\" [label=\"{}\\n{} code\\n{}\", width={:.2}, height={:.2}, fontsize={:.0}, fillcolor=\"{}\"];",
+                  escape_dot(id),
+                  escape_dot(&dir.name),
+                  dir.count.code,
+                  escape_dot(&mostly),
+                  0.75 + 3.0 * scale,
+                  0.5 + 1.5 * scale,
+                  10.0 + 14.0 * scale,
+                  color)?;
+    for child in dir.dirs.values() {
+        let child_id = format!("{}/{}", id, child.name);
+        writeln!(out, "  \"{}\" -> \"{}\";", escape_dot(id), escape_dot(&child_id))?;
+        dot_dir(out, child, &child_id, max_code)?;
+    }
+    Ok(())
+}
+
+// --format treemap-json: the directory tree in d3's "flare" shape, which most treemap and
+// sunburst code takes as is.  Directories have children, files have a value (their lines
+// of code) and a language.
+#[derive(Serialize)]
+struct FlareNode {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<FlareNode>,
+}
+
+pub fn print_treemap(out: &mut dyn Write, root: &Dir, show_sensitive: bool) -> io::Result<()> {
+    writeln!(out, "{}", serde_json::to_string_pretty(&flare(root, "", show_sensitive)).expect("json"))
+}
+
+fn flare(dir: &Dir, path: &str, show_sensitive: bool) -> FlareNode {
+    let path = if path.is_empty() { dir.name.clone() } else { format!("{}/{}", path, dir.name) };
+    let mut children: Vec<FlareNode> = dir.dirs.values().map(|d| flare(d, &path, show_sensitive)).collect();
+    for (name, &(lang, ref count)) in &dir.files_here {
+        if !show_sensitive && is_sensitive(&format!("{}/{}", path, name)) {
+            continue;
+        }
+        children.push(FlareNode {
+            name: name.clone(),
+            value: Some(count.code),
+            language: Some(lang_name(&lang).to_string()),
+            children: vec![],
+        });
+    }
+    FlareNode { name: dir.name.clone(), value: None, language: None, children }
+}
+
+// A pale color of its own for each language, the same from run to run.
+fn lang_color(lang: &Lang) -> String {
+    format!("{:.3} 0.35 0.95", lang_hue(lang))
+}
+
+// Between 0 and 1, from a hash of the name.
+fn lang_hue(lang: &Lang) -> f64 {
+    let hash = lang.to_s().bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ u64::from(b)).wrapping_mul(0x100_0000_01b3));
+    (hash % 1000) as f64 / 1000.0
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+const HTML_STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { padding: 0.2em 0.8em; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+th { cursor: pointer; user-select: none; }
+th.asc::after { content: ' \\25b2'; }
+th.desc::after { content: ' \\25bc'; }
+tbody tr:nth-child(odd) { background: #f4f4f4; }
+tfoot td { font-weight: bold; border-top: 1px solid #999; }
+nav p { margin: 0.2em 0; }
+nav b { display: inline-block; width: 1.5em; }
+details { margin: 0.5em 0; }
+summary { cursor: pointer; font-weight: bold; }
+.charts { display: flex; flex-wrap: wrap; gap: 2em; align-items: center; }
+.charts text { font-size: 12px; }
+";
+
+// Click a column header to sort its table by it, again to reverse.  Numbers sort as
+// numbers, and the total row stays at the bottom since it's in the tfoot.
+const HTML_SCRIPT: &str = "
+document.querySelectorAll('thead th').forEach(function (th) {
+  th.title = 'Sort';
+  th.addEventListener('click', function () {
+    var table = th.closest('table'), body = table.tBodies[0], col = th.cellIndex;
+    var desc = !th.classList.contains('desc');
+    table.querySelectorAll('thead th').forEach(function (h) { h.classList.remove('asc', 'desc'); });
+    th.classList.add(desc ? 'desc' : 'asc');
+    var key = function (row) {
+      var text = row.cells[col].textContent, n = Number(text);
+      return isNaN(n) ? text.toLowerCase() : n;
+    };
+    Array.from(body.rows).sort(function (a, b) {
+      var x = key(a), y = key(b), order = x < y ? -1 : x > y ? 1 : 0;
+      return desc ? -order : order;
+    }).forEach(function (row) { body.appendChild(row); });
+  });
+});
+";
+
+// How many languages get a bar or slice of their own in the charts; the rest are lumped
+// into one.
+const CHART_LANGS: usize = 10;
+
+// --format html: one self-contained page, no scripts or styles from anywhere else.  A table
+// of contents (languages grouped by their first letter), the summary table, a bar and a pie
+// chart of code per language, then a collapsed section per language listing its files.
+// Every language has an anchor, #lang-<name>, so a big report can be linked into, and every
+// table sorts by any column.
+pub fn print_html(out: &mut dyn Write, by_lang: &ByLang, show_sensitive: bool) -> io::Result<()> {
+    let counts = |c: &Count| vec![c.lines.to_string(), c.blank.to_string(), c.comment.to_string(), c.code.to_string()];
+    let anchor = |lang: &Lang| {
+        let mut slug = String::from("lang-");
+        for ch in lang_name(lang).to_lowercase().chars() {
+            if ch.is_alphanumeric() {
+                slug.push(ch);
+            } else if !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        slug.trim_end_matches('-').to_string()
+    };
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"{}\"><head><meta charset=\"utf-8\"><title>loc</title><style>{}</style></head>",
+             ui().code(), HTML_STYLE)?;
+    writeln!(out, "<body id=\"top\">")?;
+
+    let mut names: Vec<&Lang> = by_lang.langs().iter().map(|(lang, _)| lang).collect();
+    names.sort_by_key(|lang| lang_name(lang).to_lowercase());
+    writeln!(out, "<nav><h2>{}</h2>", t(Text::Languages))?;
+    let mut names = names.into_iter().peekable();
+    while let Some(first) = names.next() {
+        let letter = lang_name(first).chars().next().unwrap_or(' ').to_uppercase().to_string();
+        let mut links = vec![first];
+        while let Some(lang) = names.next_if(|l| lang_name(l).to_uppercase().starts_with(&letter)) {
+            links.push(lang);
+        }
+        let links: Vec<String> = links.iter()
+            .map(|lang| format!("<a href=\"#{}\">{}</a>", anchor(lang), escape_html(lang_name(lang))))
+            .collect();
+        writeln!(out, "<p><b>{}</b> {}</p>", escape_html(&letter), links.join(" &middot; "))?;
+    }
+    writeln!(out, "</nav>")?;
+
+    writeln!(out, "<h2>{}</h2>", t(Text::Summary))?;
+    html_head(out, &[Text::Language, Text::Files, Text::Lines, Text::Blank, Text::Comment, Text::Code])?;
+    for (lang, lang_total) in by_lang.langs() {
+        let mut cells = vec![format!("<a href=\"#{}\">{}</a>", anchor(lang), escape_html(lang_name(lang))),
+                             lang_total.files.to_string()];
+        cells.extend(counts(&lang_total.count));
+        html_row(out, &cells)?;
+    }
+    let total = by_lang.total();
+    writeln!(out, "</tbody><tfoot>")?;
+    let mut cells = vec![escape_html(t(Text::Total)), total.files.to_string()];
+    cells.extend(counts(&total.count));
+    html_row(out, &cells)?;
+    writeln!(out, "</tfoot></table>")?;
+
+    let mut by_code: Vec<(String, String, u32)> = by_lang.langs().iter()
+        .filter(|(_, t)| t.count.code > 0)
+        .map(|(lang, t)| (lang_name(lang).to_string(), format!("hsl({:.0}, 55%, 55%)", lang_hue(lang) * 360.0), t.count.code))
+        .collect();
+    by_code.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    if by_code.len() > CHART_LANGS {
+        let rest = by_code.split_off(CHART_LANGS - 1);
+        by_code.push((t(Text::Other).replace("{}", &rest.len().to_string()), String::from("#999"), rest.iter().map(|r| r.2).sum()));
+    }
+    if !by_code.is_empty() {
+        writeln!(out, "<h2>{}</h2><div class=\"charts\">", escape_html(t(Text::CodePerLanguage)))?;
+        html_bar_chart(out, &by_code)?;
+        html_pie_chart(out, &by_code)?;
+        writeln!(out, "</div>")?;
+    }
+
+    writeln!(out, "<h2>{}</h2>", escape_html(t(Text::Files)))?;
+    let mut hidden = 0;
+    for (lang, lang_total, filecounts) in by_lang.iter() {
+        let summary = t(Text::LangSummary).replace("{files}", &lang_total.files.to_string())
+                                          .replace("{code}", &lang_total.count.code.to_string());
+        writeln!(out, "<details id=\"{}\"><summary>{} ({})</summary>",
+                 anchor(&lang), escape_html(lang_name(&lang)), escape_html(&summary))?;
+        html_head(out, &[Text::File, Text::Lines, Text::Blank, Text::Comment, Text::Code])?;
+        for fc in filecounts {
+            if !show_sensitive && is_sensitive(&fc.path) {
+                hidden += 1;
+                continue;
+            }
+            let mut cells = vec![escape_html(&fc.path)];
+            cells.extend(counts(&fc.count));
+            html_row(out, &cells)?;
+        }
+        writeln!(out, "</tbody></table><p><a href=\"#top\">{}</a></p></details>", escape_html(t(Text::BackToTop)))?;
+    }
+    if hidden > 0 {
+        writeln!(out, "<p>{}.</p>", escape_html(&t(Text::HiddenSensitive).replace("{}", &hidden.to_string())))?;
+    }
+    writeln!(out, "<script>{}</script>", HTML_SCRIPT)?;
+    writeln!(out, "</body></html>")?;
+    out.flush()
+}
+
+// Opens a table, up to its tbody.
+fn html_head(out: &mut dyn Write, headings: &[Text]) -> io::Result<()> {
+    let cells: Vec<String> = headings.iter().map(|&h| format!("<th>{}</th>", escape_html(t(h)))).collect();
+    writeln!(out, "<table><thead><tr>{}</tr></thead><tbody>", cells.join(""))
+}
+
+fn html_row(out: &mut dyn Write, cells: &[String]) -> io::Result<()> {
+    let cells: Vec<String> = cells.iter().map(|c| format!("<td>{}</td>", c)).collect();
+    writeln!(out, "<tr>{}</tr>", cells.join(""))
+}
+
+// (name, color, code) for each bar, biggest first.
+fn html_bar_chart(out: &mut dyn Write, bars: &[(String, String, u32)]) -> io::Result<()> {
+    let (label_w, bar_w, row_h) = (140.0, 300.0, 22.0);
+    let max = f64::from(bars.iter().map(|b| b.2).max().unwrap_or(1).max(1));
+    writeln!(out, "<svg width=\"{}\" height=\"{}\" role=\"img\" aria-label=\"Code per language\">",
+             label_w + bar_w + 80.0, row_h * bars.len() as f64)?;
+    for (i, (name, color, code)) in bars.iter().enumerate() {
+        let y = row_h * i as f64;
+        let w = (f64::from(*code) / max * bar_w).max(1.0);
+        writeln!(out, "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>", label_w - 6.0, y + 15.0, escape_html(name))?;
+        writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"><title>{}: {}</title></rect>",
+                 label_w, y + 3.0, w, row_h - 6.0, color, escape_html(name), code)?;
+        writeln!(out, "<text x=\"{:.1}\" y=\"{}\">{}</text>", label_w + w + 6.0, y + 15.0, code)?;
+    }
+    writeln!(out, "</svg>")
+}
+
+fn html_pie_chart(out: &mut dyn Write, slices: &[(String, String, u32)]) -> io::Result<()> {
+    let (r, c) = (100.0, 110.0);
+    let total = f64::from(slices.iter().map(|s| s.2).sum::<u32>().max(1));
+    writeln!(out, "<svg width=\"{}\" height=\"{}\" role=\"img\" aria-label=\"Share of code per language\">", c * 2.0, c * 2.0)?;
+    let mut angle: f64 = 0.0;
+    for (name, color, code) in slices {
+        let share = f64::from(*code) / total;
+        let title = format!("<title>{}: {} ({:.1}%)</title>", escape_html(name), code, share * 100.0);
+        if share >= 0.9999 {
+            // an arc can't go all the way round
+            writeln!(out, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\">{}</circle>", c, c, r, color, title)?;
+            break;
+        }
+        let point = |a: f64| (c + r * a.sin(), c - r * a.cos());
+        let (x1, y1) = point(angle);
+        angle += share * 2.0 * std::f64::consts::PI;
+        let (x2, y2) = point(angle);
+        writeln!(out, "<path d=\"M{} {} L{:.2} {:.2} A{} {} 0 {} 1 {:.2} {:.2} Z\" fill=\"{}\" stroke=\"#fff\">{}</path>",
+                 c, c, x1, y1, r, r, if share > 0.5 { 1 } else { 0 }, x2, y2, color, title)?;
+    }
+    writeln!(out, "</svg>")
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&'  => escaped.push_str("&amp;"),
+            '<'  => escaped.push_str("&lt;"),
+            '>'  => escaped.push_str("&gt;"),
+            '"'  => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            ch   => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// Third-party packages, biggest first.  top limits how many get a row of their own (0
+// for all of them); the rest still go into the total.
+pub fn print_deps(kind: &str, mut deps: Vec<Dep>, top: usize) {
+    let linesep = str_repeat("-", 80);
+    deps.sort_by(|a, b| b.count.code.cmp(&a.count.code)
+                          .then_with(|| a.name.cmp(&b.name))
+                          .then_with(|| a.version.cmp(&b.version)));
+
+    println!("{}", linesep);
+    println!(" {0: <27} {1: >7} {2: >10} {3: >9} {4: >10}  Mostly",
+             kind,
+             "Files",
+             "Lines",
+             "Comment",
+             "Code");
+    println!("{}", linesep);
+
+    let mut totals = LangTotal::default();
+    for (i, dep) in deps.iter().enumerate() {
+        totals.files += dep.files;
+        totals.count.merge(&dep.count);
+        if top > 0 && i >= top {
+            continue;
+        }
+        let name = if dep.version.is_empty() { dep.name.clone() } else { format!("{} {}", dep.name, dep.version) };
+        println!(" {0: <27} {1: >7} {2: >10} {3: >9} {4: >10}  {5}",
+                 last_n_chars(&name, 27),
+                 dep.files,
+                 dep.count.lines,
+                 dep.count.comment,
+                 dep.count.code,
+                 dep.mostly.as_ref().map(lang_name).unwrap_or(""));
+    }
+    if top > 0 && deps.len() > top {
+        println!(" ({} more)", deps.len() - top);
+    }
+
+    println!("{}", linesep);
+    println!(" {0: <27} {1: >7} {2: >10} {3: >9} {4: >10}",
+             format!("Total ({})", deps.len()),
+             totals.files,
+             totals.count.lines,
+             totals.count.comment,
+             totals.count.code);
+    println!("{}", linesep);
+}
+
+// --explain: each line of a file with what it counted as, tabs and trailing spaces made
+// visible since they can be what decides.
+pub fn print_explain(path: &str, lang: Lang, kinds: &[LineKind], text: &str) {
+    println!("{}: {}", path, lang_name(&lang));
+    println!("Blank lines: {}", BlankRule::for_lang(lang).describe());
+    if lang == Lang::Unrecognized {
+        return;
+    }
+    let mut count = Count::default();
+    for (i, (line, &kind)) in text.lines().zip(kinds).enumerate() {
+        count.add_line(kind);
+        let kind = match kind {
+            LineKind::Code    => "code",
+            LineKind::Comment => "comment",
+            LineKind::Blank   => "blank",
+        };
+        let shown = line.replace('\t', "→");
+        let trailing = shown.len() - shown.trim_end().len();
+        println!("{:>6} {:<8} {}{}", i + 1, kind, shown.trim_end(), "·".repeat(trailing));
+    }
+    println!("{} lines: {} code, {} comment, {} blank", count.lines, count.code, count.comment, count.blank);
+}
+
+// --doc-coverage: each crate's pub items and how many are documented, then its modules.
+pub fn print_doc_coverage(crates: &[CrateDocs]) {
+    let linesep = str_repeat("-", 80);
+    println!("{}", linesep);
+    println!(" {0: <44} {1: >10} {2: >11} {3: >10}", "Crate / module", "Pub items", "Documented", "Coverage");
+    println!("{}", linesep);
+
+    let row = |name: &str, cov: &DocCoverage| {
+        println!(" {0: <44} {1: >10} {2: >11} {3: >9.1}%",
+                 last_n_chars(name, 44), cov.items, cov.documented, cov.percent());
+    };
+    let mut total = DocCoverage::default();
+    for krate in crates {
+        let crate_total = krate.total();
+        total.merge(&crate_total);
+        row(&krate.name, &crate_total);
+        for (module, cov) in &krate.modules {
+            row(&format!("  {}", module), cov);
+        }
+    }
+    println!("{}", linesep);
+    row(&format!("Total ({} crates)", crates.len()), &total);
+    println!("{}", linesep);
+}
+
+// Languages and files with comments under min_ratio of comments + code.  Languages that
+// can't have comments are left out.  Returns how many files fell short.
+pub fn print_comment_ratio<I: IntoIterator<Item = FileCount>>(filecounts: I, min_ratio: f64, show_sensitive: bool) -> usize {
+    let linesep = str_repeat("-", 80);
+    let ratio = |c: &Count| f64::from(c.comment) / f64::from((c.comment + c.code).max(1));
+
+    let mut checked = 0;
+    let mut by_lang: HashMap<Lang, LangTotal> = HashMap::new();
+    let mut files: Vec<FileCount> = vec![];
+    for fc in filecounts.into_iter().filter(|fc| fc.count.code > 0 && has_comments(fc.lang)) {
+        checked += 1;
+        let total = by_lang.entry(fc.lang).or_default();
+        total.files += 1;
+        total.count.merge(&fc.count);
+        if ratio(&fc.count) < min_ratio {
+            files.push(fc);
+        }
+    }
+    let mut langs: Vec<(&Lang, &LangTotal)> = by_lang.iter().filter(|&(_, t)| ratio(&t.count) < min_ratio).collect();
+    langs.sort_by(|a, b| ratio(&a.1.count).total_cmp(&ratio(&b.1.count)).then(lang_name(a.0).cmp(lang_name(b.0))));
+
+    files.sort_by(|a, b| ratio(&a.count).total_cmp(&ratio(&b.count)).then(b.count.code.cmp(&a.count.code)));
+    let under = files.len();
+
+    println!("{}", linesep);
+    println!(" {0: <37} {1: >8} {2: >10} {3: >10} {4: >8}",
+             "Language",
+             "Files",
+             "Comment",
+             "Code",
+             "Ratio");
+    println!("{}", linesep);
+    for &(lang, total) in &langs {
+        println!(" {0: <37} {1: >8} {2: >10} {3: >10} {4: >7.1}%",
+                 lang_name(lang),
+                 total.files,
+                 total.count.comment,
+                 total.count.code,
+                 100.0 * ratio(&total.count));
+    }
+    if !langs.is_empty() {
+        println!("{}", linesep);
+    }
+    let mut hidden = 0;
+    for fc in files {
+        if !show_sensitive && is_sensitive(&fc.path) {
+            hidden += 1;
+            continue;
+        }
+        println!("|{0: <37} {1: >8} {2: >10} {3: >10} {4: >7.1}%",
+                 last_n_chars(&fc.path, 37),
+                 "",
+                 fc.count.comment,
+                 fc.count.code,
+                 100.0 * ratio(&fc.count));
+    }
+    println!("{}", linesep);
+    println!(" {} of {} files have less than {:.1}% comments", under, checked, 100.0 * min_ratio);
+    print_hidden_note(hidden);
+    under
+}
+
+fn has_comments(lang: Lang) -> bool {
+    let (singles, multis) = counter_config_for_lang(lang);
+    !singles.is_empty() || !multis.is_empty()
+}
+
+// One row of totals per rev, then code lines per language with a column per rev so you can
+// see where the revs diverge.
+#[cfg(feature = "git")]
+pub fn print_rev_comparison(revs: &[RevCount]) {
+    let linesep = str_repeat("-", 80);
+
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
+             "Branch",
+             "Files",
+             "Lines",
+             "Blank",
+             "Comment",
+             "Code");
+    println!("{}", linesep);
+    for rev in revs {
+        let mut total = LangTotal::default();
+        for lang_total in rev.by_lang.values() {
+            total.files += lang_total.files;
+            total.count.merge(&lang_total.count);
+        }
+        println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
+                 last_n_chars(&rev.rev, 17),
+                 total.files,
+                 total.count.lines,
+                 total.count.blank,
+                 total.count.comment,
+                 total.count.code);
+    }
+    println!("{}", linesep);
+
+    let mut langs: Vec<&Lang> = revs.iter().flat_map(|rev| rev.by_lang.keys()).collect();
+    langs.sort_by_key(|lang| lang_name(lang));
+    langs.dedup();
+    let code = |rev: &RevCount, lang: &Lang| rev.by_lang.get(lang).map_or(0, |t| t.count.code);
+    langs.sort_by_key(|lang| Reverse(revs.iter().map(|rev| code(rev, lang)).max()));
+
+    let linesep = str_repeat("-", 18 + 13 * revs.len());
+    println!();
+    println!("{}", linesep);
+    print!(" {0: <17}", "Code");
+    for rev in revs {
+        print!(" {0: >12}", last_n_chars(&rev.rev, 12));
+    }
+    println!();
+    println!("{}", linesep);
+    for lang in langs {
+        print!(" {0: <17}", lang_name(lang));
+        for rev in revs {
+            print!(" {0: >12}", code(rev, lang));
+        }
+        println!();
+    }
+    println!("{}", linesep);
+}
+
+#[cfg(feature = "git")]
+pub fn print_between(from: &str, to: &str, deltas: &HashMap<Lang, LangDelta>) {
+    let linesep = str_repeat("-", 80);
+    let row = |name: &dyn std::fmt::Display, d: &LangDelta| {
+        println!(" {0: <17} {1: >7} {2: >7} {3: >8} {4: >11} {5: >11} {6: >11}",
+                 name,
+                 d.added,
+                 d.removed,
+                 d.modified,
+                 d.before.code,
+                 d.after.code,
+                 format!("{:+}", i64::from(d.after.code) - i64::from(d.before.code)));
+    };
+
+    println!("Changes from {} to {}", from, to);
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >7} {2: >7} {3: >8} {4: >11} {5: >11} {6: >11}",
+             "Language",
+             "Added",
+             "Removed",
+             "Modified",
+             "Code before",
+             "Code after",
+             "Change");
+    println!("{}", linesep);
+
+    let mut langs: Vec<(&Lang, &LangDelta)> = deltas.iter().collect();
+    langs.sort_by_key(|&(lang, d)| (Reverse((i64::from(d.after.code) - i64::from(d.before.code)).abs()),
+                                    lang_name(lang)));
+    let mut total = LangDelta::default();
+    for (lang, d) in langs {
+        // Languages nobody touched are just noise in release notes
+        if d.added + d.removed + d.modified > 0 {
+            row(&lang_name(lang), d);
+        }
+        total.added    += d.added;
+        total.removed  += d.removed;
+        total.modified += d.modified;
+        total.before.merge(&d.before);
+        total.after.merge(&d.after);
+    }
+
+    println!("{}", linesep);
+    row(&"Total", &total);
+    println!("{}", linesep);
+}
+
+#[cfg(feature = "git")]
+pub fn print_age(ages: &HashMap<Lang, AgeBuckets>, untracked: usize) {
+    let linesep = str_repeat("-", 80);
+    let cell = |n: u32, total: u32| {
+        format!("{} ({:.0}%)", n, if total == 0 { 0.0 } else { 100.0 * f64::from(n) / f64::from(total) })
+    };
+    let row = |name: &dyn std::fmt::Display, a: &AgeBuckets| {
+        let total = a.total();
+        println!(" {0: <17} {1: >12} {2: >15} {3: >15} {4: >15}",
+                 name,
+                 total,
+                 cell(a.buckets[0], total),
+                 cell(a.buckets[1], total),
+                 cell(a.buckets[2], total));
+    };
+
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >12} {2: >15} {3: >15} {4: >15}",
+             "Language",
+             "Code",
+             "< 6 months",
+             "6-24 months",
+             "> 24 months");
+    println!("{}", linesep);
+
+    let mut langs: Vec<(&Lang, &AgeBuckets)> = ages.iter().collect();
+    langs.sort_by_key(|&(lang, a)| (Reverse(a.total()), lang_name(lang)));
+    let mut total = AgeBuckets::default();
+    for (lang, a) in langs {
+        row(&lang_name(lang), a);
+        total.merge(a);
+    }
+
+    println!("{}", linesep);
+    row(&"Total", &total);
+    println!("{}", linesep);
+    if untracked > 0 {
+        println!(" {} files aren't tracked by git and were left out", untracked);
+    }
+}
+
+// A bus factor of 1 means one person owns more than half of that code.
+#[cfg(feature = "git")]
+pub fn print_bus_factor(group_name: &str, groups: &HashMap<String, Authorship>, untracked: usize) {
+    let linesep = str_repeat("-", 80);
+
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >10} {2: >8} {3: >10}  Top author",
+             group_name,
+             "Code",
+             "Authors",
+             "Bus factor");
+    println!("{}", linesep);
+
+    let mut sorted: Vec<(&String, &Authorship)> = groups.iter().filter(|&(_, a)| a.total() > 0).collect();
+    sorted.sort_by_key(|&(name, a)| (Reverse(a.total()), name.clone()));
+    let mut total = Authorship::default();
+    for (name, a) in sorted {
+        print_authorship_row(name, a);
+        total.merge(a);
+    }
+
+    println!("{}", linesep);
+    print_authorship_row("Total", &total);
+    println!("{}", linesep);
+    if untracked > 0 {
+        println!(" {} files aren't tracked by git and were left out", untracked);
+    }
+}
+
+#[cfg(feature = "git")]
+fn print_authorship_row(name: &str, a: &Authorship) {
+    let top = match a.ranked().first() {
+        Some(&(author, n)) => format!("{} ({:.0}%)",
+                                      first_n_chars(author, 20),
+                                      100.0 * f64::from(n) / f64::from(a.total())),
+        None => String::new(),
+    };
+    println!(" {0: <17} {1: >10} {2: >8} {3: >10}  {4}",
+             last_n_chars(name, 17),
+             a.total(),
+             a.lines.len(),
+             a.bus_factor(),
+             top);
+}
+
+#[cfg(feature = "git")]
+fn first_n_chars(s: &str, n: usize) -> String {
+    s.chars().take(n).collect()
+}
+
+// Churn per language, then the files with the most churn.  Churn/code is how many times
+// over the file has been rewritten, which is what makes a hotspot.
+#[cfg(feature = "git")]
+pub fn print_churn(files: &[FileChurn], all_files: bool, show_sensitive: bool) {
+    let linesep = str_repeat("-", 80);
+
+    let mut by_lang: HashMap<Lang, (u32, u32, Churn)> = HashMap::new();
+    for f in files {
+        let entry = by_lang.entry(f.lang).or_insert((0, 0, Churn::default()));
+        entry.0 += 1;
+        entry.1 += f.count.code;
+        entry.2.merge(&f.churn);
+    }
+    let mut langs: Vec<(&Lang, &(u32, u32, Churn))> = by_lang.iter().collect();
+    langs.sort_by_key(|&(lang, (_, _, churn))| (Reverse(churn.total()), lang_name(lang)));
+
+    println!("{}", linesep);
+    println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
+             "Language",
+             "Files",
+             "Code",
+             "Added",
+             "Deleted",
+             "Churn");
+    println!("{}", linesep);
+    for (lang, &(nfiles, code, ref churn)) in langs {
+        println!(" {0: <17} {1: >8} {2: >12} {3: >12} {4: >12} {5: >12}",
+                 lang_name(lang),
+                 nfiles,
+                 code,
+                 churn.added,
+                 churn.deleted,
+                 churn.total());
+    }
+    println!("{}", linesep);
+
+    let mut hot: Vec<&FileChurn> = files.iter().filter(|f| f.churn.total() > 0).collect();
+    let before = hot.len();
+    hot.retain(|f| show_sensitive || !is_sensitive(&f.path));
+    let hidden = before - hot.len();
+    hot.sort_by_key(|f| (Reverse(f.churn.total()), f.path.clone()));
+    if !all_files {
+        hot.truncate(10);
+    }
+    if hot.is_empty() {
+        print_hidden_note(hidden);
+        return;
+    }
+
+    println!();
+    println!("{}", linesep);
+    println!(" {0: <37} {1: >9} {2: >9} {3: >9} {4: >10}",
+             "Hotspots",
+             "Code",
+             "Added",
+             "Deleted",
+             "Churn/Code");
+    println!("{}", linesep);
+    for f in hot {
+        println!(" {0: <37} {1: >9} {2: >9} {3: >9} {4: >10.1}",
+                 last_n_chars(&f.path, 37),
+                 f.count.code,
+                 f.churn.added,
+                 f.churn.deleted,
+                 f64::from(f.churn.total()) / f64::from(f.count.code.max(1)));
+    }
+    println!("{}", linesep);
+    print_hidden_note(hidden);
+}
+
+// TODO(cgag): i think this is in the stdlib
+fn last_n_chars(s: &str, n: usize) -> String {
+    if s.len() <= n {
+        return String::from(s);
+    }
+    s.chars().skip(s.len() - n).collect::<String>()
+}
+
+// TODO(cgag): i think this is in the stdlib
+fn str_repeat(s: &str, n: usize) -> String {
+    std::iter::repeat_n(s, n).collect::<Vec<_>>().join("")
+}
+
+// What --gen-corpus wrote, which a count of the same directory should match exactly.
+pub fn print_corpus(dir: &str, lang: Lang, total: &LangTotal) {
+    println!("Wrote {} {} files to {}, which should count as:", total.files, lang_name(&lang), dir);
+    let _ = print_totals_by_lang(&mut io::stdout(), &[(lang_name(&lang), total)], false, Colors::Off);
+}
+
+// loc conformance: a row per language and a column per kind of snippet, then what went
+// wrong with each that failed.  Returns whether everything not known to fail passed.
+pub fn print_conformance(cases: &[Case]) -> bool {
+    let mut names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+    names.sort();
+    names.dedup();
+    let mut langs: Vec<Lang> = cases.iter().map(|c| c.lang).collect();
+    langs.sort_by_key(|lang| lang_name(lang).to_string());
+    langs.dedup();
+    let widths: Vec<usize> = names.iter().map(|name| name.len().max(5)).collect();
+    let linesep = str_repeat("-", 18 + widths.iter().map(|w| w + 1).sum::<usize>());
+
+    println!("{}", linesep);
+    print!(" {}", pad(t(Text::Language), 17, false));
+    for (name, &w) in names.iter().zip(&widths) {
+        print!(" {:>w$}", name, w = w);
+    }
+    println!();
+    println!("{}", linesep);
+    for lang in &langs {
+        print!(" {: <17}", lang_name(lang));
+        for (name, &w) in names.iter().zip(&widths) {
+            let cell = match cases.iter().find(|c| c.lang == *lang && c.name == *name) {
+                None                              => "",
+                Some(c) if c.passed() && c.known  => "fixed",
+                Some(c) if c.passed()             => "ok",
+                Some(c) if c.known                => "known",
+                Some(_)                           => "FAIL",
+            };
+            print!(" {:>w$}", cell, w = w);
+        }
+        println!();
+    }
+    println!("{}", linesep);
+
+    let counts = |c: &Count| format!("{} code, {} comment, {} blank", c.code, c.comment, c.blank);
+    for c in cases.iter().filter(|c| c.passed() == c.known) {
+        if c.known {
+            println!(" {}: passes now, take known off it in expected.toml", c.path);
+        } else {
+            println!(" {}: expected {}, got {}", c.path, counts(&c.expected), counts(&c.got));
+        }
+    }
+    let failed = cases.iter().filter(|c| !c.passed() && !c.known).count();
+    let known = cases.iter().filter(|c| !c.passed() && c.known).count();
+    println!(" {} passed, {} failed, {} known to fail", cases.iter().filter(|c| c.passed()).count(), failed, known);
+    failed == 0
+}
+
+// The Language ... Code headings the summary and --files tables share, and the --percent
+// ones after them.
+fn print_count_header(out: &mut dyn Write, percent: bool) -> io::Result<()> {
+    write!(out, " {}", pad(t(Text::Language), 17, false))?;
+    for col in columns() {
+        write!(out, " {}", pad(t(col.heading()), col.width(), true))?;
+    }
+    if percent {
+        write!(out, " {} {}",
+                    pad(&format!("%{}", t(Text::Lines)), 7, true),
+                    pad(&format!("%{}", t(Text::Code)), 7, true))?;
+    }
+    writeln!(out)
+}
+
+fn print_totals_by_lang(out: &mut dyn Write, totals_by_lang: &[(&str, &LangTotal)], percent: bool, colors: Colors) -> io::Result<()> {
+    let linesep = str_repeat("-", table_width(if percent { 16 } else { 0 }));
+    let mut totals = LangTotal::default();
+    for &(_, total) in totals_by_lang {
+        totals.files         += total.files;
+        totals.bytes         += total.bytes;
+        totals.max_line       = totals.max_line.max(total.max_line);
+        totals.count.code    += total.count.code;
+        totals.count.blank   += total.count.blank;
+        totals.count.comment += total.count.comment;
+        totals.count.lines   += total.count.lines;
+    }
+    let share = |n: u32, all: u32| if all == 0 { 0.0 } else { f64::from(n) / f64::from(all) };
+    // Each row's share of all the lines and all the code, and the end of the row
+    let shares = |out: &mut dyn Write, count: &Count, bold: bool| {
+        if percent {
+            write!(out, " {:>6.1}% {:>6.1}%",
+                        100.0 * share(count.lines, totals.count.lines),
+                        100.0 * share(count.code, totals.count.code))?;
+        }
+        writeln!(out, "{}", if bold { RESET } else { "" })
+    };
+    // The rows with the most code stand out, when there are enough rows for that to mean
+    // anything
+    let mut by_code: Vec<u32> = totals_by_lang.iter().map(|&(_, t)| t.count.code).collect();
+    by_code.sort_by_key(|&code| Reverse(code));
+    let biggest = if colors != Colors::Off && by_code.len() > BIGGEST { by_code[BIGGEST - 1].max(1) } else { u32::MAX };
+
+    writeln!(out, "{}", linesep)?;
+    print_count_header(out, percent)?;
+    writeln!(out, "{}", linesep)?;
+
+    for &(lang, total) in totals_by_lang {
+        let bold = total.count.code >= biggest;
+        let heat = if colors == Colors::Heat { Some(heat(share(total.count.code, totals.count.code))) } else { None };
+        write!(out, "{} {: <17}", if bold { BOLD } else { "" }, lang)?;
+        write_colored_cells(out, total, heat)?;
+        shares(out, &total.count, bold)?;
+    }
+
+    writeln!(out, "{}", linesep)?;
+    let bold = colors != Colors::Off;
+    write!(out, "{} {}", if bold { BOLD } else { "" }, pad(t(Text::Total), 17, false))?;
+    write_cells(out, &totals)?;
+    shares(out, &totals.count, bold)?;
+    writeln!(out, "{}", linesep)
+}
+
+// --color: how many of the biggest languages are in bold
+const BIGGEST: usize = 3;
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// --heat: red for a quarter or more of all the code, then yellow, green, and gray for
+// under 1%
+fn heat(share: f64) -> &'static str {
+    match share {
+        s if s >= 0.25 => "\x1b[31m",
+        s if s >= 0.10 => "\x1b[33m",
+        s if s >= 0.01 => "\x1b[32m",
+        _              => "\x1b[90m",
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json;
+
+use loc::*;
+
+use worker::FileCount;
+
+// The parts of a --format json report (see output::Report) it takes to draw it again
+#[derive(Deserialize)]
+struct Report {
+    languages: Vec<ReportLang>,
+    // Only there so a json file that merely has a "languages" key isn't taken for a report
+    #[allow(dead_code)]
+    total: serde_json::Value,
+    #[serde(default)]
+    hidden: usize,
+}
+
+#[derive(Deserialize)]
+struct ReportLang {
+    language: String,
+    renamed_from: Option<String>,
+    files: u32,
+    #[serde(flatten)]
+    count: ReportCount,
+    by_file: Option<Vec<ReportFile>>,
+}
+
+#[derive(Deserialize)]
+struct ReportFile {
+    path: String,
+    #[serde(flatten)]
+    count: ReportCount,
+}
+
+#[derive(Deserialize)]
+struct ReportCount {
+    lines: u32,
+    blank: u32,
+    comment: u32,
+    code: u32,
+}
+
+impl ReportCount {
+    fn to_count(&self) -> Count {
+        Count { lines: self.lines, blank: self.blank, comment: self.comment, code: self.code }
+    }
+}
+
+pub struct Reloaded {
+    pub filecounts: Vec<FileCount>,
+    // Whether the report had every language's files in it (saved with --files), rather
+    // than just the totals
+    pub listed: bool,
+    // Sensitive files the report left out, and so aren't in filecounts
+    pub hidden: usize,
+    // Languages the report called something else, and what, to call them that again
+    pub renames: Vec<(String, String)>,
+}
+
+// loc report.json --format md: the counts from an earlier --format json report, so it can be
+// printed in any other format without counting again.  Ok(None) when path isn't a report at
+// all, so that it's counted like any other file.
+pub fn load(path: &str) -> Result<Option<Reloaded>, String> {
+    if !path.ends_with(".json") || !Path::new(path).is_file() {
+        return Ok(None);
+    }
+    let report: Report = match fs::read(path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok()) {
+        Some(report) => report,
+        None => return Ok(None),
+    };
+    let mut reloaded = Reloaded { filecounts: vec![], listed: true, hidden: report.hidden, renames: vec![] };
+    for l in report.languages {
+        let name = l.renamed_from.as_ref().unwrap_or(&l.language);
+        let lang = match Lang::from_name(name) {
+            Some(lang) => lang,
+            None => return Err(format!("unknown language '{}'", name)),
+        };
+        if let Some(original) = l.renamed_from {
+            reloaded.renames.push((original, l.language));
+        }
+        match l.by_file {
+            Some(files) => reloaded.filecounts.extend(files.into_iter().map(|f| FileCount {
+                count: f.count.to_count(),
+                path: f.path,
+                lang,
+                meta: None,
+                files: 1,
+                bytes: 0,
+                max_line: 0,
+            })),
+            // Only the totals, so one pathless FileCount stands in for all of the
+            // language's files.  Fine for anything that only adds them up.
+            None => {
+                reloaded.listed = false;
+                reloaded.filecounts.push(FileCount {
+                    path: String::new(),
+                    lang,
+                    count: l.count.to_count(),
+                    meta: None,
+                    files: l.files,
+                    bytes: 0,
+                    max_line: 0,
+                });
+            }
+        }
+    }
+    Ok(Some(reloaded))
+}
@@ -0,0 +1,201 @@
+// The reports on a finished count, and everything else done with one: the table (or
+// whichever --format), --by-dir and the like, --append-history, --badge, and the notes
+// after the table.
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use clap::ArgMatches;
+
+use loc::*;
+
+use aggregate::{self, ByLang};
+use badge;
+use cocomo;
+use counter::Walked;
+use filter::{PathGroups, RegexGroups};
+use history;
+use i18n::Text;
+use notify;
+use options::{Options, Scan};
+use output;
+use sample;
+#[cfg(feature = "sqlite")]
+use sqlite;
+use walk;
+use worker::Counted;
+
+// What a count comes to
+pub struct Results {
+    pub by_lang: ByLang,
+    // Everything else the workers found along the way, their files being in by_lang now
+    pub counted: Counted,
+    pub walked: Walked,
+    // For --ambiguous
+    pub ambiguous: Vec<(String, Lang, Guess)>,
+}
+
+pub fn render(matches: &ArgMatches, opts: &Options, scan: &Scan, results: Results) {
+    let Results { by_lang, counted, walked, ambiguous } = results;
+    let roots = opts.counted_roots();
+    if let Some((sampled, all_files)) = walked.sampled {
+        let (by_lang, total) = sample::estimate(by_lang.all(), sampled, all_files);
+        output::print_estimates(&by_lang, &total, sampled, all_files);
+        return
+    }
+
+    if let Some(history) = matches.value_of("append-history") {
+        let total = by_lang.total();
+        if let Err(e) = history::append(history, total.files, &total.count) {
+            println!("Error: --append-history {}: {}", history, e);
+            std::process::exit(1);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        if let Some(db) = matches.value_of("sqlite") {
+            let targets = matches.value_of("image").map(|image| vec![image]).unwrap_or_else(|| opts.targets());
+            if let Err(e) = sqlite::export(db, &targets, &by_lang, opts.show_sensitive) {
+                println!("Error: --sqlite {}: {}", db, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(svg) = matches.value_of("badge") {
+        let (label, code) = match matches.value_of("badge-lang") {
+            Some(name) => {
+                let lang = match Lang::from_name(name) {
+                    Some(lang) => lang,
+                    None => {
+                        println!("Error: unknown language for --badge-lang: '{}'", name);
+                        std::process::exit(1);
+                    }
+                };
+                let code = by_lang.langs().iter().filter(|l| l.0 == lang).map(|l| l.1.count.code).sum();
+                (format!("{} code", output::lang_name(&lang)), code)
+            }
+            None => (String::from("lines of code"), by_lang.total().count.code),
+        };
+        if let Err(e) = badge::write(svg, &label, code) {
+            println!("Error: --badge {}: {}", svg, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(url) = matches.value_of("notify-webhook") {
+        let text = notify::message(&by_lang, opts.baseline.as_ref());
+        if let Err(e) = notify::post(url, &text) {
+            println!("Error: --notify-webhook: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let show_sensitive = opts.show_sensitive;
+    if let Some(ratio) = matches.value_of("min-comment-ratio") {
+        let min_ratio = match ratio.parse::<f64>() {
+            Ok(r) if (0.0..=1.0).contains(&r) => r,
+            _ => {
+                println!("Error: --min-comment-ratio should be between 0 and 1, got '{}'", ratio);
+                std::process::exit(1);
+            }
+        };
+        // Ignore any leading ./ on either side, since the walk adds one for "."
+        let clean = |p: &str| -> PathBuf {
+            Path::new(p).components().skip_while(|c| *c == Component::CurDir).collect()
+        };
+        let paths: Option<Vec<PathBuf>> = matches.values_of("comment-ratio-path").map(|paths| paths.map(clean).collect());
+        let checked = by_lang.all().filter(|fc| match paths {
+            Some(ref paths) => paths.iter().any(|p| clean(&fc.path).starts_with(p)),
+            None => true,
+        });
+        if output::print_comment_ratio(checked, min_ratio, show_sensitive) > 0 {
+            std::process::exit(1);
+        }
+        fail_on_unrecognized(&counted.unrecognized_source);
+        return
+    }
+
+    let code = by_lang.total().count.code;
+    if matches.is_present("by-dir") {
+        let depth = match matches.value_of("dir-depth").map(str::parse::<usize>) {
+            Some(Ok(depth)) if depth > 0 => depth,
+            Some(_) => {
+                println!("Error: --dir-depth takes how many directories down to go, 1 or more");
+                std::process::exit(1);
+            }
+            None => 1,
+        };
+        let by_dir = aggregate::by_key(by_lang.all(), |path| walk::dir_at_depth(path, &roots, depth));
+        output::print_by_dir(Text::Directory, &by_dir, &opts.sort);
+    } else if let Some(groups) = PathGroups::from_matches(matches) {
+        let by_group = aggregate::by_key(by_lang.all(), |path| groups.group(path, &roots).to_string());
+        output::print_by_dir(Text::Path, &by_group, &opts.sort);
+    } else if let Some(groups) = RegexGroups::from_matches(matches) {
+        let by_group = aggregate::by_key(by_lang.all(), |path| groups.group(path, &roots));
+        output::print_by_dir(Text::Group, &by_group, &opts.sort);
+    } else if matches.is_present("family") {
+        // Most likely a closed pipe, with nobody left to tell
+        if output::print_families(&mut io::stdout().lock(), &by_lang, &opts.sort).is_err() {
+            std::process::exit(1);
+        }
+    } else {
+        let counts = output::Counts {
+            by_lang,
+            by_file: opts.by_file || matches.is_present("ordered"),
+            show_sensitive,
+            others: &opts.others,
+            template: opts.template.as_ref(),
+            percent: opts.percent,
+            totals_only: opts.totals_only,
+            cocomo: opts.cocomo,
+        };
+        output::write_reports(&opts.outputs, &counts, opts.colors);
+    }
+    // Notes would just get in the way of anything reading the other formats, or a script
+    // after a single number
+    if !opts.table_only || opts.totals_only.is_some() {
+        fail_on_unrecognized(&counted.unrecognized_source);
+        return
+    }
+    if let Some(wage) = opts.cocomo {
+        output::print_cocomo(&cocomo::estimate(code, wage));
+    }
+    output::print_long_lines(&counted.long_lines, show_sensitive);
+    output::print_conflicted(&counted.conflicted, show_sensitive);
+    if matches.is_present("comment-lang") {
+        output::print_comment_langs(counted.comment_langs, show_sensitive);
+    }
+    if matches.is_present("dead-code") {
+        output::print_dead_code(&counted.dead_code);
+    }
+    if matches.is_present("ambiguous") {
+        output::print_ambiguous(ambiguous, show_sensitive);
+    }
+    if matches.is_present("skipped") {
+        let mut skipped = walk::census(&opts.targets(), &scan.walk_opts, &walked.paths);
+        skipped.unreadable = counted.unreadable;
+        skipped.marked_ignored = counted.marked_ignored;
+        skipped.lfs_pointers = counted.lfs_pointers.len();
+        skipped.duplicates = walked.duplicates;
+        output::print_skipped(&skipped, scan.walk_opts.owner.is_some());
+    }
+    fail_on_unrecognized(&counted.unrecognized_source);
+}
+
+// --strict-detection: a new language in the repo should fail CI rather than quietly go
+// uncounted.  On stderr, so it can't end up in a json report or the like.
+pub fn fail_on_unrecognized(files: &[String]) {
+    if files.is_empty() {
+        return;
+    }
+    let mut files = files.to_vec();
+    files.sort();
+    eprintln!("Error: --strict-detection: {} files look like source code in a language loc doesn't \
+               recognize:", files.len());
+    for file in &files {
+        eprintln!("  {}", file);
+    }
+    eprintln!("Map their extensions to a language in .loc.toml, or exclude them, to let this pass");
+    std::process::exit(1);
+}
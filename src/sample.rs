@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use loc::*;
+
+use worker::FileCount;
+
+// --sample: count a random subset of the files and scale the totals up.
+#[derive(Clone, Copy)]
+pub enum Sample {
+    Percent(f64),
+    Files(usize),
+    // --max-files N --on-limit sample: N files, but only if there are more than that
+    Over(usize),
+}
+
+impl FromStr for Sample {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Sample, ()> {
+        if let Some(percent) = s.strip_suffix('%') {
+            match percent.parse::<f64>() {
+                Ok(p) if p > 0.0 && p <= 100.0 => Ok(Sample::Percent(p / 100.0)),
+                _ => Err(()),
+            }
+        } else {
+            match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Sample::Files(n)),
+                _ => Err(()),
+            }
+        }
+    }
+}
+
+impl Sample {
+    // Whether it takes a sample of n files, rather than counting them all
+    pub fn applies(&self, n: usize) -> bool {
+        match *self {
+            Sample::Over(max) => n > max,
+            _ => true,
+        }
+    }
+
+}
+
+// Picks the sample as the walk hands over paths.  A number of files is reservoir sampled,
+// so only that many paths are ever held however big the tree is (which is the point of
+// --on-limit sample); a percentage can't know how many that is until the walk's done.
+pub struct Picker {
+    sample: Sample,
+    seen: usize,
+    kept: Vec<String>,
+    rng: XorShift,
+}
+
+impl Picker {
+    pub fn new(sample: Sample) -> Picker {
+        Picker { sample, seen: 0, kept: vec![], rng: XorShift::seeded() }
+    }
+
+    pub fn offer(&mut self, path: String) {
+        self.seen += 1;
+        match self.sample {
+            Sample::Percent(_) => self.kept.push(path),
+            Sample::Files(n) | Sample::Over(n) => {
+                if self.kept.len() < n {
+                    self.kept.push(path);
+                } else {
+                    let i = (self.rng.next() % self.seen as u64) as usize;
+                    if i < n {
+                        self.kept[i] = path;
+                    }
+                }
+            }
+        }
+    }
+
+    // The files to count, and how many there were to choose from
+    pub fn picked(mut self) -> (Vec<String>, usize) {
+        if let Sample::Percent(p) = self.sample {
+            // Partial Fisher-Yates
+            let paths = &mut self.kept;
+            let k = ((paths.len() as f64 * p).ceil() as usize).max(1).min(paths.len());
+            for i in 0..k {
+                let j = i + (self.rng.next() % (paths.len() - i) as u64) as usize;
+                paths.swap(i, j);
+            }
+            paths.truncate(k);
+        }
+        (self.kept, self.seen)
+    }
+}
+
+// An estimated total and the half-width of its 95% confidence interval.
+#[derive(Default, Clone, Copy)]
+pub struct Estimate {
+    pub total: f64,
+    pub margin: f64,
+}
+
+pub struct LangEstimate {
+    pub files: Estimate,
+    pub lines: Estimate,
+    pub code: Estimate,
+}
+
+// Scales the counts from sampled files up to all n files.  Every sampled file contributes
+// to every language (as zero, if it's in another language or wasn't counted at all), which
+// makes each total a plain sample mean times n, with the usual finite population
+// correction on the error.
+pub fn estimate<I: IntoIterator<Item = FileCount>>(filecounts: I, sampled: usize, n: usize) -> (HashMap<Lang, LangEstimate>, LangEstimate) {
+    let mut by_lang: HashMap<Lang, [Moments; 3]> = HashMap::new();
+    let mut all = [Moments::default(); 3];
+    for fc in filecounts {
+        let values = [1.0, f64::from(fc.count.lines), f64::from(fc.count.code)];
+        let lang = by_lang.entry(fc.lang).or_default();
+        for i in 0..3 {
+            lang[i].add(values[i]);
+            all[i].add(values[i]);
+        }
+    }
+
+    let scale = |m: &[Moments; 3]| LangEstimate {
+        files: m[0].estimate(sampled, n),
+        lines: m[1].estimate(sampled, n),
+        code: m[2].estimate(sampled, n),
+    };
+    (by_lang.iter().map(|(lang, m)| (*lang, scale(m))).collect(), scale(&all))
+}
+
+#[derive(Default, Clone, Copy)]
+struct Moments {
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl Moments {
+    fn add(&mut self, x: f64) {
+        self.sum += x;
+        self.sum_sq += x * x;
+    }
+
+    fn estimate(&self, k: usize, n: usize) -> Estimate {
+        let (k, n) = (k as f64, n as f64);
+        let mean = self.sum / k;
+        if k < 2.0 {
+            return Estimate { total: mean * n, margin: f64::INFINITY };
+        }
+        let variance = ((self.sum_sq - k * mean * mean) / (k - 1.0)).max(0.0);
+        let std_err = n * ((1.0 - k / n) * variance / k).sqrt();
+        Estimate {
+            total: mean * n,
+            margin: 1.96 * std_err,
+        }
+    }
+}
+
+// Good enough for picking files, and saves a dependency.
+struct XorShift(u64);
+
+impl XorShift {
+    fn seeded() -> XorShift {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        XorShift(nanos | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -0,0 +1,95 @@
+// What a file we don't know the language of is, from its first few kilobytes, the way
+// file(1) would put it: text, a binary format with a magic number we recognize, or just
+// data.  Only the start of the file matters, so callers needn't read the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sniffed {
+    Text,
+    // The mime type of the format
+    Binary(&'static str),
+    Data,
+}
+
+// How much of the file sniff looks at
+pub const SNIFF_LEN: usize = 8192;
+
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x7fELF",              "application/x-executable"),
+    (b"MZ",                   "application/vnd.microsoft.portable-executable"),
+    (b"\xcf\xfa\xed\xfe",     "application/x-mach-binary"),
+    (b"\xce\xfa\xed\xfe",     "application/x-mach-binary"),
+    (b"\xca\xfe\xba\xbe",     "application/java-vm"),
+    (b"\0asm",                "application/wasm"),
+    (b"PK\x03\x04",           "application/zip"),
+    (b"\x1f\x8b",             "application/gzip"),
+    (b"BZh",                  "application/x-bzip2"),
+    (b"\xfd7zXZ\0",           "application/x-xz"),
+    (b"\x28\xb5\x2f\xfd",     "application/zstd"),
+    (b"7z\xbc\xaf\x27\x1c",   "application/x-7z-compressed"),
+    (b"%PDF-",                "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n",    "image/png"),
+    (b"\xff\xd8\xff",         "image/jpeg"),
+    (b"GIF87a",               "image/gif"),
+    (b"GIF89a",               "image/gif"),
+    (b"RIFF",                 "application/x-riff"),
+    (b"OggS",                 "audio/ogg"),
+    (b"ID3",                  "audio/mpeg"),
+    (b"wOFF",                 "font/woff"),
+    (b"wOF2",                 "font/woff2"),
+    (b"SQLite format 3\0",    "application/vnd.sqlite3"),
+    (b"\xd0\xcf\x11\xe0",     "application/x-ole-storage"),
+];
+
+pub fn sniff(bytes: &[u8]) -> Sniffed {
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if let Some(&(_, mime)) = MAGIC.iter().find(|&&(magic, _)| head.starts_with(magic)) {
+        return Sniffed::Binary(mime);
+    }
+    // utf-16 has NULs all through it, but announces itself
+    if head.starts_with(b"\xff\xfe") || head.starts_with(b"\xfe\xff") {
+        return Sniffed::Text;
+    }
+    if head.contains(&0) {
+        return Sniffed::Data;
+    }
+    // Text in any 8-bit encoding (utf8 included) has hardly any control characters
+    let controls = head.iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\n' | b'\r' | b'\t' | 0x0c | 0x1b))
+        .count();
+    if controls * 100 > head.len() {
+        Sniffed::Data
+    } else {
+        Sniffed::Text
+    }
+}
+
+// Extensions of text files that aren't anybody's source code
+const NOT_SOURCE: &[&str] = &[
+    "txt", "text", "log", "out", "csv", "tsv", "lock", "sum", "diff", "patch", "pem", "crt",
+    "key", "pub", "asc", "sig", "gpg", "rst", "adoc", "org", "license", "bak", "orig", "rej",
+    "svg", "ics", "vcf", "srt", "po", "pot", "map",
+];
+
+// Whether an unrecognized file looks like it's source code in some language we don't
+// know, going by its first few kilobytes: text, and either a shebang or an extension that
+// isn't a known kind of prose or data, with enough lines ending the way code does.
+pub fn looks_like_source(path: &str, bytes: &[u8]) -> bool {
+    if sniff(bytes) != Sniffed::Text {
+        return false;
+    }
+    if bytes.starts_with(b"#!") {
+        return true;
+    }
+    let ext = match path.rsplit('/').next().and_then(|name| name.rsplit_once('.')) {
+        Some((stem, ext)) if !stem.is_empty() => ext.to_lowercase(),
+        _ => return false,
+    };
+    if NOT_SOURCE.contains(&ext.as_str()) {
+        return false;
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(SNIFF_LEN)]);
+    let lines: Vec<&str> = head.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let code_like = lines.iter()
+        .filter(|l| l.ends_with([';', '{', '}', '(', ')', ',', ':', '=']))
+        .count();
+    lines.len() >= 3 && code_like * 10 >= lines.len() * 3
+}
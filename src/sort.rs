@@ -0,0 +1,137 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use edit_distance::edit_distance as distance;
+
+use loc::*;
+
+use output::lang_name;
+use worker::FileCount;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Code,
+    Comment,
+    Blank,
+    Lines,
+    // With --files these only order the languages, since every file of a language has the
+    // same one and counts as one file
+    Language,
+    Files,
+}
+
+impl FromStr for Key {
+    /// A Some variant indicates a suggested value -- the given value was close (perhaps
+    /// because of a typo) to a valid value. None indicates that the given value was not
+    /// close to a correct value.
+    type Err = Option<String>;
+    fn from_str(s: &str) -> Result<Key, Self::Err> {
+        match s {
+            "blank"    | "Blank"    => Ok(Key::Blank),
+            "code"     | "Code"     => Ok(Key::Code),
+            "comment"  | "Comment"  => Ok(Key::Comment),
+            "lines"    | "Lines"    => Ok(Key::Lines),
+            "language" | "Language" => Ok(Key::Language),
+            "files"    | "Files"    => Ok(Key::Files),
+            s if distance(&s.to_lowercase(), "blank")    <= 2  => Err(Some("Blank".into())),
+            s if distance(&s.to_lowercase(), "code")     <= 2  => Err(Some("Code".into())),
+            s if distance(&s.to_lowercase(), "comment")  <= 2  => Err(Some("Comment".into())),
+            s if distance(&s.to_lowercase(), "lines")    <= 2  => Err(Some("Lines".into())),
+            s if distance(&s.to_lowercase(), "language") <= 2  => Err(Some("Language".into())),
+            s if distance(&s.to_lowercase(), "files")    <= 2  => Err(Some("Files".into())),
+            _ => Err(None)
+        }
+    }
+}
+
+// --sort: one or more keys, each breaking the ties the ones before it leave, like
+// "language:asc,code:desc".  Counts go biggest first and languages A to Z unless a key
+// says otherwise.
+#[derive(Clone)]
+pub struct Sort {
+    // Each key, and whether it's descending
+    keys: Vec<(Key, bool)>,
+}
+
+impl Default for Sort {
+    // By lines of code
+    fn default() -> Sort {
+        Sort { keys: vec![(Key::Code, true)] }
+    }
+}
+
+impl FromStr for Sort {
+    /// The key that didn't parse, and a suggestion for it as with Key.
+    type Err = (String, Option<String>);
+    fn from_str(s: &str) -> Result<Sort, Self::Err> {
+        let mut keys = vec![];
+        for part in s.split(',').map(str::trim) {
+            let (name, direction) = match part.find(':') {
+                Some(i) => (&part[..i], Some(&part[i + 1..])),
+                None => (part, None),
+            };
+            let key = Key::from_str(name).map_err(|suggestion| (part.to_string(), suggestion))?;
+            let descending = match direction {
+                None => key != Key::Language,
+                Some("desc") => true,
+                Some("asc") => false,
+                Some(_) => return Err((part.to_string(), None)),
+            };
+            keys.push((key, descending));
+        }
+        Ok(Sort { keys })
+    }
+}
+
+// Every report sorts through these, so the table and any machine-readable output list
+// things in the same order.  Ties go by name, so the order doesn't change from run to run.
+impl Sort {
+    pub fn langs(&self, totals: &mut [(&Lang, &LangTotal)]) {
+        totals.sort_by(|&(l1, t1), &(l2, t2)| self.compare((lang_name(l1), t1), (lang_name(l2), t2)));
+    }
+
+    // For anything else with a name and a total: directories, --family rows...
+    pub fn rows<T, N, F>(&self, rows: &mut [T], name: N, total: F)
+        where N: Fn(&T) -> &str,
+              F: Fn(&T) -> &LangTotal
+    {
+        rows.sort_by(|a, b| self.compare((name(a), total(a)), (name(b), total(b))));
+    }
+
+    pub fn file_order(&self, a: &FileCount, b: &FileCount) -> Ordering {
+        self.keys.iter()
+            .map(|&(key, descending)| {
+                let order = match (count_key(key, &a.count), count_key(key, &b.count)) {
+                    (Some(x), Some(y)) => x.cmp(&y),
+                    _ => Ordering::Equal,
+                };
+                if descending { order.reverse() } else { order }
+            })
+            .find(|&order| order != Ordering::Equal)
+            .unwrap_or_else(|| a.path.cmp(&b.path))
+    }
+
+    fn compare(&self, (name1, t1): (&str, &LangTotal), (name2, t2): (&str, &LangTotal)) -> Ordering {
+        self.keys.iter()
+            .map(|&(key, descending)| {
+                let order = match key {
+                    Key::Language => name1.cmp(name2),
+                    Key::Files    => t1.files.cmp(&t2.files),
+                    key           => count_key(key, &t1.count).cmp(&count_key(key, &t2.count)),
+                };
+                if descending { order.reverse() } else { order }
+            })
+            .find(|&order| order != Ordering::Equal)
+            .unwrap_or_else(|| name1.cmp(name2))
+    }
+}
+
+fn count_key(key: Key, count: &Count) -> Option<u32> {
+    match key {
+        Key::Code    => Some(count.code),
+        Key::Comment => Some(count.comment),
+        Key::Blank   => Some(count.blank),
+        Key::Lines   => Some(count.lines),
+        Key::Language | Key::Files => None,
+    }
+}
@@ -0,0 +1,70 @@
+use rusqlite::{params, Connection, Result};
+
+use aggregate::ByLang;
+use filter::is_sensitive;
+use history;
+use output::lang_name;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id      INTEGER PRIMARY KEY,
+    time    TEXT NOT NULL,
+    targets TEXT NOT NULL,
+    files   INTEGER NOT NULL,
+    lines   INTEGER NOT NULL,
+    blank   INTEGER NOT NULL,
+    comment INTEGER NOT NULL,
+    code    INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS languages (
+    run_id   INTEGER NOT NULL REFERENCES runs(id),
+    language TEXT NOT NULL,
+    files    INTEGER NOT NULL,
+    lines    INTEGER NOT NULL,
+    blank    INTEGER NOT NULL,
+    comment  INTEGER NOT NULL,
+    code     INTEGER NOT NULL,
+    PRIMARY KEY (run_id, language)
+);
+CREATE TABLE IF NOT EXISTS files (
+    run_id   INTEGER NOT NULL REFERENCES runs(id),
+    path     TEXT NOT NULL,
+    language TEXT NOT NULL,
+    lines    INTEGER NOT NULL,
+    blank    INTEGER NOT NULL,
+    comment  INTEGER NOT NULL,
+    code     INTEGER NOT NULL,
+    PRIMARY KEY (run_id, path)
+);
+CREATE INDEX IF NOT EXISTS files_by_path ON files (path);
+";
+
+// --sqlite: adds this run, its per-language totals and every file to the database at path,
+// creating the tables the first time.  Runs pile up, so the same file can be followed
+// from run to run by joining on path.  Returns the new run's id.
+pub fn export(path: &str, targets: &[&str], by_lang: &ByLang, show_sensitive: bool) -> Result<i64> {
+    let mut db = Connection::open(path)?;
+    db.execute_batch(SCHEMA)?;
+
+    let total = by_lang.total();
+    let tx = db.transaction()?;
+    tx.execute("INSERT INTO runs (time, targets, files, lines, blank, comment, code) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+               params![history::now(), targets.join(" "), total.files,
+                       total.count.lines, total.count.blank, total.count.comment, total.count.code])?;
+    let run = tx.last_insert_rowid();
+    {
+        let mut insert = tx.prepare("INSERT INTO languages VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        for (lang, lang_total) in by_lang.langs() {
+            let c = &lang_total.count;
+            insert.execute(params![run, lang_name(lang), lang_total.files, c.lines, c.blank, c.comment, c.code])?;
+        }
+        // Sensitive files are still in the totals, like everywhere else
+        let mut insert = tx.prepare("INSERT OR REPLACE INTO files VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        for fc in by_lang.all().filter(|fc| show_sensitive || !is_sensitive(&fc.path)) {
+            let c = &fc.count;
+            insert.execute(params![run, fc.path, lang_name(&fc.lang), c.lines, c.blank, c.comment, c.code])?;
+        }
+    }
+    tx.commit()?;
+    Ok(run)
+}
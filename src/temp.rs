@@ -0,0 +1,97 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+#[cfg(feature = "image")]
+use std::fs::DirBuilder;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, Once};
+
+// Scratch space, like --image's unpacked filesystem or --max-memory's spilled counts.  Each
+// gets a random name in the temp dir, is created fresh (never opening something already
+// there, like a symlink somebody planted), is only readable by us, and is removed at exit.
+// At exit rather than on drop, since std::process::exit, which loc errors out with all
+// over, skips destructors.
+
+static CREATED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static AT_EXIT: Once = Once::new();
+
+extern "C" {
+    fn atexit(f: extern "C" fn()) -> i32;
+}
+
+extern "C" fn remove_created() {
+    if let Ok(mut created) = CREATED.lock() {
+        for path in created.drain(..) {
+            let _ = fs::remove_dir_all(&path).or_else(|_| fs::remove_file(&path));
+        }
+    }
+}
+
+fn created(path: PathBuf) -> PathBuf {
+    AT_EXIT.call_once(|| unsafe {
+        atexit(remove_created);
+    });
+    CREATED.lock().unwrap().push(path.clone());
+    path
+}
+
+fn random_path(prefix: &str) -> PathBuf {
+    // A fresh RandomState is keyed randomly, so this hashes to something new every time
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    env::temp_dir().join(format!("{}-{:016x}", prefix, hasher.finish()))
+}
+
+#[cfg(feature = "image")]
+pub fn dir(prefix: &str) -> io::Result<PathBuf> {
+    loop {
+        let path = random_path(prefix);
+        match private_dir().create(&path) {
+            Ok(()) => return Ok(created(path)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Open for reading and writing both
+pub fn file(prefix: &str) -> io::Result<(PathBuf, File)> {
+    loop {
+        let path = random_path(prefix);
+        match private_file().open(&path) {
+            Ok(file) => return Ok((created(path), file)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(all(feature = "image", unix))]
+fn private_dir() -> DirBuilder {
+    use std::os::unix::fs::DirBuilderExt;
+    let mut builder = DirBuilder::new();
+    builder.mode(0o700);
+    builder
+}
+
+#[cfg(all(feature = "image", not(unix)))]
+fn private_dir() -> DirBuilder {
+    DirBuilder::new()
+}
+
+#[cfg(unix)]
+fn private_file() -> OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true).mode(0o600);
+    options
+}
+
+#[cfg(not(unix))]
+fn private_file() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    options
+}
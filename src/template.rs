@@ -0,0 +1,118 @@
+use loc::*;
+
+// --template: one line per language (or per file, with --files) in whatever shape the user
+// wants, "{language}: {code}" and the like, for when the table is almost what a script
+// needs but not quite.
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Language,
+    Path,
+    Files,
+    Lines,
+    Blank,
+    Comment,
+    Code,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("language", Field::Language),
+    ("path",     Field::Path),
+    ("files",    Field::Files),
+    ("lines",    Field::Lines),
+    ("blank",    Field::Blank),
+    ("comment",  Field::Comment),
+    ("code",     Field::Code),
+];
+
+// What a row is filled in from.  path is empty on language rows.
+pub struct Row<'a> {
+    pub language: &'a str,
+    pub path: &'a str,
+    pub files: u32,
+    pub count: &'a Count,
+}
+
+impl Template {
+    // {{ and }} for literal braces, and \n and \t since they're a pain to pass in a shell
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed {{{}", name)),
+                        }
+                    }
+                    let field = match FIELDS.iter().find(|&&(n, _)| n == name) {
+                        Some(&(_, field)) => field,
+                        None => {
+                            let known: Vec<String> = FIELDS.iter().map(|&(n, _)| format!("{{{}}}", n)).collect();
+                            return Err(format!("unknown placeholder {{{}}}, it takes {}", name, known.join(", ")));
+                        }
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(text.split_off(0)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err(String::from("unmatched }, write }} for a literal one")),
+                '\\' => match chars.peek() {
+                    Some('n') => { chars.next(); text.push('\n'); }
+                    Some('t') => { chars.next(); text.push('\t'); }
+                    _ => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template { pieces })
+    }
+
+    pub fn uses(&self, field: Field) -> bool {
+        self.pieces.iter().any(|p| matches!(p, Piece::Field(f) if *f == field))
+    }
+
+    pub fn render(&self, row: &Row) -> String {
+        let mut line = String::new();
+        for piece in &self.pieces {
+            match *piece {
+                Piece::Text(ref text) => line.push_str(text),
+                Piece::Field(field) => line.push_str(&match field {
+                    Field::Language => row.language.to_string(),
+                    Field::Path     => row.path.to_string(),
+                    Field::Files    => row.files.to_string(),
+                    Field::Lines    => row.count.lines.to_string(),
+                    Field::Blank    => row.count.blank.to_string(),
+                    Field::Comment  => row.count.comment.to_string(),
+                    Field::Code     => row.count.code.to_string(),
+                }),
+            }
+        }
+        line
+    }
+}
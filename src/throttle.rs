@@ -0,0 +1,90 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// --throttle: caps how fast the workers read, shared between all of them.  Each read is
+// paid for after the fact, by sleeping until the average since the start is back under
+// the limit, so a big file is a burst followed by a long pause.
+pub struct Throttle {
+    bytes_per_sec: u64,
+    // when we started, and how much has been read since
+    read: Mutex<Option<(Instant, u64)>>,
+}
+
+impl Throttle {
+    pub fn unlimited() -> Throttle {
+        Throttle { bytes_per_sec: 0, read: Mutex::new(None) }
+    }
+
+    pub fn new(bytes_per_sec: u64) -> Throttle {
+        Throttle { bytes_per_sec, read: Mutex::new(None) }
+    }
+
+    pub fn read(&self, bytes: usize) {
+        if self.bytes_per_sec == 0 {
+            return;
+        }
+        let wait = {
+            let mut read = self.read.lock().unwrap();
+            let (started, total) = read.get_or_insert_with(|| (Instant::now(), 0));
+            *total += bytes as u64;
+            let due = Duration::from_secs_f64(*total as f64 / self.bytes_per_sec as f64);
+            due.checked_sub(started.elapsed())
+        };
+        if let Some(wait) = wait {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// "50MB/s", "512k", "1GiB/s" or a plain number of bytes, per second.
+pub fn parse_rate(rate: &str) -> Option<u64> {
+    let rate = rate.trim();
+    parse_size(rate.strip_suffix("/s").unwrap_or(rate))
+}
+
+// "2G", "512MB", "1GiB" or a plain number of bytes.  K, M and G are powers of 1000, Ki, Mi
+// and Gi of 1024.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let size = size.strip_suffix(['B', 'b']).unwrap_or(size);
+    let digits = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, unit) = size.split_at(digits);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        ""   => 1.0,
+        "k"  => 1e3,
+        "m"  => 1e6,
+        "g"  => 1e9,
+        "ki" => 1024.0,
+        "mi" => 1024.0 * 1024.0,
+        "gi" => 1024.0 * 1024.0 * 1024.0,
+        _    => return None,
+    };
+    let size = (number * multiplier) as u64;
+    if size > 0 { Some(size) } else { None }
+}
+
+// --nice: the lowest cpu priority, and on Linux the idle io class, so the disk only gets
+// to us when nothing else wants it.  Threads inherit both, so this has to happen before
+// the workers start.
+#[cfg(unix)]
+pub fn be_nice() {
+    unsafe {
+        if libc::setpriority(libc::PRIO_PROCESS, 0, 19) != 0 {
+            eprintln!("Warning: --nice: couldn't lower cpu priority: {}", std::io::Error::last_os_error());
+        }
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+        // ioprio_set(IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)
+        if libc::syscall(libc::SYS_ioprio_set, 1, 0, 3 << 13) != 0 {
+            eprintln!("Warning: --nice: couldn't lower io priority: {}", std::io::Error::last_os_error());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn be_nice() {
+    eprintln!("Warning: --nice isn't supported on this platform, pass --throttle to limit reads");
+}
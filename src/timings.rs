@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+use output::human_size;
+use worker::WorkerStats;
+
+// --timings: wall clock time spent in each phase of a run, printed to stderr when it's
+// dropped, so every early return out of main still reports.  With the tracing feature
+// each phase is also a tracing span.  After those, what each worker got through, since
+// one giant file can keep a worker busy long after the others are done.
+pub struct Timings {
+    enabled: bool,
+    phases: Vec<(&'static str, Duration)>,
+    current: Option<Phase>,
+    workers: Vec<WorkerStats>,
+}
+
+struct Phase {
+    name: &'static str,
+    started: Instant,
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Timings {
+        Timings { enabled, phases: vec![], current: None, workers: vec![] }
+    }
+
+    pub fn workers(&mut self, stats: Vec<WorkerStats>) {
+        self.workers = stats;
+    }
+
+    // Ends the current phase, if any, and starts timing the next one.
+    pub fn phase(&mut self, name: &'static str) {
+        self.end();
+        self.current = Some(Phase {
+            name,
+            started: Instant::now(),
+            #[cfg(feature = "tracing")]
+            _span: tracing::info_span!("phase", name).entered(),
+        });
+    }
+
+    fn end(&mut self) {
+        if let Some(phase) = self.current.take() {
+            self.phases.push((phase.name, phase.started.elapsed()));
+        }
+    }
+}
+
+impl Drop for Timings {
+    fn drop(&mut self) {
+        self.end();
+        if !self.enabled {
+            return;
+        }
+        let total: Duration = self.phases.iter().map(|&(_, d)| d).sum();
+        let row = |name: &str, d: Duration| {
+            eprintln!(" {0: <12} {1: >10.1}ms {2: >6.1}%",
+                      name,
+                      d.as_secs_f64() * 1000.0,
+                      100.0 * d.as_secs_f64() / total.as_secs_f64().max(f64::MIN_POSITIVE));
+        };
+        eprintln!();
+        eprintln!("Timings:");
+        for &(name, d) in &self.phases {
+            row(name, d);
+        }
+        row("total", total);
+
+        if self.workers.is_empty() {
+            return;
+        }
+        eprintln!();
+        eprintln!("Workers:         files      read        busy");
+        for (i, w) in self.workers.iter().enumerate() {
+            eprintln!(" {0: <12} {1: >8} {2: >9} {3: >9.1}ms", i + 1, w.files, human_size(w.bytes), w.busy.as_secs_f64() * 1000.0);
+        }
+        let busy: Vec<f64> = self.workers.iter().map(|w| w.busy.as_secs_f64()).collect();
+        let mean = busy.iter().sum::<f64>() / busy.len() as f64;
+        let max = busy.iter().cloned().fold(0.0, f64::max);
+        if mean > 0.0 {
+            eprintln!(" The busiest worker was busy {:.1}x as long as the average", max / mean);
+        }
+    }
+}
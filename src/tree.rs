@@ -0,0 +1,77 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+use loc::*;
+
+use worker::FileCount;
+
+// The counted files as a directory tree, for the reports that draw one.  Each directory's
+// numbers include everything under it.
+#[derive(Default)]
+pub struct Dir {
+    pub name: String,
+    pub files: u32,
+    pub count: Count,
+    pub code_by_lang: HashMap<Lang, u32>,
+    // By name, so output comes out the same every time
+    pub dirs: BTreeMap<String, Dir>,
+    // The files directly in this directory, by name
+    pub files_here: BTreeMap<String, (Lang, Count)>,
+}
+
+impl Dir {
+    // The language most of the code under here is in.  Ties go to the name that sorts
+    // first.
+    pub fn mostly(&self) -> Option<Lang> {
+        self.code_by_lang.iter()
+            .filter(|&(_, &code)| code > 0)
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.to_s().cmp(a.0.to_s())))
+            .map(|(&lang, _)| lang)
+    }
+
+    fn add(&mut self, lang: Lang, count: &Count) {
+        self.files += 1;
+        self.count.merge(count);
+        *self.code_by_lang.entry(lang).or_insert(0) += count.code;
+    }
+}
+
+// Rooted at the deepest directory all the files share, named the way the paths have it
+// ("." when counting the current directory).  files gives every file each time it's called,
+// since it takes a look at them all before it can start.
+pub fn build<I, F>(files: F) -> Dir
+    where F: Fn() -> I,
+          I: Iterator<Item = FileCount>
+{
+    let clean = |path: &str| -> PathBuf {
+        Path::new(path).components().filter(|c| *c != Component::CurDir).collect()
+    };
+    let mut root_path: Option<PathBuf> = None;
+    for fc in files() {
+        let path = clean(&fc.path);
+        let root = root_path.get_or_insert_with(|| path.parent().map(Path::to_path_buf).unwrap_or_default());
+        while !path.starts_with(&root) {
+            *root = root.parent().map(Path::to_path_buf).unwrap_or_default();
+        }
+    }
+    let root_path = root_path.unwrap_or_default();
+
+    let mut root = Dir {
+        name: if root_path.as_os_str().is_empty() { String::from(".") } else { root_path.display().to_string() },
+        ..Dir::default()
+    };
+    for fc in files() {
+        let path = clean(&fc.path);
+        let rel = path.strip_prefix(&root_path).unwrap_or(&path);
+        let mut dir = &mut root;
+        dir.add(fc.lang, &fc.count);
+        for part in rel.parent().into_iter().flat_map(Path::iter) {
+            let part = part.to_string_lossy().into_owned();
+            dir = dir.dirs.entry(part.clone()).or_insert_with(|| Dir { name: part, ..Dir::default() });
+            dir.add(fc.lang, &fc.count);
+        }
+        let name = rel.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        dir.files_here.insert(name, (fc.lang, fc.count));
+    }
+    root
+}
@@ -0,0 +1,500 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::iter;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use ignore::{DirEntry, WalkBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+
+use loc::*;
+use loc::sniff::{sniff, Sniffed, SNIFF_LEN};
+
+use config::Configs;
+use detect;
+use filter::PathFilter;
+
+pub struct WalkOptions {
+    pub use_ignore: bool,
+    pub ignore_hidden: bool,
+    // The user's global gitignore (core.excludesFile) and the repo's .git/info/exclude.
+    // Both are also off whenever use_ignore is.
+    pub git_global: bool,
+    pub git_exclude: bool,
+    // Follow symlinks and, on Windows, directory junctions and cloud placeholder files.
+    pub follow: bool,
+    // Only count files belonging to this uid (--owned-by).
+    pub owner: Option<u32>,
+    pub path_filter: Arc<PathFilter>,
+    pub configs: Arc<Configs>,
+    // loc's own files (--output reports, the cache...), as absolute paths.  Never counted,
+    // or every run would count the last one's report.
+    pub own_files: Vec<PathBuf>,
+}
+
+// Every target's files, one after the other.  Stops loc when there are more than
+// max_files of them (0 for no limit), so running on / or a backup drive by mistake doesn't
+// take all afternoon.
+pub fn all_files<'a>(targets: &'a [&str], opts: &'a WalkOptions, max_files: usize) -> impl Iterator<Item = String> + 'a {
+    targets.iter()
+        .flat_map(move |target| files(target, opts))
+        .enumerate()
+        .map(move |(i, path)| {
+            if max_files > 0 && i == max_files {
+                println!("Error: found more than {} files, stopping. Pass a bigger --max-files (0 for no \
+                          limit) if you really meant to count all of them", max_files);
+                std::process::exit(1);
+            }
+            path
+        })
+}
+
+// --files-from: the paths listed in source (- for stdin), one per line or with nul
+// between them, in place of walking anything.  Whoever made the list already decided
+// what's in it, so ignore files don't come into it, though --exclude and the like still
+// do.  Directories (find lists those too) are left out.
+pub fn listed_files<'a>(source: &str, nul: bool, opts: &'a WalkOptions) -> io::Result<Box<dyn Iterator<Item = String> + 'a>> {
+    let reader: Box<dyn BufRead> = if source == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(source)?))
+    };
+    let source = source.to_string();
+    Ok(Box::new(reader.split(if nul { b'\0' } else { b'\n' })
+        .map(move |read| match read {
+            Ok(line) => line,
+            Err(e) => {
+                println!("Error: --files-from {}: {}", source, e);
+                std::process::exit(1);
+            }
+        })
+        .map(move |line| {
+            let path = String::from_utf8_lossy(&line).into_owned();
+            if nul { path } else { path.trim_end_matches('\r').to_string() }
+        })
+        .filter(|path| !path.is_empty() && !Path::new(path).is_dir())
+        .filter(move |path| opts.path_filter.keep_entry(Path::new(path), false))))
+}
+
+// Paths of every regular file under target that survives the ignore rules.  target may
+// also be a glob (see split_glob), or a file, which is counted whatever the ignore rules
+// and filters say about it: whoever named it meant it.
+pub fn files<'a>(target: &str, opts: &'a WalkOptions) -> Box<dyn Iterator<Item = String> + 'a> {
+    let (root, glob) = split_glob(target);
+    if glob.is_none() && Path::new(root).is_file() {
+        return Box::new(iter::once(root.to_string()));
+    }
+    let path_filter = opts.path_filter.clone();
+    let configs = opts.configs.clone();
+    let own_files = own_files_under(root, &opts.own_files);
+    // TODO(cgag): use WalkParallel?
+    let mut builder = WalkBuilder::new(root);
+    builder.ignore(opts.use_ignore)
+           .git_ignore(opts.use_ignore)
+           .git_global(opts.use_ignore && opts.git_global)
+           .git_exclude(opts.use_ignore && opts.git_exclude)
+           .hidden(opts.ignore_hidden)
+           .follow_links(opts.follow)
+           .filter_entry(move |entry| {
+               // Always walk a target we were explicitly given
+               entry.depth() == 0 || (path_filter.keep_entry(entry.path(), is_dir(entry)) &&
+                                      !configs.excluded(entry.path(), is_dir(entry)) &&
+                                      !own_files.iter().any(|own| own == entry.path()))
+           });
+    if let Some(glob) = glob {
+        builder.overrides(glob_override(root, &glob));
+    }
+    let walker = builder.build();
+    Box::new(walker
+        // The ignore crate reports symlink/junction loops as errors when following,
+        // so skipping errors here is what keeps --follow from spinning forever.  On
+        // shared machines most of these are permission errors, which shouldn't abort
+        // the whole scan either.
+        .filter_map(|result| match result {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                None
+            }
+        })
+        .filter(|entry| entry.file_type().expect("no filetype").is_file())
+        .filter(move |entry| opts.follow || !is_placeholder(entry))
+        .filter(move |entry| opts.owner.is_none_or(|uid| is_owned_by(entry, uid)))
+        .map(|entry| String::from(entry.path().to_str().unwrap())))
+}
+
+// path made absolute, without needing it to exist yet: a report is usually written after
+// the count, the first time anyway.
+pub fn own_file(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Some(dir.canonicalize().ok()?.join(path.file_name()?))
+}
+
+// own_files that are under root, spelled the way the walk will come across them
+// (root/report.html rather than /home/me/src/report.html), so pruning them is a plain
+// comparison.
+fn own_files_under(root: &str, own_files: &[PathBuf]) -> Vec<PathBuf> {
+    let abs_root = match Path::new(root).canonicalize() {
+        Ok(abs_root) => abs_root,
+        Err(_) => return vec![],
+    };
+    own_files.iter()
+             .filter_map(|own| own.strip_prefix(&abs_root).ok())
+             .filter(|rel| !rel.as_os_str().is_empty())
+             .map(|rel| Path::new(root).join(rel))
+             .collect()
+}
+
+// How many files under the targets didn't get counted, by why not.
+#[derive(Default)]
+pub struct Skipped {
+    pub ignored: usize,
+    pub hidden: usize,
+    pub filtered: usize,
+    pub not_owned: usize,
+    // Files we don't know the language of, by what sniff makes of them, and the ones we
+    // couldn't open to find out
+    pub unrecognized_text: usize,
+    pub binary: usize,
+    pub data: usize,
+    pub unrecognized: usize,
+    pub unreadable: usize,
+    pub marked_ignored: usize,
+    pub lfs_pointers: usize,
+    pub duplicates: usize,
+}
+
+// Walks the targets again with nothing ignored, and works out why each file that isn't in
+// walked (what the real walk produced) was left out.  Of the walked files, the ones we
+// don't know the language of are counted too; the rest only the workers know about.
+pub fn census(targets: &[&str], opts: &WalkOptions, walked: &HashSet<String>) -> Skipped {
+    let mut skipped = Skipped::default();
+    for target in targets {
+        let (root, glob) = split_glob(target);
+        let mut builder = WalkBuilder::new(root);
+        builder.standard_filters(false)
+               .follow_links(opts.follow)
+               .filter_entry(|entry| entry.file_name() != ".git");
+        if let Some(glob) = glob {
+            builder.overrides(glob_override(root, &glob));
+        }
+
+        for entry in builder.build().filter_map(Result::ok) {
+            if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+                continue;
+            }
+            let path = match entry.path().to_str() {
+                Some(path) => path,
+                None => continue,
+            };
+            if walked.contains(path) {
+                if detect::lang(&opts.configs, path) == Lang::Unrecognized {
+                    match read_head(entry.path()).map(|head| sniff(&head)) {
+                        Ok(Sniffed::Text)      => skipped.unrecognized_text += 1,
+                        Ok(Sniffed::Binary(_)) => skipped.binary += 1,
+                        Ok(Sniffed::Data)      => skipped.data += 1,
+                        Err(_)                 => skipped.unrecognized += 1,
+                    }
+                }
+            } else if opts.ignore_hidden && is_hidden_under(entry.path(), root) {
+                skipped.hidden += 1;
+            } else if is_filtered(entry.path(), root, opts) {
+                skipped.filtered += 1;
+            } else if opts.owner.is_some_and(|uid| !is_owned_by(&entry, uid)) {
+                skipped.not_owned += 1;
+            } else {
+                skipped.ignored += 1;
+            }
+        }
+    }
+    skipped
+}
+
+fn read_head(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn is_hidden_under(path: &Path, root: &str) -> bool {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    rel.components().any(|c| match c {
+        Component::Normal(name) => name.to_str().is_some_and(|n| n.starts_with('.')),
+        _ => false,
+    })
+}
+
+// Whether --include/--exclude, the glob flags, or a .loc.toml would have pruned path or
+// any directory it's in.
+fn is_filtered(path: &Path, root: &str, opts: &WalkOptions) -> bool {
+    if !opts.path_filter.keep_entry(path, false) || opts.configs.excluded(path, false) {
+        return true;
+    }
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != Path::new(root) && !dir.as_os_str().is_empty())
+        .any(|dir| !opts.path_filter.keep_entry(dir, true) || opts.configs.excluded(dir, true))
+}
+
+// The top of the git repository the current directory is in, as a relative path so the
+// output doesn't fill up with absolute ones.  A .git file counts too, for worktrees and
+// submodules.
+pub fn repo_root() -> Option<String> {
+    let cwd = env::current_dir().ok()?;
+    let (up, _) = cwd.ancestors().enumerate().find(|&(_, dir)| dir.join(".git").exists())?;
+    if up == 0 {
+        Some(String::from("."))
+    } else {
+        Some(vec![".."; up].join("/"))
+    }
+}
+
+// Windows shells don't expand globs, so `loc src/**/*.rs` reaches us as-is.  If a target
+// doesn't exist and looks like a glob we split it into the literal directory to walk and
+// the rest of the pattern, anchored to that directory.
+pub fn split_glob(target: &str) -> (&str, Option<String>) {
+    let is_glob = |s: &str| s.contains(['*', '?', '[', '{']);
+    if !is_glob(target) || Path::new(target).exists() {
+        return (target, None);
+    }
+
+    let mut root = "";
+    let mut rest = target;
+    for (i, c) in target.char_indices() {
+        if !std::path::is_separator(c) {
+            continue;
+        }
+        if is_glob(&target[..i]) {
+            break;
+        }
+        root = &target[..i];
+        rest = &target[i + 1..];
+    }
+    if root.is_empty() {
+        root = if target.starts_with(std::path::is_separator) { &target[..1] } else { "." };
+    }
+    let glob = rest.split(std::path::is_separator).collect::<Vec<_>>().join("/");
+    (root, Some(format!("/{}", glob)))
+}
+
+// Shells expand `src/*` to directories too, which we'd then count recursively.
+fn glob_override(root: &str, glob: &str) -> Override {
+    let mut builder = OverrideBuilder::new(root);
+    for pattern in &[glob.to_string(), format!("{}/**", glob)] {
+        if let Err(e) = builder.add(pattern) {
+            println!("Error: bad glob '{}': {}", glob.trim_start_matches('/'), e);
+            std::process::exit(1);
+        }
+    }
+    match builder.build() {
+        Ok(o) => o,
+        Err(e) => {
+            println!("Error: bad glob '{}': {}", glob.trim_start_matches('/'), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The directory depth levels under whichever target path is in: src for src/walk/mod.rs
+// at depth 1, src/walk at 2.  A file less deep than that goes under the directory it's in,
+// which is "." for files sitting in a target itself.
+pub fn dir_at_depth(path: &str, targets: &[&str], depth: usize) -> String {
+    for target in targets {
+        if let Ok(rest) = Path::new(path).strip_prefix(target) {
+            let dirs = rest.parent().map(|dir| dir.components().count()).unwrap_or(0);
+            let dir: PathBuf = rest.components().take(dirs.min(depth)).collect();
+            return if dir.as_os_str().is_empty() {
+                String::from(".")
+            } else {
+                dir.to_string_lossy().into_owned()
+            };
+        }
+    }
+    String::from(".")
+}
+
+fn is_dir(entry: &DirEntry) -> bool {
+    entry.file_type().is_some_and(|ft| ft.is_dir())
+}
+
+// OneDrive (and other cloud sync) placeholders look like regular files, but reading
+// one makes Windows download its contents first.  std already reports junctions as
+// symlinks, so those are skipped unless we're following links.
+#[cfg(windows)]
+fn is_placeholder(entry: &DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_OFFLINE: u32               = 0x0000_1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32        = 0x0004_0000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+
+    match entry.metadata() {
+        Ok(md) => md.file_attributes() & (FILE_ATTRIBUTE_OFFLINE
+                                          | FILE_ATTRIBUTE_RECALL_ON_OPEN
+                                          | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(windows))]
+fn is_placeholder(_entry: &DirEntry) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_owned_by(entry: &DirEntry, uid: u32) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    entry.metadata().map(|md| md.uid() == uid).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_owned_by(_entry: &DirEntry, _uid: u32) -> bool {
+    true
+}
+
+// Accepts a user name or a numeric uid.
+#[cfg(unix)]
+pub fn lookup_uid(user: &str) -> Option<u32> {
+    use std::ffi::CString;
+
+    if let Ok(uid) = user.parse() {
+        return Some(uid);
+    }
+    let name = CString::new(user).ok()?;
+    // getpwnam isn't reentrant, but this runs once before any workers start.
+    let pw = unsafe { libc::getpwnam(name.as_ptr()) };
+    if pw.is_null() {
+        None
+    } else {
+        Some(unsafe { (*pw).pw_uid })
+    }
+}
+
+#[cfg(not(unix))]
+pub fn lookup_uid(_user: &str) -> Option<u32> {
+    None
+}
+
+// Prints every ignore source that applies to target, for --explain-ignores.
+pub fn explain_ignores(target: &str, opts: &WalkOptions) {
+    println!("Ignore sources for {}:", target);
+
+    if !opts.use_ignore {
+        println!("  none: ignore files are disabled by -u");
+    } else {
+        let global = global_gitignore_path();
+        match global {
+            Some(ref path) if opts.git_global && path.is_file() => {
+                let (gi, _) = Gitignore::global();
+                println!("  global gitignore:  {} ({} rules)", path.display(), gi.len());
+            }
+            Some(ref path) if opts.git_global => {
+                println!("  global gitignore:  {} (not found)", path.display());
+            }
+            _ if opts.git_global => println!("  global gitignore:  none configured"),
+            _ => println!("  global gitignore:  disabled by --no-ignore-global"),
+        }
+
+        match git_dir(Path::new(target)).map(|dir| dir.join("info").join("exclude")) {
+            Some(ref path) if opts.git_exclude && path.is_file() => {
+                println!("  .git/info/exclude: {} ({} rules)", path.display(), rule_count(path));
+            }
+            Some(ref path) if opts.git_exclude => {
+                println!("  .git/info/exclude: {} (not found)", path.display());
+            }
+            _ if opts.git_exclude => println!("  .git/info/exclude: not in a git repository"),
+            _ => println!("  .git/info/exclude: disabled by --no-ignore-exclude"),
+        }
+
+        for path in ignore_files(target, opts) {
+            println!("  ignore file:       {} ({} rules)", path.display(), rule_count(&path));
+        }
+    }
+
+    if opts.ignore_hidden {
+        println!("  hidden files and directories are skipped (use -uu to count them)");
+    }
+    println!();
+}
+
+// .gitignore and .ignore files that the walker will read: those in the target's
+// parent directories plus any inside the tree that aren't themselves ignored.
+fn ignore_files(target: &str, opts: &WalkOptions) -> Vec<PathBuf> {
+    let is_ignore_file = |p: &Path| match p.file_name().and_then(|n| n.to_str()) {
+        Some(".gitignore") | Some(".ignore") => p.is_file(),
+        _ => false,
+    };
+
+    let mut found = vec![];
+    if let Ok(abs) = Path::new(target).canonicalize() {
+        for dir in abs.ancestors().skip(1) {
+            for name in &[".gitignore", ".ignore"] {
+                let path = dir.join(name);
+                if is_ignore_file(&path) {
+                    found.push(path);
+                }
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        found.reverse();
+    }
+
+    let walker = WalkBuilder::new(target).ignore(true)
+                                         .git_ignore(true)
+                                         .git_global(opts.git_global)
+                                         .git_exclude(opts.git_exclude)
+                                         .hidden(false)
+                                         .follow_links(opts.follow)
+                                         .filter_entry(|entry| entry.file_name() != ".git")
+                                         .build();
+    found.extend(walker.filter_map(Result::ok)
+                       .map(|entry| entry.into_path())
+                       .filter(|path| is_ignore_file(path)));
+    found
+}
+
+fn rule_count(path: &Path) -> usize {
+    let mut builder = GitignoreBuilder::new(path.parent().unwrap_or_else(|| Path::new("")));
+    builder.add(path);
+    builder.build().map(|gi| gi.len()).unwrap_or(0)
+}
+
+fn git_dir(target: &Path) -> Option<PathBuf> {
+    let abs = target.canonicalize().ok()?;
+    abs.ancestors()
+       .map(|dir| dir.join(".git"))
+       .find(|git| git.is_dir())
+}
+
+// Where git (and so the ignore crate) looks for the global gitignore.
+fn global_gitignore_path() -> Option<PathBuf> {
+    let configured = Command::new("git")
+        .args(["config", "--global", "--path", "--get", "core.excludesFile"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|s| PathBuf::from(s.trim()));
+    if configured.is_some() {
+        return configured;
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("git").join("ignore"))
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::mem;
+#[cfg(feature = "archives")]
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::SyncSender;
+use std::time::{Duration, Instant};
+
+use deque::{Stealer, Stolen};
+
+use loc::*;
+use loc::sniff::{looks_like_source, SNIFF_LEN};
+
+#[cfg(feature = "archives")]
+use archive;
+use cache::Cache;
+use config::Configs;
+use detect;
+#[cfg(feature = "archives")]
+use filter::PathFilter;
+use memory::{self, Budget, Overflow};
+use natlang;
+use throttle::Throttle;
+
+pub enum Work {
+    File(String),
+    Quit,
+}
+
+pub struct Worker {
+    pub chan: Stealer<Work>,
+    pub configs: Arc<Configs>,
+    // Whether to work out each file's FileMeta
+    pub meta: bool,
+    // Whether to work out what language each file's comments are in
+    pub comment_lang: bool,
+    // Whether to look for commented-out code (--dead-code)
+    pub dead_code: bool,
+    pub cache: Arc<Cache>,
+    pub throttle: Arc<Throttle>,
+    // Whether to check files we don't know the language of for ones that look like source
+    // (--strict-detection)
+    pub strict: bool,
+    // --format ndjson: each file goes here as soon as it's counted, instead of piling up
+    // in Counted
+    pub stream: Option<SyncSender<FileCount>>,
+    pub budget: Arc<Budget>,
+    // --archives: count the files in zip and tar archives that get past this filter, as
+    // they would have on disk
+    #[cfg(feature = "archives")]
+    pub archives: Option<Arc<PathFilter>>,
+}
+
+#[derive(Clone)]
+pub struct FileCount {
+    pub path: String,
+    pub lang: Lang,
+    pub count: Count,
+    pub meta: Option<FileMeta>,
+    // How many files this stands for: 1, but for a language's total from a saved report
+    // or one --max-memory rolled up, which have no path.
+    pub files: u32,
+    // Size on disk, of all of them for a total.  0 from a saved report, which hasn't got it.
+    pub bytes: u64,
+    // Its longest line, in bytes.  The longest of all of them for a total, 0 from a saved report.
+    pub max_line: u32,
+}
+
+// A file whose lines average over LONG_LINE bytes: minified bundles, data blobs and the
+// like, where the line count says next to nothing about how much is in there.
+pub struct LongLines {
+    pub path: String,
+    pub size: usize,
+    pub lines: u32,
+}
+
+const LONG_LINE: usize = 1000;
+
+// A file with merge conflicts nobody resolved.  Still counted, markers and both sides.
+pub struct Conflicted {
+    pub path: String,
+    pub conflicts: usize,
+}
+
+// Everything a worker found.  Git LFS pointers aren't counted, they're just collected so
+// they can be reported (or resolved with --lfs).
+#[derive(Default)]
+pub struct Counted {
+    pub files: Vec<FileCount>,
+    pub lfs_pointers: Vec<String>,
+    pub long_lines: Vec<LongLines>,
+    pub conflicted: Vec<Conflicted>,
+    // path -> what its comments are written in, for --comment-lang
+    pub comment_langs: Vec<(String, Option<&'static str>)>,
+    pub unreadable: usize,
+    // Files with a loc:ignore-file marker
+    pub marked_ignored: usize,
+    // Files in no language we know that look like source anyway, with --strict-detection
+    pub unrecognized_source: Vec<String>,
+    // A total per language of the files past --max-memory, when nothing lists files
+    pub rolled_up: Vec<FileCount>,
+    // One per worker, for --timings
+    pub stats: Vec<WorkerStats>,
+    pub dead_code: HashMap<Lang, DeadCode>,
+}
+
+// --dead-code: a language's comment lines, and how many look like commented-out code
+#[derive(Default, Clone, Copy)]
+pub struct DeadCode {
+    pub comment: u32,
+    pub dead: u32,
+}
+
+// What one worker got through, to show how evenly the work was spread
+#[derive(Default)]
+pub struct WorkerStats {
+    pub files: usize,
+    pub bytes: usize,
+    // Time spent on files, rather than waiting for them
+    pub busy: Duration,
+}
+
+impl Counted {
+    pub fn merge(&mut self, other: Counted) {
+        self.files.extend(other.files);
+        self.lfs_pointers.extend(other.lfs_pointers);
+        self.long_lines.extend(other.long_lines);
+        self.conflicted.extend(other.conflicted);
+        self.comment_langs.extend(other.comment_langs);
+        self.unreadable += other.unreadable;
+        self.marked_ignored += other.marked_ignored;
+        self.unrecognized_source.extend(other.unrecognized_source);
+        for fc in other.rolled_up {
+            memory::roll_up(&mut self.rolled_up, fc);
+        }
+        self.stats.extend(other.stats);
+        for (lang, d) in other.dead_code {
+            let total = self.dead_code.entry(lang).or_default();
+            total.comment += d.comment;
+            total.dead += d.dead;
+        }
+    }
+
+    // Changes how every path reads, for --image
+    pub fn relabel<F: Fn(String) -> String>(&mut self, shown: F) {
+        let paths = self.files.iter_mut().map(|fc| &mut fc.path)
+                              .chain(self.lfs_pointers.iter_mut())
+                              .chain(self.unrecognized_source.iter_mut())
+                              .chain(self.comment_langs.iter_mut().map(|(path, _)| path))
+                              .chain(self.long_lines.iter_mut().map(|f| &mut f.path))
+                              .chain(self.conflicted.iter_mut().map(|f| &mut f.path));
+        for path in paths {
+            *path = shown(mem::take(path));
+        }
+    }
+}
+
+// This concurrency pattern ripped directly from ripgrep
+impl Worker {
+    pub fn run(self) -> Counted {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("worker").entered();
+        let mut counted = Counted::default();
+        let mut stats = WorkerStats::default();
+        loop {
+            match self.chan.steal() {
+                // What causes these?
+                Stolen::Empty | Stolen::Abort => continue,
+                Stolen::Data(Work::Quit) => break,
+                Stolen::Data(Work::File(path)) => {
+                    self.budget.dequeue(&path);
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::debug_span!("file", path = &*path).entered();
+                    let started = Instant::now();
+                    stats.files += 1;
+                    let lang = detect::lang(&self.configs, &path);
+                    let kept = counted.files.len();
+                    if self.opens(&path) {
+                        stats.bytes += self.count_archive(path, &mut counted);
+                        self.pass_on(kept, &mut counted);
+                    } else if lang != Lang::Unrecognized {
+                        stats.bytes += self.count_file(path, lang, &mut counted);
+                        self.pass_on(kept, &mut counted);
+                    } else if self.strict {
+                        let head = read_head(&path);
+                        stats.bytes += head.len();
+                        if looks_like_source(&path, &head) {
+                            counted.unrecognized_source.push(path);
+                        }
+                    }
+                    stats.busy += started.elapsed();
+                }
+            };
+        }
+        counted.stats.push(stats);
+        counted
+    }
+
+    // What was just counted, everything in counted.files after the first kept: straight
+    // out with --format ndjson, or to the overflow if it's past --max-memory
+    fn pass_on(&self, kept: usize, counted: &mut Counted) {
+        if let Some(ref stream) = self.stream {
+            for fc in counted.files.drain(kept..) {
+                let _ = stream.send(fc);
+            }
+            return;
+        }
+        for fc in counted.files.split_off(kept) {
+            if self.budget.keep(&fc) {
+                counted.files.push(fc);
+            } else {
+                self.overflow(fc, counted);
+            }
+        }
+    }
+
+    fn overflow(&self, fc: FileCount, counted: &mut Counted) {
+        match self.budget.overflow {
+            Overflow::RollUp => memory::roll_up(&mut counted.rolled_up, fc),
+            Overflow::Spill(ref spill) => {
+                if let Err(e) = spill.write(&fc) {
+                    println!("Error: --max-memory: setting counts aside on disk: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    // Returns how many bytes it read, none when the cache had it
+    fn count_file(&self, path: String, lang: Lang, counted: &mut Counted) -> usize {
+        // --file-meta, --comment-lang and --dead-code need the contents anyway
+        let md = if self.cache.enabled() && !self.meta && !self.comment_lang && !self.dead_code {
+            fs::metadata(&path).ok()
+        } else {
+            None
+        };
+        if let Some(ref md) = md {
+            if let Some(hit) = self.cache.get(&path, md, (lang, self.configs.encoding(&path, lang))) {
+                let fc = FileCount { path, lang, count: hit.count, meta: None, files: 1, bytes: md.len(), max_line: hit.max_line };
+                found(counted, fc, md.len() as usize, hit.conflicts);
+                return 0;
+            }
+        }
+
+        let read = fs::read(&path);
+        let size = read.as_ref().map(Vec::len).unwrap_or(0);
+        if read.is_ok() {
+            self.throttle.read(size);
+        }
+        match read {
+            Ok(ref bytes) if is_lfs_pointer(bytes) => counted.lfs_pointers.push(path),
+            Ok(bytes) => self.count_contents(path, lang, &bytes, md.as_ref(), counted),
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {}", path, e);
+                counted.unreadable += 1;
+            }
+        }
+        size
+    }
+
+    // A file's contents, read off disk (and with its metadata, for the cache) or out of an
+    // archive
+    fn count_contents(&self, path: String, lang: Lang, bytes: &[u8], md: Option<&fs::Metadata>, counted: &mut Counted) {
+        // --file-meta still describes what's on disk
+        let encoding = self.configs.encoding(&path, lang);
+        let decoded = encoding.map(|charset| charset.decode(bytes));
+        let text = match strip_ignored(decoded.as_deref().unwrap_or(bytes)) {
+            Some(text) => text,
+            None => {
+                counted.marked_ignored += 1;
+                return;
+            }
+        };
+        let text = &*text;
+        let count = count_bytes(lang, text);
+        let conflicts = conflict_markers(text);
+        let max_line = longest_line(text);
+        if let Some(md) = md {
+            self.cache.insert(&path, md, (lang, encoding), &count, conflicts, max_line);
+        }
+        if self.comment_lang && count.comment > 0 {
+            counted.comment_langs.push((path.clone(), natlang::comment_lang(lang, text)));
+        }
+        if self.dead_code && count.comment > 0 {
+            if let Some(dead) = commented_out_code(lang, text) {
+                let d = counted.dead_code.entry(lang).or_default();
+                d.comment += count.comment;
+                d.dead += dead;
+            }
+        }
+        let meta = if self.meta { Some(file_meta(bytes)) } else { None };
+        let fc = FileCount { path, lang, count, meta, files: 1, bytes: bytes.len() as u64, max_line };
+        found(counted, fc, bytes.len(), conflicts);
+    }
+
+    // Whether path is an archive to count the files in, with --archives
+    #[cfg(feature = "archives")]
+    fn opens(&self, path: &str) -> bool {
+        self.archives.is_some() && archive::is_archive(path)
+    }
+
+    #[cfg(not(feature = "archives"))]
+    fn opens(&self, _path: &str) -> bool {
+        false
+    }
+
+    #[cfg(not(feature = "archives"))]
+    fn count_archive(&self, _path: String, _counted: &mut Counted) -> usize {
+        unreachable!("opens() is always false without the archives feature")
+    }
+
+    // --archives: each file in the archive, under the archive's path as if it were a
+    // directory.  Returns how many bytes it read.
+    #[cfg(feature = "archives")]
+    fn count_archive(&self, path: String, counted: &mut Counted) -> usize {
+        let filter = self.archives.as_ref().expect("only opened with --archives");
+        let mut size = 0;
+        let read = archive::members(&path, |name, bytes| {
+            size += bytes.len();
+            let member = format!("{}/{}", path, name);
+            let excluded = Path::new(&member).ancestors().enumerate().any(|(i, p)| self.configs.excluded(p, i > 0));
+            if !filter.is_match(&member) || excluded {
+                return;
+            }
+            let lang = detect::lang(&self.configs, &member);
+            if lang != Lang::Unrecognized && !is_lfs_pointer(&bytes) {
+                self.count_contents(member, lang, &bytes, None, counted);
+            }
+        });
+        if let Err(e) = read {
+            eprintln!("Warning: skipping {}: {}", path, e);
+            counted.unreadable += 1;
+        }
+        size
+    }
+}
+
+fn read_head(path: &str) -> Vec<u8> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(SNIFF_LEN as u64).read_to_end(&mut head);
+    }
+    head
+}
+
+// Files with mostly very long lines, or conflict markers, are counted but also noted.
+fn found(counted: &mut Counted, fc: FileCount, size: usize, conflicts: usize) {
+    if size > 10 * LONG_LINE && size / fc.count.lines.max(1) as usize > LONG_LINE {
+        counted.long_lines.push(LongLines {
+            path: fc.path.clone(),
+            size,
+            lines: fc.count.lines,
+        });
+    }
+    if conflicts > 0 {
+        counted.conflicted.push(Conflicted {
+            path: fc.path.clone(),
+            conflicts,
+        });
+    }
+    counted.files.push(fc);
+}
@@ -0,0 +1,2 @@
+char *s = "/* not a comment";
+int y;
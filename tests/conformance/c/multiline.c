@@ -0,0 +1,3 @@
+/* a
+   comment */ int x;
+int y;
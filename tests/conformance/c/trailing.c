@@ -0,0 +1,2 @@
+int x = 1; // trailing
+int y = 2; /* trailing */
@@ -0,0 +1,5 @@
+package main
+
+var s = `/* not a comment`
+
+func main() {}
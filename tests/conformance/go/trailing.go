@@ -0,0 +1,4 @@
+package main // trailing
+
+// a comment
+func main() {}
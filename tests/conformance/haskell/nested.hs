@@ -0,0 +1,4 @@
+{- outer
+   {- inner -}
+   still in the outer comment -}
+main = putStrLn "hi"
@@ -0,0 +1,2 @@
+main = putStrLn "hi" -- trailing
+-- a comment
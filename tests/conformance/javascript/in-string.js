@@ -0,0 +1,3 @@
+const s = "// not a comment";
+const t = '/* nor this';
+const u = 1;
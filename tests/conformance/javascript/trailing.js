@@ -0,0 +1,2 @@
+let x = 1; // trailing
+/* before */ let y = 2;
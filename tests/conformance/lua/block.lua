@@ -0,0 +1,3 @@
+--[[ a block
+comment ]]
+x = 1
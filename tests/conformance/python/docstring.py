@@ -0,0 +1,4 @@
+def f():
+    """A docstring,
+    over two lines."""
+    return 1
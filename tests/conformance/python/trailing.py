@@ -0,0 +1,4 @@
+x = 1  # trailing
+# a comment
+
+y = 2
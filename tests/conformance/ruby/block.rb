@@ -0,0 +1,4 @@
+=begin
+a block comment
+=end
+y = 1
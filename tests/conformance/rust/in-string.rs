@@ -0,0 +1,4 @@
+fn main() {
+    let s = "/* not a comment";
+    let u = "// nor this";
+}
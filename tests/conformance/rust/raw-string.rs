@@ -0,0 +1,5 @@
+fn main() {
+    let s = r#"
+// not a comment, in a raw string
+"#;
+}
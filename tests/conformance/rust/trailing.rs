@@ -0,0 +1,3 @@
+fn main() {
+    let t = 1; // a trailing comment is still code
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::redundant_static_lifetimes, clippy::identity_op)]
+
 extern crate loc;
 
 use loc::*;
@@ -225,3 +227,179 @@ test_count![SOLIDITY,
             solidity_comment,
             solidity_blank,
             solidity_lines];
+
+#[test]
+fn line_kinds_agree_with_count() {
+    let bytes = std::fs::read(IPL).unwrap();
+    let mut c = Count::default();
+    for kind in line_kinds(Lang::C, &bytes).unwrap() {
+        c.add_line(kind);
+    }
+    assert_eq!(IPL_EXPECTED, c);
+}
+
+#[test]
+fn shared_extensions_are_guesses() {
+    assert_eq!(Guess::Certain, guess("src/lib.rs"));
+    assert_eq!(Guess::Shebang, guess("bin/run"));
+    assert!(guess("include/foo.h").confidence() < 1.0);
+}
+
+#[test]
+fn conflict_markers_need_both_ends() {
+    let conflict = b"a\n<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> topic\n";
+    assert_eq!(1, conflict_markers(conflict));
+    assert_eq!(0, conflict_markers(b"<<<<<<< HEAD\nb\n"));
+    assert_eq!(0, conflict_markers(b"<<<<<<<<<<\n>>>>>>>>>>\n"));
+}
+
+#[test]
+fn count_from_str() {
+    let c = Count::from_str(Lang::Rust, "// generated\nfn main() {}\n\n");
+    assert_eq!(c, Count { code: 1, comment: 1, blank: 1, lines: 3 });
+}
+
+#[test]
+fn longest_line_leaves_out_line_endings() {
+    assert_eq!(5, longest_line(b"ab\r\nabcde\r\nabc"));
+    assert_eq!(0, longest_line(b""));
+}
+
+#[test]
+fn file_meta_encoding_and_endings() {
+    let meta = file_meta(b"\xEF\xBB\xBFa\r\nb\r\n");
+    assert_eq!((Encoding::Utf8, true, LineEndings::CrLf), (meta.encoding, meta.bom, meta.line_endings));
+    assert_eq!(LineEndings::Mixed, file_meta(b"a\nb\r\n").line_endings);
+    assert_eq!(Encoding::Other, file_meta(b"caf\xE9\n").encoding);
+}
+
+#[test]
+fn spans_split_code_strings_and_comments() {
+    let lines = line_spans(Lang::C, b"  x = \"/* no */\"; // yes\n").unwrap();
+    let kinds: Vec<SpanKind> = lines[0].spans.iter().map(|s| s.kind).collect();
+    assert_eq!(vec![SpanKind::Blank, SpanKind::Code, SpanKind::String, SpanKind::Code, SpanKind::Comment], kinds);
+    assert_eq!(LineKind::Code, lines[0].kind);
+}
+
+#[test]
+fn commented_out_code_isnt_prose() {
+    let c = b"// x = compute(y);\n// Works out the total, then returns it.\n/* old_call(a, b); */\n\
+              int y = 2; // z = 3;\n/*\n * if (done) {\n * Nothing else to see here.\n */\n";
+    assert_eq!(Some(3), commented_out_code(Lang::C, c));
+    assert_eq!(Some(1), commented_out_code(Lang::Python, b"# print(totals[lang])\n# just a note\n"));
+    assert!(looks_like_code("foo(bar, baz)"));
+    assert!(!looks_like_code("see parse (in walk.rs) for why"));
+}
+
+#[test]
+fn generated_corpus_counts_as_promised() {
+    let dir = std::env::temp_dir().join(format!("loc-corpus-{}", std::process::id()));
+    let expected = corpus::generate(&dir, Lang::Rust, 150, 7).unwrap();
+
+    let mut counted = Count::default();
+    for sub in std::fs::read_dir(&dir).unwrap() {
+        for file in std::fs::read_dir(sub.unwrap().path()).unwrap() {
+            counted.merge(&count(file.unwrap().path().to_str().unwrap()));
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(expected, counted);
+}
+
+// Found by fuzzing: multibyte comment markers used to slice strs mid-character
+#[test]
+fn multibyte_markers_dont_panic() {
+    let bytes = "=cut-}\n-}›{-(*--[[\r*)\"日本\n‹'=pod/*é\\<open>\\<open>‹\\›'`\n]]*/\n".as_bytes();
+    let c = count_bytes(Lang::Isabelle, bytes);
+    assert_eq!(4, c.lines);
+}
+
+#[test]
+fn ebcdic_decodes_before_counting() {
+    let ebcdic = charset::Charset::from_label("ebcdic").unwrap();
+    // "/* hi */\nint x;\n" in code page 037, with NL line ends
+    let bytes = b"\x61\x5C\x40\x88\x89\x40\x5C\x61\x15\x89\x95\xA3\x40\xA7\x5E\x15";
+    let c = count_bytes(Lang::C, &ebcdic.decode(bytes));
+    assert_eq!((1, 1, 2), (c.comment, c.code, c.lines));
+}
+
+#[test]
+fn lang_from_path_never_reads() {
+    assert_eq!(Lang::Makefile, lang_from_path("Makefile"));
+    assert_eq!(Lang::Unrecognized, lang_from_path("tests/data/no-such-script"));
+}
+
+#[test]
+fn sniff_sorts_unrecognized_files() {
+    use loc::sniff::{sniff, Sniffed};
+    assert_eq!(Sniffed::Text, sniff(b"just some notes\nabout things\n"));
+    assert_eq!(Sniffed::Binary("image/png"), sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+    assert_eq!(Sniffed::Data, sniff(b"\x01\x02\x00\x03garbage"));
+}
+
+#[test]
+fn ignore_markers_leave_regions_out() {
+    let src = b"fn a() {}\n// loc:ignore-start\nfn generated() {}\n// loc:ignore-end\nfn b() {}\n";
+    let c = count_bytes(Lang::Rust, &strip_ignored(src).unwrap());
+    assert_eq!((2, 2), (c.code, c.lines));
+    assert!(strip_ignored(b"# loc:ignore-file\nx = 1\n").is_none());
+    // a mention in prose isn't a marker
+    let prose: &[u8] = b"Mark it with `loc:ignore-start` to skip it.\nMore.\n";
+    assert_eq!(prose, &*strip_ignored(prose).unwrap());
+}
+
+#[test]
+fn looks_like_source_wants_code_shaped_text() {
+    use loc::sniff::looks_like_source;
+    let zig = b"const std = @import(\"std\");\npub fn main() void {\n    std.debug.print(\"hi\", .{});\n}\n";
+    assert!(looks_like_source("src/main.zig", zig));
+    assert!(!looks_like_source("notes.txt", zig));
+    assert!(!looks_like_source("notes.foo", b"just notes\nabout things\nand more\n"));
+    assert!(looks_like_source("run", b"#!/usr/bin/env janet\n(print 1)\n"));
+}
+
+#[test]
+fn doc_coverage_counts_documented_pub_items() {
+    use loc::doc_coverage::{module_path, scan, DocCoverage};
+    use std::path::Path;
+    let src = "\
+//! The module's own docs don't count for the first item
+pub fn undocumented() {}
+
+/// Documented
+#[derive(Debug)]
+#[cfg_attr(feature = \"serde\", derive(
+    Serialize,
+))]
+pub struct Documented {
+    pub field: u32,
+}
+
+/** Also documented */
+pub const fn also() {}
+
+#[doc = \"And this\"]
+pub extern \"C\" fn and_this() {}
+
+// Just a comment
+pub(crate) fn internal() {}
+pub use self::Documented as Reexported;
+pub mod nodocs;
+";
+    assert_eq!(DocCoverage { items: 5, documented: 3 }, scan(src));
+    assert_eq!("crate", module_path(Path::new("lib.rs")));
+    assert_eq!("crate::git", module_path(Path::new("git/mod.rs")));
+    assert_eq!("crate::git::revs", module_path(Path::new("git/revs.rs")));
+}
+
+#[test]
+fn indentation_only_lines_count_where_they_mean_something() {
+    // The tab-only line in the recipe is an empty command, the one after X := 1 isn't
+    let make = count_bytes(Lang::Makefile, b"all: build\n\techo hi\n\t\n\techo bye\n\nURL = http://x\n\t\n");
+    assert_eq!(Count { code: 5, comment: 0, blank: 2, lines: 7 }, make);
+    // Indented but empty lines are part of a block scalar's text, and blank anywhere else
+    let yaml = count_bytes(Lang::Yaml, b"a: |\n  one\n  \n  two\nb: 1\n  \nc: >-\n    folded\n");
+    assert_eq!(Count { code: 7, comment: 0, blank: 1, lines: 8 }, yaml);
+    let rust = count_bytes(Lang::Rust, b"fn main() {\n    \n}\n");
+    assert_eq!(Count { code: 2, comment: 0, blank: 1, lines: 3 }, rust);
+}
@@ -0,0 +1,5 @@
+#!/bin/sh
+# Builds everything.
+set -e
+
+cargo build --release
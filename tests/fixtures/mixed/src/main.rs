@@ -0,0 +1,13 @@
+// Entry point.
+/* Parses the arguments
+   and hands off to run(). */
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    run(&args);
+}
+
+fn run(args: &[String]) {
+    // "// not a comment" below
+    println!("// not a comment {}", args.len());
+}
@@ -0,0 +1,10 @@
+#!/usr/bin/env python3
+# Helpers for the build.
+
+def add(a, b):
+    # sum them
+    return a + b
+
+
+def sub(a, b):
+    return a - b
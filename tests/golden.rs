@@ -0,0 +1,577 @@
+// Runs the loc binary over the trees in tests/fixtures and tests/data and compares its
+// reports with the ones in tests/golden, so a refactor can't quietly change the numbers.
+// If a change is meant to change them, rerun with LOC_UPDATE_GOLDEN=1 and check the diff.
+extern crate loc;
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// A directory for a test to make its own tree in, gone when the test's done with it,
+// whether or not it passed
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Scratch {
+        let dir = env::temp_dir().join(format!("loc-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+}
+
+impl Deref for Scratch {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn golden(name: &str, args: &[&str]) {
+    // -u so nobody's global gitignore changes the results
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).arg("-u").args(args).output().unwrap();
+    assert!(out.status.success(), "loc {:?} failed: {}", args, String::from_utf8_lossy(&out.stderr));
+    let actual = String::from_utf8(out.stdout).unwrap();
+
+    let path = Path::new("tests/golden").join(format!("{}.txt", name));
+    if env::var_os("LOC_UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!("{}: {} (run with LOC_UPDATE_GOLDEN=1 to create it)", path.display(), e)
+    });
+    if expected != actual {
+        let mut diff = String::new();
+        for (i, (e, a)) in expected.lines().zip(actual.lines()).enumerate() {
+            if e != a {
+                diff.push_str(&format!("line {}:\n-{}\n+{}\n", i + 1, e, a));
+            }
+        }
+        let (e, a) = (expected.lines().count(), actual.lines().count());
+        if e != a {
+            diff.push_str(&format!("expected {} lines, got {}\n", e, a));
+        }
+        panic!("loc {:?} doesn't match {}:\n{}", args, path.display(), diff);
+    }
+}
+
+#[test]
+fn data_summary() {
+    golden("data-summary", &["tests/data"]);
+}
+
+#[test]
+fn data_files() {
+    golden("data-files", &["--files", "--sort", "lines", "tests/data"]);
+}
+
+#[test]
+fn mixed_summary() {
+    golden("mixed-summary", &["--sort", "language", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn data_sort_keys() {
+    golden("data-sort-keys", &["--sort", "files:desc,language", "tests/data"]);
+}
+
+#[test]
+fn data_files_sort_keys() {
+    golden("data-files-sort-keys", &["--files", "--sort", "language,code:asc", "tests/data"]);
+}
+
+#[test]
+fn mixed_by_dir() {
+    golden("mixed-by-dir", &["--by-dir", "tests/fixtures/mixed"]);
+}
+
+#[test]
+#[cfg(feature = "regex-filters")]
+fn mixed_group_by() {
+    golden("mixed-group-by", &["--group-by", r"\.(rs|py)$", "tests/fixtures/mixed"]);
+}
+
+#[test]
+#[cfg(feature = "archives")]
+fn archives() {
+    golden("archives", &["--archives", "--files", "--sort", "language", "tests/fixtures/archives"]);
+    golden("archives", &["--archives", "--max-memory", "100", "--files", "--sort", "language", "tests/fixtures/archives"]);
+}
+
+// A docker save tarball with one layer, and the reports relative to where loc runs, not
+// inside the unpacked image
+#[test]
+#[cfg(feature = "image")]
+fn image_reports_land_where_asked() {
+    let dir = Scratch::new("image");
+    fs::create_dir(dir.join("out")).unwrap();
+    let image = env::current_dir().unwrap().join("tests/fixtures/image.tar");
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).current_dir(&*dir)
+        .arg("--image").arg(&image).args(["--files", "--output", "report.json", "--output", "out/report.csv"])
+        .output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(dir.join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["total"]["files"], 2);
+    let csv = fs::read_to_string(dir.join("out/report.csv")).unwrap();
+    assert!(csv.contains("Python,./app/main.py,4,1,1,2"), "{}", csv);
+}
+
+// The files in an archive go through the same filters they would on disk, and one that
+// unpacks to something huge is skipped rather than read into memory
+#[test]
+#[cfg(feature = "archives")]
+fn archive_members_are_filtered() {
+    let paths = |args: &[&str]| -> Vec<String> {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc"))
+            .args(["-u", "--archives", "--format", "json", "--files", "tests/fixtures/archive-filters"]).args(args)
+            .output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        assert!(String::from_utf8_lossy(&out.stderr).contains("skipping bomb.py"));
+        let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        let mut paths: Vec<String> = report["languages"].as_array().unwrap().iter()
+            .flat_map(|lang| lang["by_file"].as_array().unwrap().iter())
+            .map(|file| file["path"].as_str().unwrap().trim_start_matches("tests/fixtures/archive-filters/").to_string())
+            .collect();
+        paths.sort();
+        paths
+    };
+    // secrets.yml is counted, but not listed
+    assert_eq!(paths(&[]), ["vend.zip/README.md", "vend.zip/lib/lib.rs", "vend.zip/target/gen.py"]);
+    assert_eq!(paths(&["--exclude-glob", "*.md", "--defaults"]), ["vend.zip/lib/lib.rs"]);
+    if cfg!(feature = "regex-filters") {
+        assert_eq!(paths(&["--include", r"lib\.rs"]), ["vend.zip/lib/lib.rs"]);
+        assert_eq!(paths(&["--exclude", r"README\.md"]), ["vend.zip/lib/lib.rs", "vend.zip/target/gen.py"]);
+    }
+}
+
+#[test]
+fn mixed_by_dir_depth() {
+    golden("mixed-by-dir-depth", &["--by-dir", "--dir-depth", "2", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_family() {
+    golden("mixed-family", &["--family", "--sort", "language", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_no_vendor() {
+    golden("mixed-no-vendor", &["--exclude-glob", "vendor/", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_json() {
+    golden("mixed-json", &["--format", "json", "--files", "--file-meta", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_html() {
+    golden("mixed-html", &["--format", "html", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn data_files_csv() {
+    golden("data-files-csv", &["--format", "csv", "--files", "--sort", "lines", "tests/data"]);
+}
+
+#[test]
+fn mixed_markdown() {
+    golden("mixed-markdown", &["--format", "md", "--files", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_plain72() {
+    golden("mixed-plain72", &["--format", "plain72", "--files", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_lang_ui_ja() {
+    golden("mixed-lang-ui-ja", &["--lang-ui", "ja", "--by-dir", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_prometheus() {
+    golden("mixed-prometheus", &["--format", "prometheus", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_treemap() {
+    golden("mixed-treemap", &["--format", "treemap-json", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn data_several_outputs() {
+    golden("data-several-outputs", &["--output", "md:-", "--output", "csv:-", "--sort", "lines", "tests/data"]);
+}
+
+#[test]
+fn mixed_report_as_markdown() {
+    // Printed again from a saved --format json --files report, without counting anything
+    golden("mixed-report-markdown", &["--format", "md", "--files", "tests/fixtures/reports/mixed.json"]);
+}
+
+#[test]
+fn renamed_report_reads_back() {
+    let dir = Scratch::new("renamed-report");
+    let report = dir.join("report.json");
+    let out = Command::new(env!("CARGO_BIN_EXE_loc"))
+        .args(["-u", "--format", "json", "--rename-lang", "Rust=Oxide", "--output"]).arg(&report)
+        .arg("tests/fixtures/mixed").output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["--format", "md"]).arg(&report).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stdout));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("| Oxide | 1 | 13 | 2 | 4 | 7 |"));
+}
+
+#[test]
+fn mixed_template() {
+    golden("mixed-template", &["--files", "--template", "{language}\\t{path}\\t{code}/{lines}", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_ndjson() {
+    // One file, since streamed lines come out in whatever order the workers finish
+    golden("mixed-ndjson", &["--format", "ndjson", "tests/fixtures/mixed/src/main.rs"]);
+}
+
+#[test]
+fn mixed_group_by_path() {
+    golden("mixed-group-by-path", &["tests/fixtures/mixed", "--group-by-path", "src/*", "vendor/", "docs/*"]);
+}
+
+#[test]
+fn data_percent() {
+    golden("data-percent", &["--percent", "tests/data"]);
+}
+
+#[test]
+fn data_columns() {
+    golden("data-columns", &["--columns", "code,comment", "tests/data"]);
+}
+
+#[test]
+fn mixed_files_columns() {
+    golden("mixed-files-columns", &["--files", "--columns", "lines,files,code", "tests/fixtures/mixed"]);
+}
+
+// Not a golden file, since it changes with every new flag.  Checks the parts wrappers lean on.
+#[test]
+fn capabilities() {
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).arg("--capabilities").output().unwrap();
+    assert!(out.status.success());
+    let caps: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(caps["schema"], 1);
+    assert!(caps["languages"].as_array().unwrap().iter().any(|l| l == "Rust"));
+    assert!(caps["formats"].as_array().unwrap().iter().any(|f| f == "ndjson"));
+    let flags = caps["flags"].as_array().unwrap();
+    let columns = flags.iter().find(|f| f["long"] == "columns").expect("no --columns");
+    assert_eq!(columns["takes_value"], true);
+    let format = flags.iter().find(|f| f["long"] == "format").expect("no --format");
+    assert!(format["values"].as_array().unwrap().iter().any(|v| v == "json"));
+    assert!(flags.iter().any(|f| f["long"] == "files" && f["takes_value"] == false));
+}
+
+#[test]
+fn own_output_isnt_counted() {
+    let dir = Scratch::new("own-output");
+    fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let report = dir.join("report.json");
+    // Twice, so the second run has the first one's report sitting in the tree
+    for _ in 0..2 {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc")).arg("-u").arg("--output").arg(&report).arg(&*dir)
+            .output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    }
+    let report: serde_json::Value = serde_json::from_slice(&fs::read(&report).unwrap()).unwrap();
+    assert_eq!(report["total"]["files"], 1);
+}
+
+#[test]
+fn named_file_is_counted_when_ignored() {
+    let dir = Scratch::new("named-file");
+    fs::write(dir.join(".ignore"), "gen.rs\n").unwrap();
+    fs::write(dir.join("gen.rs"), "fn main() {}\n").unwrap();
+    // Not through golden(), since its -u would make this pass anyway
+    let out = Command::new(env!("CARGO_BIN_EXE_loc"))
+        .args(["--format", "json", "--exclude-glob", "*.rs"]).arg(dir.join("gen.rs"))
+        .output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(report["total"]["code"], 1);
+}
+
+#[test]
+fn defaults_skip_build_output() {
+    let dir = Scratch::new("defaults");
+    for sub in &["src", "target/debug"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("target/debug/build.rs"), "fn build() {}\n").unwrap();
+    let files = |args: &[&str]| -> serde_json::Value {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["-u", "--format", "json"]).args(args).arg(&*dir)
+            .output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()["total"]["files"].clone()
+    };
+    assert_eq!(files(&[]), 2);
+    assert_eq!(files(&["--defaults"]), 1);
+    assert_eq!(files(&["--defaults", "--no-default-excludes"]), 2);
+}
+
+#[test]
+fn named_targets() {
+    let dir = Scratch::new("named-targets");
+    for sub in &["services/billing", "libs", "docs"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(dir.join("services/billing/main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.join("libs/lib.rs"), "pub fn f() {}\n").unwrap();
+    fs::write(dir.join("docs/notes.rs"), "fn notes() {}\n").unwrap();
+    fs::write(dir.join(".loc.toml"), "[targets]\nbackend = [\"services/\", \"libs/\"]\ndefault = [\"docs\"]\n").unwrap();
+    let files = |args: &[&str]| -> serde_json::Value {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc")).current_dir(dir.join("libs"))
+            .args(["--format", "json"]).args(args).output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()["total"]["files"].clone()
+    };
+    let (backend, default) = (files(&["@backend"]), files(&[]));
+    assert_eq!(backend, 2);
+    assert_eq!(default, 1);
+}
+
+#[test]
+fn files_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_loc"))
+        .args(["--format", "json", "--files-from", "-", "-0"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn().unwrap();
+    // With a directory in the list, the way find gives them, which is left out, and a file
+    // listed twice, which is only counted once
+    child.stdin.take().unwrap()
+        .write_all(b"tests/fixtures/mixed/src/main.rs\0tests/data\0tests/data/zig.zig\0./tests/data/zig.zig\0").unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(report["total"]["files"], 2);
+}
+
+#[test]
+fn conformance_snippets() {
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["--conformance", "tests/conformance"]).output().unwrap();
+    let report = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "{}", report);
+    assert!(report.contains(" 0 failed"), "{}", report);
+    // The built in ones are the same snippets, and don't need a checkout to be run from
+    let scratch = Scratch::new("conformance");
+    let built_in = Command::new(env!("CARGO_BIN_EXE_loc")).arg("--conformance").current_dir(&*scratch).output().unwrap();
+    assert!(built_in.status.success());
+    assert_eq!(String::from_utf8_lossy(&built_in.stdout).lines().last(), report.lines().last());
+}
+
+#[test]
+fn counts_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_loc"))
+        .args(["--format", "json", "--stdin-lang", "rust", "-"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"// hi\nfn main() {}\n\n").unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(report["languages"][0]["language"], "Rust");
+    assert_eq!(report["total"]["code"], 1);
+    assert_eq!(report["total"]["comment"], 1);
+}
+
+#[test]
+fn only_takes_the_rest_from_the_cache() {
+    let dir = Scratch::new("only");
+    for sub in &["tree/a", "tree/b", "cache"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    fs::write(dir.join("tree/a/a.rs"), "fn a() {}\n").unwrap();
+    fs::write(dir.join("tree/b/b.rs"), "fn b() {}\n").unwrap();
+    let code = |args: &[&str]| -> serde_json::Value {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc")).current_dir(&*dir).env("LOC_CACHE_DIR", dir.join("cache"))
+            .args(["-u", "--format", "json", "--cache"]).args(args).arg("tree").output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()["total"]["code"].clone()
+    };
+    let before = code(&[]);
+    // Both change, but only a is looked at again
+    fs::write(dir.join("tree/a/a.rs"), "fn a() {}\nfn aa() {}\n").unwrap();
+    fs::write(dir.join("tree/b/b.rs"), "fn b() {}\nfn bb() {}\n").unwrap();
+    let after = code(&["--only", "tree/a"]);
+    assert_eq!(before, 2);
+    assert_eq!(after, 3);
+}
+
+// What a file was decoded from is part of what's cached for it
+#[test]
+fn cache_follows_encodings() {
+    let dir = Scratch::new("cache-encodings");
+    for sub in &["src", "cache"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    let utf16: Vec<u8> = "x = 1\ny = 2\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    fs::write(dir.join("src/a.py"), utf16).unwrap();
+    let lines = |args: &[&str]| -> serde_json::Value {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc")).current_dir(&*dir).env("LOC_CACHE_DIR", dir.join("cache"))
+            .args(["-u", "--format", "json"]).args(args).arg("src").output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap()["total"]["lines"].clone()
+    };
+    let raw = lines(&["--cache"]);
+    fs::write(dir.join("src/.loc.toml"), "[encodings]\nPython = \"utf-16le\"\n").unwrap();
+    assert_eq!(lines(&["--cache"]), lines(&[]));
+    assert_ne!(lines(&[]), raw);
+}
+
+#[test]
+fn data_totals_only() {
+    golden("data-totals-only", &["--totals-only", "tests/data"]);
+    golden("data-code-only", &["--totals-only", "--code-only", "tests/data"]);
+}
+
+// A limit so low every file goes past it, which mustn't change a number
+#[test]
+fn max_memory_changes_nothing() {
+    golden("data-summary", &["--max-memory", "100", "tests/data"]);
+    golden("data-files", &["--max-memory", "100", "--files", "--sort", "lines", "tests/data"]);
+}
+
+// Enough files past --max-memory that they're sorted into several runs on disk, which
+// have to merge back into the same listings, and not be left behind
+#[test]
+fn max_memory_merges_runs() {
+    let dir = Scratch::new("spill");
+    let (src, tmp) = (dir.join("src"), dir.join("tmp"));
+    fs::create_dir(&tmp).unwrap();
+    for i in 0..3000 {
+        let sub = src.join(format!("d{}", i % 10));
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(sub.join(format!("f{}.{}", i, ["rs", "py", "c"][i % 3])), "x\n".repeat(i % 17 + 1)).unwrap();
+    }
+    let loc = |args: &[&str]| {
+        let out = Command::new(env!("CARGO_BIN_EXE_loc")).env("TMPDIR", &tmp).arg("-u").args(args).arg(&src).output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        out.stdout
+    };
+    for args in [&["--files"][..], &["--ordered", "--files"], &["--format", "json", "--files"], &["--by-dir"]] {
+        assert!(loc(args) == loc(&[args, &["--max-memory", "1K"]].concat()), "{:?}", args);
+    }
+    assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+}
+
+#[test]
+fn mixed_bytes() {
+    golden("mixed-bytes", &["--files", "--columns", "files,code,bytes", "tests/fixtures/mixed"]);
+    // Which has to survive a trip through --max-memory's spill file
+    golden("mixed-bytes", &["--max-memory", "100", "--files", "--columns", "files,code,bytes", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn mixed_line_lengths() {
+    golden("mixed-line-lengths", &["--files", "--columns", "code,avg-line,max-line", "tests/fixtures/mixed"]);
+    golden("mixed-line-lengths", &["--max-memory", "100", "--files", "--columns", "code,avg-line,max-line", "tests/fixtures/mixed"]);
+}
+
+#[test]
+fn data_dead_code() {
+    golden("data-dead-code", &["--dead-code", "tests/data"]);
+}
+
+#[test]
+fn data_top() {
+    golden("data-top", &["--top", "3", "tests/data"]);
+    // Which only the summary table folds
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["--top", "1", "--format", "md", "tests/data"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("Error: --top"));
+}
+
+#[test]
+fn data_cocomo() {
+    golden("data-cocomo", &["--cocomo", "--avg-wage", "80000", "tests/data"]);
+}
+
+#[test]
+fn data_heat() {
+    golden("data-heat", &["--color", "always", "--heat", "tests/data"]);
+}
+
+#[test]
+fn timings_show_each_worker() {
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["-u", "--timings", "tests/fixtures/mixed"]).output().unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let workers = stderr.split("Workers:").nth(1).expect("no per-worker stats");
+    // Every file goes through some worker
+    let files: usize = workers.lines().skip(1)
+        .filter_map(|l| l.split_whitespace().nth(1)?.parse::<usize>().ok())
+        .sum();
+    assert_eq!(6, files);
+}
+
+#[test]
+fn max_files_on_limit() {
+    let loc = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_loc")).arg("-u").args(args).output().unwrap();
+    assert!(!loc(&["--max-files", "5", "tests/data"]).status.success());
+    // Past the limit it estimates from a sample of that many instead
+    let out = loc(&["--max-files", "5", "--on-limit", "sample", "tests/data"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("Estimated from 5 of"), "{}", String::from_utf8_lossy(&out.stdout));
+    // and under it counts everything as usual
+    let all = loc(&["tests/data"]);
+    assert_eq!(loc(&["--max-files", "1000", "--on-limit", "sample", "tests/data"]).stdout, all.stdout);
+}
+
+#[test]
+fn generated_corpus_is_ground_truth() {
+    // A tree of several languages whose counts are known up front, through the whole
+    // pipeline: walk, detection, counting and the report
+    let scratch = Scratch::new("corpus");
+    let mut expected = vec![];
+    for (i, &lang) in [loc::Lang::Rust, loc::Lang::Python, loc::Lang::C, loc::Lang::Lua, loc::Lang::Haskell].iter().enumerate() {
+        let count = loc::corpus::generate(&scratch.join(lang.to_s()), lang, 40, i as u64).unwrap();
+        expected.push((lang.to_s().to_string(), count));
+    }
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["-u", "--format", "json"]).arg(&*scratch).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let langs = report["languages"].as_array().unwrap();
+    assert_eq!(langs.len(), expected.len());
+    for (name, count) in expected {
+        let got = langs.iter().find(|l| l["language"] == name).unwrap_or_else(|| panic!("no {} in the report", name));
+        assert_eq!(got["files"], 40, "{}", name);
+        assert_eq!((got["code"].as_u64(), got["comment"].as_u64(), got["blank"].as_u64(), got["lines"].as_u64()),
+                   (Some(count.code as u64), Some(count.comment as u64), Some(count.blank as u64), Some(count.lines as u64)),
+                   "{}", name);
+    }
+}
+
+#[test]
+fn size_estimate_is_close() {
+    // On loc's own source, which is ordinary hand-written code, the bytes-per-line figures
+    // should land within 15% of the real line count
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["-u", "--estimate", "src"]).output().unwrap();
+    let table = String::from_utf8_lossy(&out.stdout);
+    let total = table.lines().find(|l| l.trim_start().starts_with("Total")).unwrap_or_else(|| panic!("{}", table));
+    let estimated: f64 = total.split_whitespace().last().unwrap().parse().unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_loc")).args(["-u", "--format", "json", "src"]).output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let lines = report["total"]["lines"].as_f64().unwrap();
+    assert!((estimated - lines).abs() <= lines * 0.15, "estimated {} lines, there are {}", estimated, lines);
+}